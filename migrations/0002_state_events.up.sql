@@ -0,0 +1,11 @@
+-- Create append-only journal of state events
+CREATE TABLE IF NOT EXISTS state_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    recorded_at INTEGER NOT NULL, -- Unix time in milliseconds
+    kind TEXT NOT NULL,
+    object_id INTEGER NOT NULL,
+    payload_json TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_state_events_object_id ON state_events(object_id, recorded_at);
+CREATE INDEX IF NOT EXISTS idx_state_events_kind ON state_events(kind, recorded_at);
@@ -0,0 +1,66 @@
+-- session_id references sessions, and SQLite can't drop a column used in a
+-- foreign key, so the tables are rebuilt without the identity columns.
+
+DROP INDEX IF EXISTS idx_state_events_session_id;
+DROP INDEX IF EXISTS idx_devices_natural_key;
+DROP INDEX IF EXISTS idx_nodes_natural_key;
+DROP INDEX IF EXISTS idx_clients_natural_key;
+
+CREATE TABLE state_events_unidentified (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    recorded_at INTEGER NOT NULL, -- Unix time in milliseconds
+    kind TEXT NOT NULL,
+    object_id INTEGER NOT NULL,
+    payload_json TEXT NOT NULL
+);
+INSERT INTO state_events_unidentified (id, recorded_at, kind, object_id, payload_json)
+SELECT id, recorded_at, kind, object_id, payload_json FROM state_events;
+DROP TABLE state_events;
+ALTER TABLE state_events_unidentified RENAME TO state_events;
+
+CREATE TABLE devices_unidentified (
+    object_id INTEGER PRIMARY KEY,
+    props_json TEXT NOT NULL,
+    profile_index INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO devices_unidentified (object_id, props_json, profile_index, created_at, updated_at)
+SELECT object_id, props_json, profile_index, created_at, updated_at FROM devices;
+DROP TABLE devices;
+ALTER TABLE devices_unidentified RENAME TO devices;
+
+CREATE TABLE nodes_unidentified (
+    object_id INTEGER PRIMARY KEY,
+    props_json TEXT NOT NULL,
+    volumes_json TEXT,
+    mute BOOLEAN,
+    peaks_json TEXT,
+    rate INTEGER,
+    positions_json TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO nodes_unidentified (object_id, props_json, volumes_json, mute, peaks_json, rate, positions_json, created_at, updated_at)
+SELECT object_id, props_json, volumes_json, mute, peaks_json, rate, positions_json, created_at, updated_at FROM nodes;
+DROP TABLE nodes;
+ALTER TABLE nodes_unidentified RENAME TO nodes;
+
+CREATE TABLE clients_unidentified (
+    object_id INTEGER PRIMARY KEY,
+    props_json TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO clients_unidentified (object_id, props_json, created_at, updated_at)
+SELECT object_id, props_json, created_at, updated_at FROM clients;
+DROP TABLE clients;
+ALTER TABLE clients_unidentified RENAME TO clients;
+
+CREATE INDEX IF NOT EXISTS idx_state_events_object_id ON state_events(object_id, recorded_at);
+CREATE INDEX IF NOT EXISTS idx_state_events_kind ON state_events(kind, recorded_at);
+CREATE INDEX IF NOT EXISTS idx_clients_object_id ON clients(object_id);
+CREATE INDEX IF NOT EXISTS idx_nodes_object_id ON nodes(object_id);
+CREATE INDEX IF NOT EXISTS idx_devices_object_id ON devices(object_id);
+
+DROP TABLE IF EXISTS sessions;
@@ -0,0 +1,27 @@
+-- Create sessions table, one row per wiremix connection to PipeWire
+CREATE TABLE IF NOT EXISTS sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at INTEGER NOT NULL, -- Unix time in milliseconds
+    ended_at INTEGER, -- NULL while running or after a crash
+    remote TEXT
+);
+
+-- Record identity that survives PipeWire reusing object IDs
+ALTER TABLE clients ADD COLUMN object_serial INTEGER;
+ALTER TABLE clients ADD COLUMN natural_key TEXT;
+ALTER TABLE clients ADD COLUMN session_id INTEGER REFERENCES sessions(id);
+
+ALTER TABLE nodes ADD COLUMN object_serial INTEGER;
+ALTER TABLE nodes ADD COLUMN natural_key TEXT;
+ALTER TABLE nodes ADD COLUMN session_id INTEGER REFERENCES sessions(id);
+
+ALTER TABLE devices ADD COLUMN object_serial INTEGER;
+ALTER TABLE devices ADD COLUMN natural_key TEXT;
+ALTER TABLE devices ADD COLUMN session_id INTEGER REFERENCES sessions(id);
+
+ALTER TABLE state_events ADD COLUMN session_id INTEGER REFERENCES sessions(id);
+
+CREATE INDEX IF NOT EXISTS idx_clients_natural_key ON clients(natural_key);
+CREATE INDEX IF NOT EXISTS idx_nodes_natural_key ON nodes(natural_key);
+CREATE INDEX IF NOT EXISTS idx_devices_natural_key ON devices(natural_key);
+CREATE INDEX IF NOT EXISTS idx_state_events_session_id ON state_events(session_id);
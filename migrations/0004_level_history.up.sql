@@ -0,0 +1,18 @@
+-- Create downsampled history of node levels
+CREATE TABLE IF NOT EXISTS level_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    node_id INTEGER NOT NULL,
+    natural_key TEXT,
+    session_id INTEGER REFERENCES sessions(id),
+    bucket_start INTEGER NOT NULL, -- Unix time in milliseconds
+    bucket_ms INTEGER NOT NULL, -- Bucket width in milliseconds
+    channel INTEGER NOT NULL,
+    min REAL NOT NULL,
+    max REAL NOT NULL,
+    mean REAL NOT NULL,
+    samples INTEGER NOT NULL, -- Number of peak readings in the bucket
+    UNIQUE(node_id, session_id, bucket_ms, bucket_start, channel)
+);
+
+CREATE INDEX IF NOT EXISTS idx_level_history_natural_key ON level_history(natural_key, bucket_start);
+CREATE INDEX IF NOT EXISTS idx_level_history_bucket ON level_history(bucket_ms, bucket_start);
@@ -0,0 +1,9 @@
+-- Create table of stream settings to restore, keyed by configurable match
+-- rules rather than object IDs
+CREATE TABLE IF NOT EXISTS stream_restore (
+    match_key TEXT PRIMARY KEY, -- For example "client:application.name=Firefox"
+    volumes_json TEXT,
+    mute BOOLEAN,
+    target TEXT, -- node.name of the target, or NULL to follow the default
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,14 @@
+ALTER TABLE metadata DROP COLUMN last_seen_at;
+ALTER TABLE metadata DROP COLUMN present;
+
+ALTER TABLE links DROP COLUMN last_seen_at;
+ALTER TABLE links DROP COLUMN present;
+
+ALTER TABLE devices DROP COLUMN last_seen_at;
+ALTER TABLE devices DROP COLUMN present;
+
+ALTER TABLE nodes DROP COLUMN last_seen_at;
+ALTER TABLE nodes DROP COLUMN present;
+
+ALTER TABLE clients DROP COLUMN last_seen_at;
+ALTER TABLE clients DROP COLUMN present;
@@ -0,0 +1,17 @@
+-- Track which rows belong to objects that currently exist. Objects removed
+-- while wiremix wasn't running are marked absent once enumeration finishes
+-- rather than being deleted, so their history stays queryable.
+ALTER TABLE clients ADD COLUMN present BOOLEAN NOT NULL DEFAULT 1;
+ALTER TABLE clients ADD COLUMN last_seen_at INTEGER; -- Unix time in milliseconds
+
+ALTER TABLE nodes ADD COLUMN present BOOLEAN NOT NULL DEFAULT 1;
+ALTER TABLE nodes ADD COLUMN last_seen_at INTEGER;
+
+ALTER TABLE devices ADD COLUMN present BOOLEAN NOT NULL DEFAULT 1;
+ALTER TABLE devices ADD COLUMN last_seen_at INTEGER;
+
+ALTER TABLE links ADD COLUMN present BOOLEAN NOT NULL DEFAULT 1;
+ALTER TABLE links ADD COLUMN last_seen_at INTEGER;
+
+ALTER TABLE metadata ADD COLUMN present BOOLEAN NOT NULL DEFAULT 1;
+ALTER TABLE metadata ADD COLUMN last_seen_at INTEGER;
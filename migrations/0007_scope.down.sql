@@ -0,0 +1,218 @@
+-- Rows from different scopes may share object IDs, so only one of them is
+-- kept for each object ID.
+
+DROP INDEX IF EXISTS idx_level_history_scope;
+DROP INDEX IF EXISTS idx_level_history_natural_key;
+DROP INDEX IF EXISTS idx_level_history_bucket;
+DROP INDEX IF EXISTS idx_state_events_scope;
+DROP INDEX IF EXISTS idx_metadata_properties_metadata_id;
+DROP INDEX IF EXISTS idx_device_enum_routes_device_id;
+DROP INDEX IF EXISTS idx_device_routes_device_id;
+DROP INDEX IF EXISTS idx_device_profiles_device_id;
+DROP INDEX IF EXISTS idx_links_input_id;
+DROP INDEX IF EXISTS idx_links_output_id;
+DROP INDEX IF EXISTS idx_metadata_name;
+DROP INDEX IF EXISTS idx_devices_natural_key;
+DROP INDEX IF EXISTS idx_nodes_natural_key;
+DROP INDEX IF EXISTS idx_clients_natural_key;
+
+ALTER TABLE sessions DROP COLUMN host;
+ALTER TABLE sessions DROP COLUMN scope;
+
+CREATE TABLE level_history_unscoped (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    node_id INTEGER NOT NULL,
+    natural_key TEXT,
+    session_id INTEGER REFERENCES sessions(id),
+    bucket_start INTEGER NOT NULL, -- Unix time in milliseconds
+    bucket_ms INTEGER NOT NULL, -- Bucket width in milliseconds
+    channel INTEGER NOT NULL,
+    min REAL NOT NULL,
+    max REAL NOT NULL,
+    mean REAL NOT NULL,
+    samples INTEGER NOT NULL, -- Number of peak readings in the bucket
+    UNIQUE(node_id, session_id, bucket_ms, bucket_start, channel)
+);
+INSERT OR IGNORE INTO level_history_unscoped (node_id, natural_key, session_id, bucket_start, bucket_ms, channel, min, max, mean, samples)
+SELECT node_id, natural_key, session_id, bucket_start, bucket_ms, channel, min, max, mean, samples FROM level_history;
+DROP TABLE level_history;
+ALTER TABLE level_history_unscoped RENAME TO level_history;
+CREATE INDEX IF NOT EXISTS idx_level_history_natural_key ON level_history(natural_key, bucket_start);
+CREATE INDEX IF NOT EXISTS idx_level_history_bucket ON level_history(bucket_ms, bucket_start);
+ALTER TABLE state_events DROP COLUMN scope;
+
+CREATE TABLE stream_restore_unscoped (
+    match_key TEXT PRIMARY KEY,
+    volumes_json TEXT,
+    mute BOOLEAN,
+    target TEXT,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+INSERT OR IGNORE INTO stream_restore_unscoped (match_key, volumes_json, mute, target, updated_at)
+SELECT match_key, volumes_json, mute, target, updated_at FROM stream_restore;
+DROP TABLE stream_restore;
+ALTER TABLE stream_restore_unscoped RENAME TO stream_restore;
+
+CREATE TABLE metadata_properties_unscoped (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    metadata_id INTEGER NOT NULL,
+    subject INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (metadata_id) REFERENCES metadata(object_id) ON DELETE CASCADE,
+    UNIQUE(metadata_id, subject, key)
+);
+INSERT OR IGNORE INTO metadata_properties_unscoped (metadata_id, subject, key, value, created_at)
+SELECT metadata_id, subject, key, value, created_at FROM metadata_properties;
+DROP TABLE metadata_properties;
+ALTER TABLE metadata_properties_unscoped RENAME TO metadata_properties;
+
+CREATE TABLE metadata_unscoped (
+    object_id INTEGER PRIMARY KEY,
+    metadata_name TEXT,
+    present BOOLEAN NOT NULL DEFAULT 1,
+    last_seen_at INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+INSERT OR IGNORE INTO metadata_unscoped (object_id, metadata_name, present, last_seen_at, created_at, updated_at)
+SELECT object_id, metadata_name, present, last_seen_at, created_at, updated_at FROM metadata;
+DROP TABLE metadata;
+ALTER TABLE metadata_unscoped RENAME TO metadata;
+
+CREATE TABLE links_unscoped (
+    object_id INTEGER PRIMARY KEY,
+    output_id INTEGER NOT NULL,
+    input_id INTEGER NOT NULL,
+    present BOOLEAN NOT NULL DEFAULT 1,
+    last_seen_at INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+INSERT OR IGNORE INTO links_unscoped (object_id, output_id, input_id, present, last_seen_at, created_at, updated_at)
+SELECT object_id, output_id, input_id, present, last_seen_at, created_at, updated_at FROM links;
+DROP TABLE links;
+ALTER TABLE links_unscoped RENAME TO links;
+
+CREATE TABLE device_enum_routes_unscoped (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    device_id INTEGER NOT NULL,
+    enum_route_index INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    available BOOLEAN NOT NULL,
+    profiles_json TEXT NOT NULL,
+    devices_json TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (device_id) REFERENCES devices(object_id) ON DELETE CASCADE,
+    UNIQUE(device_id, enum_route_index)
+);
+INSERT OR IGNORE INTO device_enum_routes_unscoped (device_id, enum_route_index, description, available, profiles_json, devices_json, created_at)
+SELECT device_id, enum_route_index, description, available, profiles_json, devices_json, created_at FROM device_enum_routes;
+DROP TABLE device_enum_routes;
+ALTER TABLE device_enum_routes_unscoped RENAME TO device_enum_routes;
+
+CREATE TABLE device_routes_unscoped (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    device_id INTEGER NOT NULL,
+    route_index INTEGER NOT NULL,
+    route_device INTEGER NOT NULL,
+    profiles_json TEXT NOT NULL,
+    description TEXT NOT NULL,
+    available BOOLEAN NOT NULL,
+    volumes_json TEXT NOT NULL,
+    mute BOOLEAN NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (device_id) REFERENCES devices(object_id) ON DELETE CASCADE,
+    UNIQUE(device_id, route_index)
+);
+INSERT OR IGNORE INTO device_routes_unscoped (device_id, route_index, route_device, profiles_json, description, available, volumes_json, mute, created_at)
+SELECT device_id, route_index, route_device, profiles_json, description, available, volumes_json, mute, created_at FROM device_routes;
+DROP TABLE device_routes;
+ALTER TABLE device_routes_unscoped RENAME TO device_routes;
+
+CREATE TABLE device_profiles_unscoped (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    device_id INTEGER NOT NULL,
+    profile_index INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    available BOOLEAN NOT NULL,
+    classes_json TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (device_id) REFERENCES devices(object_id) ON DELETE CASCADE,
+    UNIQUE(device_id, profile_index)
+);
+INSERT OR IGNORE INTO device_profiles_unscoped (device_id, profile_index, description, available, classes_json, created_at)
+SELECT device_id, profile_index, description, available, classes_json, created_at FROM device_profiles;
+DROP TABLE device_profiles;
+ALTER TABLE device_profiles_unscoped RENAME TO device_profiles;
+
+CREATE TABLE devices_unscoped (
+    object_id INTEGER PRIMARY KEY,
+    props_json TEXT NOT NULL,
+    profile_index INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    object_serial INTEGER,
+    natural_key TEXT,
+    session_id INTEGER REFERENCES sessions(id),
+    present BOOLEAN NOT NULL DEFAULT 1,
+    last_seen_at INTEGER
+);
+INSERT OR IGNORE INTO devices_unscoped (object_id, props_json, profile_index, created_at, updated_at, object_serial, natural_key, session_id, present, last_seen_at)
+SELECT object_id, props_json, profile_index, created_at, updated_at, object_serial, natural_key, session_id, present, last_seen_at FROM devices;
+DROP TABLE devices;
+ALTER TABLE devices_unscoped RENAME TO devices;
+
+CREATE TABLE nodes_unscoped (
+    object_id INTEGER PRIMARY KEY,
+    props_json TEXT NOT NULL,
+    volumes_json TEXT,
+    mute BOOLEAN,
+    peaks_json TEXT,
+    rate INTEGER,
+    positions_json TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    object_serial INTEGER,
+    natural_key TEXT,
+    session_id INTEGER REFERENCES sessions(id),
+    present BOOLEAN NOT NULL DEFAULT 1,
+    last_seen_at INTEGER
+);
+INSERT OR IGNORE INTO nodes_unscoped (object_id, props_json, volumes_json, mute, peaks_json, rate, positions_json, created_at, updated_at, object_serial, natural_key, session_id, present, last_seen_at)
+SELECT object_id, props_json, volumes_json, mute, peaks_json, rate, positions_json, created_at, updated_at, object_serial, natural_key, session_id, present, last_seen_at FROM nodes;
+DROP TABLE nodes;
+ALTER TABLE nodes_unscoped RENAME TO nodes;
+
+CREATE TABLE clients_unscoped (
+    object_id INTEGER PRIMARY KEY,
+    props_json TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    object_serial INTEGER,
+    natural_key TEXT,
+    session_id INTEGER REFERENCES sessions(id),
+    present BOOLEAN NOT NULL DEFAULT 1,
+    last_seen_at INTEGER
+);
+INSERT OR IGNORE INTO clients_unscoped (object_id, props_json, created_at, updated_at, object_serial, natural_key, session_id, present, last_seen_at)
+SELECT object_id, props_json, created_at, updated_at, object_serial, natural_key, session_id, present, last_seen_at FROM clients;
+DROP TABLE clients;
+ALTER TABLE clients_unscoped RENAME TO clients;
+
+CREATE INDEX IF NOT EXISTS idx_clients_object_id ON clients(object_id);
+CREATE INDEX IF NOT EXISTS idx_nodes_object_id ON nodes(object_id);
+CREATE INDEX IF NOT EXISTS idx_devices_object_id ON devices(object_id);
+CREATE INDEX IF NOT EXISTS idx_links_object_id ON links(object_id);
+CREATE INDEX IF NOT EXISTS idx_metadata_object_id ON metadata(object_id);
+CREATE INDEX IF NOT EXISTS idx_metadata_name ON metadata(metadata_name);
+CREATE INDEX IF NOT EXISTS idx_links_output_id ON links(output_id);
+CREATE INDEX IF NOT EXISTS idx_links_input_id ON links(input_id);
+CREATE INDEX IF NOT EXISTS idx_device_profiles_device_id ON device_profiles(device_id);
+CREATE INDEX IF NOT EXISTS idx_device_routes_device_id ON device_routes(device_id);
+CREATE INDEX IF NOT EXISTS idx_device_enum_routes_device_id ON device_enum_routes(device_id);
+CREATE INDEX IF NOT EXISTS idx_metadata_properties_metadata_id ON metadata_properties(metadata_id);
+CREATE INDEX IF NOT EXISTS idx_clients_natural_key ON clients(natural_key);
+CREATE INDEX IF NOT EXISTS idx_nodes_natural_key ON nodes(natural_key);
+CREATE INDEX IF NOT EXISTS idx_devices_natural_key ON devices(natural_key);
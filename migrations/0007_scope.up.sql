@@ -0,0 +1,241 @@
+-- Scope object rows by the wiremix instance and PipeWire remote that wrote
+-- them, so that several instances can share one database without overwriting
+-- each other's object IDs. Existing rows get an empty scope.
+--
+-- SQLite can't change a primary key in place, so the object tables are
+-- rebuilt.
+
+DROP INDEX IF EXISTS idx_clients_object_id;
+DROP INDEX IF EXISTS idx_nodes_object_id;
+DROP INDEX IF EXISTS idx_devices_object_id;
+DROP INDEX IF EXISTS idx_links_object_id;
+DROP INDEX IF EXISTS idx_metadata_object_id;
+DROP INDEX IF EXISTS idx_metadata_name;
+DROP INDEX IF EXISTS idx_links_output_id;
+DROP INDEX IF EXISTS idx_links_input_id;
+DROP INDEX IF EXISTS idx_device_profiles_device_id;
+DROP INDEX IF EXISTS idx_device_routes_device_id;
+DROP INDEX IF EXISTS idx_device_enum_routes_device_id;
+DROP INDEX IF EXISTS idx_metadata_properties_metadata_id;
+DROP INDEX IF EXISTS idx_clients_natural_key;
+DROP INDEX IF EXISTS idx_nodes_natural_key;
+DROP INDEX IF EXISTS idx_devices_natural_key;
+
+CREATE TABLE clients_scoped (
+    scope TEXT NOT NULL DEFAULT '',
+    object_id INTEGER NOT NULL,
+    props_json TEXT NOT NULL,
+    object_serial INTEGER,
+    natural_key TEXT,
+    session_id INTEGER REFERENCES sessions(id),
+    present BOOLEAN NOT NULL DEFAULT 1,
+    last_seen_at INTEGER, -- Unix time in milliseconds
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (scope, object_id)
+);
+INSERT INTO clients_scoped (object_id, props_json, object_serial, natural_key, session_id, present, last_seen_at, created_at, updated_at)
+SELECT object_id, props_json, object_serial, natural_key, session_id, present, last_seen_at, created_at, updated_at FROM clients;
+DROP TABLE clients;
+ALTER TABLE clients_scoped RENAME TO clients;
+
+CREATE TABLE nodes_scoped (
+    scope TEXT NOT NULL DEFAULT '',
+    object_id INTEGER NOT NULL,
+    props_json TEXT NOT NULL,
+    volumes_json TEXT,
+    mute BOOLEAN,
+    peaks_json TEXT,
+    rate INTEGER,
+    positions_json TEXT,
+    object_serial INTEGER,
+    natural_key TEXT,
+    session_id INTEGER REFERENCES sessions(id),
+    present BOOLEAN NOT NULL DEFAULT 1,
+    last_seen_at INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (scope, object_id)
+);
+INSERT INTO nodes_scoped (object_id, props_json, volumes_json, mute, peaks_json, rate, positions_json, object_serial, natural_key, session_id, present, last_seen_at, created_at, updated_at)
+SELECT object_id, props_json, volumes_json, mute, peaks_json, rate, positions_json, object_serial, natural_key, session_id, present, last_seen_at, created_at, updated_at FROM nodes;
+DROP TABLE nodes;
+ALTER TABLE nodes_scoped RENAME TO nodes;
+
+CREATE TABLE devices_scoped (
+    scope TEXT NOT NULL DEFAULT '',
+    object_id INTEGER NOT NULL,
+    props_json TEXT NOT NULL,
+    profile_index INTEGER,
+    object_serial INTEGER,
+    natural_key TEXT,
+    session_id INTEGER REFERENCES sessions(id),
+    present BOOLEAN NOT NULL DEFAULT 1,
+    last_seen_at INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (scope, object_id)
+);
+INSERT INTO devices_scoped (object_id, props_json, profile_index, object_serial, natural_key, session_id, present, last_seen_at, created_at, updated_at)
+SELECT object_id, props_json, profile_index, object_serial, natural_key, session_id, present, last_seen_at, created_at, updated_at FROM devices;
+DROP TABLE devices;
+ALTER TABLE devices_scoped RENAME TO devices;
+
+CREATE TABLE device_profiles_scoped (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    scope TEXT NOT NULL DEFAULT '',
+    device_id INTEGER NOT NULL,
+    profile_index INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    available BOOLEAN NOT NULL,
+    classes_json TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (scope, device_id) REFERENCES devices(scope, object_id) ON DELETE CASCADE,
+    UNIQUE(scope, device_id, profile_index)
+);
+INSERT INTO device_profiles_scoped (device_id, profile_index, description, available, classes_json, created_at)
+SELECT device_id, profile_index, description, available, classes_json, created_at FROM device_profiles;
+DROP TABLE device_profiles;
+ALTER TABLE device_profiles_scoped RENAME TO device_profiles;
+
+CREATE TABLE device_routes_scoped (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    scope TEXT NOT NULL DEFAULT '',
+    device_id INTEGER NOT NULL,
+    route_index INTEGER NOT NULL,
+    route_device INTEGER NOT NULL,
+    profiles_json TEXT NOT NULL,
+    description TEXT NOT NULL,
+    available BOOLEAN NOT NULL,
+    volumes_json TEXT NOT NULL,
+    mute BOOLEAN NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (scope, device_id) REFERENCES devices(scope, object_id) ON DELETE CASCADE,
+    UNIQUE(scope, device_id, route_index)
+);
+INSERT INTO device_routes_scoped (device_id, route_index, route_device, profiles_json, description, available, volumes_json, mute, created_at)
+SELECT device_id, route_index, route_device, profiles_json, description, available, volumes_json, mute, created_at FROM device_routes;
+DROP TABLE device_routes;
+ALTER TABLE device_routes_scoped RENAME TO device_routes;
+
+CREATE TABLE device_enum_routes_scoped (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    scope TEXT NOT NULL DEFAULT '',
+    device_id INTEGER NOT NULL,
+    enum_route_index INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    available BOOLEAN NOT NULL,
+    profiles_json TEXT NOT NULL,
+    devices_json TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (scope, device_id) REFERENCES devices(scope, object_id) ON DELETE CASCADE,
+    UNIQUE(scope, device_id, enum_route_index)
+);
+INSERT INTO device_enum_routes_scoped (device_id, enum_route_index, description, available, profiles_json, devices_json, created_at)
+SELECT device_id, enum_route_index, description, available, profiles_json, devices_json, created_at FROM device_enum_routes;
+DROP TABLE device_enum_routes;
+ALTER TABLE device_enum_routes_scoped RENAME TO device_enum_routes;
+
+CREATE TABLE links_scoped (
+    scope TEXT NOT NULL DEFAULT '',
+    object_id INTEGER NOT NULL,
+    output_id INTEGER NOT NULL,
+    input_id INTEGER NOT NULL,
+    present BOOLEAN NOT NULL DEFAULT 1,
+    last_seen_at INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (scope, object_id)
+);
+INSERT INTO links_scoped (object_id, output_id, input_id, present, last_seen_at, created_at, updated_at)
+SELECT object_id, output_id, input_id, present, last_seen_at, created_at, updated_at FROM links;
+DROP TABLE links;
+ALTER TABLE links_scoped RENAME TO links;
+
+CREATE TABLE metadata_scoped (
+    scope TEXT NOT NULL DEFAULT '',
+    object_id INTEGER NOT NULL,
+    metadata_name TEXT,
+    present BOOLEAN NOT NULL DEFAULT 1,
+    last_seen_at INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (scope, object_id)
+);
+INSERT INTO metadata_scoped (object_id, metadata_name, present, last_seen_at, created_at, updated_at)
+SELECT object_id, metadata_name, present, last_seen_at, created_at, updated_at FROM metadata;
+DROP TABLE metadata;
+ALTER TABLE metadata_scoped RENAME TO metadata;
+
+CREATE TABLE metadata_properties_scoped (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    scope TEXT NOT NULL DEFAULT '',
+    metadata_id INTEGER NOT NULL,
+    subject INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (scope, metadata_id) REFERENCES metadata(scope, object_id) ON DELETE CASCADE,
+    UNIQUE(scope, metadata_id, subject, key)
+);
+INSERT INTO metadata_properties_scoped (metadata_id, subject, key, value, created_at)
+SELECT metadata_id, subject, key, value, created_at FROM metadata_properties;
+DROP TABLE metadata_properties;
+ALTER TABLE metadata_properties_scoped RENAME TO metadata_properties;
+
+CREATE TABLE stream_restore_scoped (
+    scope TEXT NOT NULL DEFAULT '',
+    match_key TEXT NOT NULL,
+    volumes_json TEXT,
+    mute BOOLEAN,
+    target TEXT,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (scope, match_key)
+);
+INSERT INTO stream_restore_scoped (match_key, volumes_json, mute, target, updated_at)
+SELECT match_key, volumes_json, mute, target, updated_at FROM stream_restore;
+DROP TABLE stream_restore;
+ALTER TABLE stream_restore_scoped RENAME TO stream_restore;
+
+DROP INDEX IF EXISTS idx_level_history_natural_key;
+DROP INDEX IF EXISTS idx_level_history_bucket;
+CREATE TABLE level_history_scoped (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    scope TEXT NOT NULL DEFAULT '',
+    node_id INTEGER NOT NULL,
+    natural_key TEXT,
+    session_id INTEGER REFERENCES sessions(id),
+    bucket_start INTEGER NOT NULL, -- Unix time in milliseconds
+    bucket_ms INTEGER NOT NULL, -- Bucket width in milliseconds
+    channel INTEGER NOT NULL,
+    min REAL NOT NULL,
+    max REAL NOT NULL,
+    mean REAL NOT NULL,
+    samples INTEGER NOT NULL, -- Number of peak readings in the bucket
+    UNIQUE(scope, node_id, session_id, bucket_ms, bucket_start, channel)
+);
+INSERT INTO level_history_scoped (node_id, natural_key, session_id, bucket_start, bucket_ms, channel, min, max, mean, samples)
+SELECT node_id, natural_key, session_id, bucket_start, bucket_ms, channel, min, max, mean, samples FROM level_history;
+DROP TABLE level_history;
+ALTER TABLE level_history_scoped RENAME TO level_history;
+
+-- The event journal only needs the scope for querying
+ALTER TABLE state_events ADD COLUMN scope TEXT NOT NULL DEFAULT '';
+
+ALTER TABLE sessions ADD COLUMN scope TEXT NOT NULL DEFAULT '';
+ALTER TABLE sessions ADD COLUMN host TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_clients_natural_key ON clients(natural_key);
+CREATE INDEX IF NOT EXISTS idx_nodes_natural_key ON nodes(natural_key);
+CREATE INDEX IF NOT EXISTS idx_devices_natural_key ON devices(natural_key);
+CREATE INDEX IF NOT EXISTS idx_metadata_name ON metadata(scope, metadata_name);
+CREATE INDEX IF NOT EXISTS idx_links_output_id ON links(scope, output_id);
+CREATE INDEX IF NOT EXISTS idx_links_input_id ON links(scope, input_id);
+CREATE INDEX IF NOT EXISTS idx_device_profiles_device_id ON device_profiles(scope, device_id);
+CREATE INDEX IF NOT EXISTS idx_device_routes_device_id ON device_routes(scope, device_id);
+CREATE INDEX IF NOT EXISTS idx_device_enum_routes_device_id ON device_enum_routes(scope, device_id);
+CREATE INDEX IF NOT EXISTS idx_metadata_properties_metadata_id ON metadata_properties(scope, metadata_id);
+CREATE INDEX IF NOT EXISTS idx_state_events_scope ON state_events(scope, recorded_at);
+CREATE INDEX IF NOT EXISTS idx_level_history_scope ON level_history(scope, bucket_start);
+CREATE INDEX IF NOT EXISTS idx_level_history_natural_key ON level_history(natural_key, bucket_start);
+CREATE INDEX IF NOT EXISTS idx_level_history_bucket ON level_history(bucket_ms, bucket_start);
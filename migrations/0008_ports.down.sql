@@ -0,0 +1,24 @@
+DROP INDEX IF EXISTS idx_ports_node_id;
+DROP INDEX IF EXISTS idx_links_input_id;
+DROP INDEX IF EXISTS idx_links_output_id;
+
+DROP TABLE IF EXISTS ports;
+
+CREATE TABLE links_nodes (
+    scope TEXT NOT NULL DEFAULT '',
+    object_id INTEGER NOT NULL,
+    output_id INTEGER NOT NULL,
+    input_id INTEGER NOT NULL,
+    present BOOLEAN NOT NULL DEFAULT 1,
+    last_seen_at INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (scope, object_id)
+);
+INSERT INTO links_nodes (scope, object_id, output_id, input_id, present, last_seen_at, created_at, updated_at)
+SELECT scope, object_id, output_id, input_id, present, last_seen_at, created_at, updated_at FROM links;
+DROP TABLE links;
+ALTER TABLE links_nodes RENAME TO links;
+
+CREATE INDEX IF NOT EXISTS idx_links_output_id ON links(scope, output_id);
+CREATE INDEX IF NOT EXISTS idx_links_input_id ON links(scope, input_id);
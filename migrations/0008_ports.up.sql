@@ -0,0 +1,42 @@
+-- Track ports, and which ports each link connects.
+--
+-- Adding columns would drop the links primary key, so the table is rebuilt.
+-- Existing links get port ID 0 until they're seen again.
+
+DROP INDEX IF EXISTS idx_links_output_id;
+DROP INDEX IF EXISTS idx_links_input_id;
+
+CREATE TABLE links_ports (
+    scope TEXT NOT NULL DEFAULT '',
+    object_id INTEGER NOT NULL,
+    output_id INTEGER NOT NULL,
+    input_id INTEGER NOT NULL,
+    output_port_id INTEGER NOT NULL DEFAULT 0,
+    input_port_id INTEGER NOT NULL DEFAULT 0,
+    present BOOLEAN NOT NULL DEFAULT 1,
+    last_seen_at INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (scope, object_id)
+);
+INSERT INTO links_ports (scope, object_id, output_id, input_id, present, last_seen_at, created_at, updated_at)
+SELECT scope, object_id, output_id, input_id, present, last_seen_at, created_at, updated_at FROM links;
+DROP TABLE links;
+ALTER TABLE links_ports RENAME TO links;
+
+CREATE TABLE IF NOT EXISTS ports (
+    scope TEXT NOT NULL DEFAULT '',
+    object_id INTEGER NOT NULL,
+    props_json TEXT NOT NULL,
+    direction TEXT NOT NULL, -- "Input" or "Output"
+    node_id INTEGER,
+    present BOOLEAN NOT NULL DEFAULT 1,
+    last_seen_at INTEGER, -- Unix time in milliseconds
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (scope, object_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_links_output_id ON links(scope, output_id);
+CREATE INDEX IF NOT EXISTS idx_links_input_id ON links(scope, input_id);
+CREATE INDEX IF NOT EXISTS idx_ports_node_id ON ports(scope, node_id);
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_state_events_natural_key;
+
+ALTER TABLE state_events DROP COLUMN natural_key;
+ALTER TABLE state_events DROP COLUMN object_serial;
+ALTER TABLE state_events DROP COLUMN object_type;
@@ -0,0 +1,9 @@
+-- Record which logical object each journaled event pertains to. PipeWire
+-- reuses object IDs, so the live-state row for an ID may describe a later
+-- object, and history can only follow an object across restarts if its
+-- identity is stored with the event. Earlier events are left without one.
+ALTER TABLE state_events ADD COLUMN object_type TEXT; -- "node", "device" or "client"
+ALTER TABLE state_events ADD COLUMN object_serial INTEGER;
+ALTER TABLE state_events ADD COLUMN natural_key TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_state_events_natural_key ON state_events(scope, natural_key, recorded_at);
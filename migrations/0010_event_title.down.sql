@@ -0,0 +1 @@
+ALTER TABLE state_events DROP COLUMN title;
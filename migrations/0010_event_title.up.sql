@@ -0,0 +1,3 @@
+-- Record a title for the object each journaled event pertains to, so that
+-- history can describe objects which have since been removed.
+ALTER TABLE state_events ADD COLUMN title TEXT;
@@ -0,0 +1,3 @@
+-- The replaced properties can't be recovered, and empty properties are valid
+-- for earlier versions too.
+SELECT 1;
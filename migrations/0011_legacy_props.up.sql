@@ -0,0 +1,6 @@
+-- Databases written before properties were stored as JSON hold debug dumps
+-- of them instead. Those can't be read back, so replace them with empty
+-- properties. They're rewritten the next time each object is seen.
+UPDATE clients SET props_json = '{}' WHERE NOT json_valid(props_json);
+UPDATE nodes SET props_json = '{}' WHERE NOT json_valid(props_json);
+UPDATE devices SET props_json = '{}' WHERE NOT json_valid(props_json);
@@ -1,9 +1,9 @@
 //! Main rendering and event processing for the application.
 
-use std::sync::mpsc;
-use std::time::{Duration, Instant};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::config::{Config, Peaks};
+use crate::config::{Config, MeterSource, Peaks};
 use crate::wirehose::{CommandSender, Event as PipewireEvent, StateEvent};
 
 use anyhow::{anyhow, Result};
@@ -24,12 +24,21 @@ use crossterm::event::{
 use serde::Deserialize;
 use smallvec::{smallvec, SmallVec};
 
+use crate::db::db::{self as database, HistoryFilter};
+use crate::db::db_channel::{DatabaseHandle, DatabaseWorker, DbMessage};
 use crate::device_kind::DeviceKind;
 use crate::event::Event;
 use crate::help::{HelpWidget, HelpWidgetState};
+use crate::history::{HistoryList, HistoryListWidget, HISTORY_LIMIT};
+use crate::meter;
 use crate::object_list::{ObjectList, ObjectListWidget};
+use crate::patchbay::{Patchbay, PatchbayWidget};
+use crate::stream_restore::{self, StreamRestore};
 use crate::view::{self, ListKind, View};
-use crate::wirehose::{state::State, ObjectId};
+use crate::virtual_device::{
+    FormResult, VirtualDeviceForm, VirtualDeviceWidget,
+};
+use crate::wirehose::{state::State, ObjectId, PortDirection};
 
 /// A UI action.
 ///
@@ -53,9 +62,23 @@ pub enum Action {
     TabRight,
     SelectTab(usize),
     SetAbsoluteVolume(f32),
+    SetAbsoluteVolumeDb(f32),
+    SetRelativeBalance(f32),
+    ToggleChannelLock,
+    SelectPreviousChannel,
+    SelectNextChannel,
+    FilterHistory,
+    NewVirtualDevice,
+    RemoveVirtualDevice,
     #[serde(skip_deserializing)]
     SelectObject(ObjectId),
     #[serde(skip_deserializing)]
+    SelectHistoryEntry(usize),
+    #[serde(skip_deserializing)]
+    SelectPort(ObjectId),
+    #[serde(skip_deserializing)]
+    SelectChannel(usize),
+    #[serde(skip_deserializing)]
     SetTarget(view::Target),
     // This can be used to delete a default keybinding - make it do nothing.
     Nothing,
@@ -74,15 +97,45 @@ impl std::fmt::Display for Action {
             Action::SelectObject(object_id) => {
                 write!(f, "Select object {object_id:?}")
             }
+            Action::SelectHistoryEntry(index) => {
+                write!(f, "Select history entry {index}")
+            }
+            Action::SelectPort(object_id) => {
+                write!(f, "Select port {object_id:?}")
+            }
+            Action::SelectChannel(index) => {
+                write!(f, "Select channel {index}")
+            }
             Action::SetTarget(_) => write!(f, "Set target"),
             Action::ToggleMute => write!(f, "Toggle mute"),
             Action::SetAbsoluteVolume(vol) => {
                 write!(f, "Set volume to {}%", Self::format_percentage(*vol))
             }
+            Action::SetAbsoluteVolumeDb(db) => {
+                write!(f, "Set volume to {db} dB")
+            }
             Action::SetRelativeVolume(vol) => {
                 Self::format_relative_volume(f, *vol)
             }
+            Action::SetRelativeBalance(balance) if *balance < 0.0 => write!(
+                f,
+                "Shift balance left by {}%",
+                Self::format_percentage(-balance)
+            ),
+            Action::SetRelativeBalance(balance) => write!(
+                f,
+                "Shift balance right by {}%",
+                Self::format_percentage(*balance)
+            ),
+            Action::ToggleChannelLock => write!(f, "Lock/unlock channels"),
+            Action::SelectPreviousChannel => {
+                write!(f, "Select previous channel")
+            }
+            Action::SelectNextChannel => write!(f, "Select next channel"),
             Action::SetDefault => write!(f, "Set default"),
+            Action::FilterHistory => write!(f, "Show history of item"),
+            Action::NewVirtualDevice => write!(f, "Create virtual device"),
+            Action::RemoveVirtualDevice => write!(f, "Remove virtual device"),
             Action::Help => write!(f, "Show/hide help"),
             Action::Exit => write!(f, "Exit wiremix"),
             Action::Nothing => write!(f, "Nothing"),
@@ -135,6 +188,10 @@ pub enum TabKind {
     Output,
     Input,
     Configuration,
+    History,
+    Patchbay,
+    Midi,
+    Video,
 }
 
 impl TabKind {
@@ -151,6 +208,10 @@ impl std::fmt::Display for TabKind {
             TabKind::Output => write!(f, "Output Devices"),
             TabKind::Input => write!(f, "Input Devices"),
             TabKind::Configuration => write!(f, "Configuration"),
+            TabKind::History => write!(f, "History"),
+            TabKind::Patchbay => write!(f, "Patchbay"),
+            TabKind::Midi => write!(f, "MIDI"),
+            TabKind::Video => write!(f, "Video"),
         }
     }
 }
@@ -204,6 +265,25 @@ pub struct App<'a> {
     drag_row: Option<u16>,
     /// Position in help text (None if not showing help)
     help_position: Option<u16>,
+    /// Restores stream settings from the database, if enabled
+    stream_restore: Option<StreamRestore>,
+    /// The database thread, if a database is configured
+    database: Option<DatabaseWorker>,
+    /// For reading the database thread's queue counters
+    database_handle: Option<DatabaseHandle>,
+    /// The most recent database error, shown in the status line
+    database_error: Option<String>,
+    /// How many database errors have occurred
+    database_error_count: usize,
+    /// The most recent command PipeWire couldn't carry out, shown in the
+    /// status line
+    command_error: Option<String>,
+    /// Changes loaded from the database for the History tab
+    history: HistoryList,
+    /// Selection and picked port for the Patchbay tab
+    patchbay: Patchbay,
+    /// The form for creating a virtual device, if it's open
+    virtual_device_form: Option<VirtualDeviceForm>,
 }
 
 macro_rules! current_list {
@@ -215,6 +295,7 @@ macro_rules! current_list {
 impl<'a> App<'a> {
     pub fn new(
         wirehose: &'a dyn CommandSender,
+        tx: Arc<mpsc::Sender<Event>>,
         rx: mpsc::Receiver<Event>,
         config: Config,
     ) -> Self {
@@ -248,37 +329,89 @@ impl<'a> App<'a> {
                 TabKind::Configuration.to_string(),
                 ObjectList::new(ListKind::Device, None),
             ),
+            // History is rendered from the HistoryList rather than from
+            // objects, so this list is never shown.
+            Tab::new(TabKind::History.to_string(), ObjectList::default()),
+            // Likewise, the Patchbay is rendered from ports and links.
+            Tab::new(TabKind::Patchbay.to_string(), ObjectList::default()),
+            Tab::new(
+                TabKind::Midi.to_string(),
+                ObjectList::new(ListKind::Node(view::NodeKind::Midi), None),
+            ),
+            Tab::new(
+                TabKind::Video.to_string(),
+                ObjectList::new(ListKind::Node(view::NodeKind::Video), None),
+            ),
         ];
 
-        // Update peaks with VU-meter-style ballistics
-        let peak_processor = |current_peak, new_peak, rate, samples| {
-            // Attack/release time of 300 ms
-            let time_constant = 0.3;
-            let coef =
-                1.0 - (-(samples as f32) / (time_constant * rate as f32)).exp();
-
-            current_peak + (new_peak - current_peak) * coef
-        };
-
         let mut state = State::default()
-            .with_peak_processor(Box::new(peak_processor))
-            .with_capture(config.peaks != Peaks::Off);
+            .with_peak_processor(Box::new(meter::ballistics(&config.meter)))
+            .with_peak_hold(config.meter.peak_hold())
+            .with_capture(config.peaks != Peaks::Off)
+            .with_measured_levels(config.meter.source != MeterSource::Peak);
+
+        let mut stream_restore = None;
+        let mut database = None;
+        let mut database_handle = None;
+        let mut database_error = None;
 
         // Initialize database if configured
         if let Some(database_url) = &config.database_url {
-            match crate::db::db_channel::DatabaseThread::new(database_url) {
+            match crate::db::db_channel::DatabaseThread::new(
+                database_url,
+                config.remote.as_deref(),
+                &config.database_scope.clone().unwrap_or_else(|| {
+                    crate::db::db::default_scope(config.remote.as_deref())
+                }),
+                config.database_flush_interval,
+                config.database_queue_capacity,
+                config.database_queue_overflow,
+            ) {
                 Ok((db_thread, db_handle)) => {
+                    // Send errors and loaded history to the UI
+                    let history_tx = Arc::clone(&tx);
+                    let db_thread = db_thread
+                        .with_error_handler(move |error| {
+                            let _ = tx.send(Event::DatabaseError(error));
+                        })
+                        .with_history_handler(move |history| {
+                            let _ = history_tx.send(Event::History(history));
+                        });
+
+                    if config.stream_restore.enabled {
+                        match db_thread.stream_settings() {
+                            Ok(saved) => {
+                                stream_restore = Some(StreamRestore::new(
+                                    config.stream_restore.clone(),
+                                    db_handle.clone(),
+                                    saved,
+                                ));
+                            }
+                            Err(e) => {
+                                database_error = Some(format!(
+                                    "Failed to load stream settings: {e}"
+                                ));
+                            }
+                        }
+                    }
+
                     // Start the database thread
-                    db_thread.run();
-                    state = state.with_database(db_handle);
+                    database = Some(db_thread.run());
+                    database_handle = Some(db_handle.clone());
+                    state = state
+                        .with_database(db_handle)
+                        .with_journal(config.journal_events.clone())
+                        .with_persisted_peaks(config.database_peaks)
+                        .with_level_history(config.database_level_history);
                 }
                 Err(e) => {
-                    eprintln!("Warning: Failed to initialize database at '{}': {}", database_url, e);
+                    database_error =
+                        Some(format!("Failed to open '{database_url}': {e}"));
                 }
             }
         }
 
-        App {
+        let mut app = App {
             exit: false,
             wirehose,
             rx,
@@ -293,7 +426,27 @@ impl<'a> App<'a> {
             config,
             drag_row: None,
             help_position: None,
+            stream_restore,
+            database,
+            database_handle,
+            database_error_count: usize::from(database_error.is_some()),
+            database_error,
+            command_error: None,
+            history: HistoryList::default(),
+            patchbay: Patchbay::default(),
+            virtual_device_form: None,
+        };
+        if app.is_history_tab() {
+            app.request_history();
         }
+
+        app
+    }
+
+    /// Take the database thread so that it can be shut down after the app
+    /// exits.
+    pub fn take_database(&mut self) -> Option<DatabaseWorker> {
+        self.database.take()
     }
 
     pub fn run(mut self, terminal: &mut DefaultTerminal) -> Result<()> {
@@ -333,7 +486,13 @@ impl<'a> App<'a> {
                 self.mouse_areas.clear();
 
                 terminal.draw(|frame| {
-                    current_list!(self).update(frame.area(), &self.view);
+                    if self.is_history_tab() {
+                        self.history.update(frame.area());
+                    } else if self.is_patchbay_tab() {
+                        self.patchbay.update(frame.area(), &self.view);
+                    } else {
+                        current_list!(self).update(frame.area(), &self.view);
+                    }
 
                     self.draw(frame);
                 })?;
@@ -354,16 +513,142 @@ impl<'a> App<'a> {
             current_tab_index: self.current_tab_index,
             view: &self.view,
             config: &self.config,
+            status: self.status(),
+            is_history_enabled: self.database_handle.is_some(),
         };
         let mut widget_state = AppWidgetState {
             mouse_areas: &mut self.mouse_areas,
             tabs: &mut self.tabs,
+            history: &mut self.history,
+            patchbay: &self.patchbay,
+            virtual_device_form: self.virtual_device_form.as_ref(),
             help_position: &mut self.help_position,
         };
 
         frame.render_stateful_widget(widget, frame.area(), &mut widget_state);
     }
 
+    /// Describe failed commands and database problems for the status line.
+    fn status(&self) -> Option<String> {
+        let status: Vec<String> = self
+            .command_error
+            .iter()
+            .cloned()
+            .chain(self.database_status())
+            .collect();
+
+        (!status.is_empty()).then(|| status.join(" | "))
+    }
+
+    /// Describe database problems for the status line.
+    fn database_status(&self) -> Option<String> {
+        let mut problems = Vec::new();
+
+        if let Some(stats) =
+            self.database_handle.as_ref().map(DatabaseHandle::stats)
+        {
+            if stats.dropped > 0 {
+                problems.push(format!("{} changes dropped", stats.dropped));
+            }
+            // Messages are taken off the queue as they arrive, so a short
+            // queue isn't worth a status line of its own
+            let backlog = self.config.database_queue_capacity / 10;
+            if stats.depth > backlog
+                || stats.dropped > 0
+                || self.database_error.is_some()
+            {
+                problems.push(format!("{} queued", stats.depth));
+            }
+        }
+        if let Some(error) = &self.database_error {
+            problems.push(format!(
+                "{} errors (latest: {})",
+                self.database_error_count, error
+            ));
+        }
+
+        (!problems.is_empty())
+            .then(|| format!("Database: {}", problems.join("; ")))
+    }
+
+    fn is_history_tab(&self) -> bool {
+        self.current_tab_index == TabKind::History.index()
+    }
+
+    fn is_patchbay_tab(&self) -> bool {
+        self.current_tab_index == TabKind::Patchbay.index()
+    }
+
+    /// Ask the database thread for history matching the current filter. The
+    /// result arrives as an [`Event::History`].
+    fn request_history(&mut self) {
+        if let Some(database_handle) = &self.database_handle {
+            let _ = database_handle.send(DbMessage::LoadHistory {
+                filter: self.history.filter.clone(),
+                limit: HISTORY_LIMIT,
+            });
+        }
+    }
+
+    /// Toggle the history filter. On the History tab this filters by the
+    /// selected entry's object, or clears an existing filter. On other tabs
+    /// it shows the history of the selected object.
+    fn filter_history(&mut self) {
+        let (filter, title) = if self.is_history_tab() {
+            if self.history.filter.is_some() {
+                (None, None)
+            } else if let Some(entry) = self.history.selected_entry() {
+                (Some(entry.filter.clone()), Some(entry.title.clone()))
+            } else {
+                return;
+            }
+        } else {
+            let Some(object_id) = current_list!(self).selected else {
+                return;
+            };
+            if let Some(node) = self.view.nodes.get(&object_id) {
+                let filter = HistoryFilter {
+                    object_id: object_id.into(),
+                    natural_key: Some(node.name.clone()),
+                };
+                (Some(filter), Some(node.title.clone()))
+            } else if let Some(device) = self.view.devices.get(&object_id) {
+                let filter = HistoryFilter {
+                    object_id: object_id.into(),
+                    natural_key: device.name.clone(),
+                };
+                (Some(filter), Some(device.title.clone()))
+            } else {
+                return;
+            }
+        };
+
+        self.current_tab_index = TabKind::History.index();
+        self.history.set_filter(filter, title);
+        self.request_history();
+    }
+
+    /// Jump to the selected history entry's object on the tab that lists it.
+    fn show_history_object(&mut self) {
+        let Some(object_id) = self
+            .history
+            .selected_entry()
+            .and_then(|entry| entry.live_object(&self.view))
+        else {
+            return;
+        };
+
+        let history_index = TabKind::History.index();
+        if let Some(index) =
+            self.tabs.iter().enumerate().position(|(i, tab)| {
+                i != history_index && tab.list.contains(&self.view, object_id)
+            })
+        {
+            self.current_tab_index = index;
+            self.tabs[index].list.selected = Some(object_id);
+        }
+    }
+
     fn exit(&mut self, error_message: Option<String>) {
         self.exit = true;
         self.error_message = error_message;
@@ -441,6 +726,19 @@ impl Handle for Event {
         match self {
             Event::Input(event) => event.handle(app),
             Event::Pipewire(event) => event.handle(app),
+            Event::DatabaseError(error) => {
+                app.database_error = Some(error);
+                app.database_error_count += 1;
+                Ok(true)
+            }
+            Event::History(records) => {
+                app.history.set_records(
+                    &records,
+                    &app.view,
+                    app.config.volume_scale,
+                );
+                Ok(app.is_history_tab())
+            }
         }
     }
 }
@@ -462,6 +760,23 @@ impl Handle for KeyEvent {
             return Ok(false);
         }
 
+        if let Some(form) = &mut app.virtual_device_form {
+            match form.handle_key(self) {
+                FormResult::Continue => {}
+                FormResult::Cancel => app.virtual_device_form = None,
+                FormResult::Submit => {
+                    app.view.create_virtual_device(
+                        form.device_kind,
+                        form.name.trim(),
+                        form.positions(),
+                        form.linger,
+                    );
+                    app.virtual_device_form = None;
+                }
+            }
+            return Ok(true);
+        }
+
         if let Some(&action) = app.config.keybindings.get(&self) {
             return action.handle(app);
         }
@@ -499,12 +814,54 @@ impl Handle for Action {
             }
         }
 
+        if app.virtual_device_form.is_some() {
+            // Key presses go straight to the form, so this is a click outside
+            // of it.
+            if self == Action::CloseDropdown {
+                app.virtual_device_form = None;
+                return Ok(true);
+            }
+            return Ok(false);
+        }
+
+        let previous_tab_index = app.current_tab_index;
+
         match self {
             Action::SelectTab(index) => {
                 if index < app.tabs.len() {
                     app.current_tab_index = index;
                 }
             }
+            Action::MoveDown if app.is_history_tab() => app.history.down(),
+            Action::MoveUp if app.is_history_tab() => app.history.up(),
+            Action::ActivateDropdown if app.is_history_tab() => {
+                app.show_history_object();
+            }
+            Action::SelectHistoryEntry(index) => app.history.select(index),
+            Action::FilterHistory => app.filter_history(),
+            Action::MoveDown if app.is_patchbay_tab() => {
+                app.patchbay.down(&app.view);
+            }
+            Action::MoveUp if app.is_patchbay_tab() => {
+                app.patchbay.up(&app.view);
+            }
+            // Volume keys move between the output and input columns
+            Action::SetRelativeVolume(volume) if app.is_patchbay_tab() => {
+                app.patchbay.focus(if volume < 0.0 {
+                    PortDirection::Output
+                } else {
+                    PortDirection::Input
+                });
+            }
+            Action::ActivateDropdown if app.is_patchbay_tab() => {
+                app.patchbay.activate(&app.view);
+            }
+            Action::CloseDropdown if app.is_patchbay_tab() => {
+                app.patchbay.cancel();
+            }
+            Action::SelectPort(object_id) => {
+                app.patchbay.click(object_id, &app.view);
+            }
             Action::MoveDown => {
                 current_list!(app).down(&app.view);
             }
@@ -541,20 +898,53 @@ impl Handle for Action {
                     .config
                     .enforce_max_volume
                     .then_some(app.config.max_volume_percent);
-                current_list!(app).set_absolute_volume(&app.view, volume, max);
+                let scale = app.config.volume_scale;
+                current_list!(app)
+                    .set_absolute_volume(&app.view, volume, max, scale);
                 return Ok(current_list!(app)
-                    .set_absolute_volume(&app.view, volume, max));
+                    .set_absolute_volume(&app.view, volume, max, scale));
+            }
+            Action::SetAbsoluteVolumeDb(db) => {
+                let volume = app.config.volume_scale.from_db(db);
+                return Action::SetAbsoluteVolume(volume).handle(app);
             }
             Action::SetRelativeVolume(volume) => {
                 // Relative decreases have no maximum.
                 let max = (volume > 0.0 && app.config.enforce_max_volume)
                     .then_some(app.config.max_volume_percent);
+                let scale = app.config.volume_scale;
                 return Ok(current_list!(app)
-                    .set_relative_volume(&app.view, volume, max));
+                    .set_relative_volume(&app.view, volume, max, scale));
+            }
+            Action::SetRelativeBalance(balance) => {
+                let scale = app.config.volume_scale;
+                current_list!(app)
+                    .set_relative_balance(&app.view, balance, scale);
+            }
+            Action::ToggleChannelLock => {
+                current_list!(app).toggle_channel_lock();
+            }
+            Action::SelectPreviousChannel => {
+                current_list!(app).previous_channel(&app.view);
+            }
+            Action::SelectNextChannel => {
+                current_list!(app).next_channel(&app.view);
+            }
+            Action::SelectChannel(channel) => {
+                current_list!(app).select_channel(&app.view, channel);
             }
             Action::SetDefault => {
                 current_list!(app).set_default(&app.view);
             }
+            Action::NewVirtualDevice => {
+                if let Some(device_kind) = current_list!(app).device_kind() {
+                    app.virtual_device_form =
+                        Some(VirtualDeviceForm::new(device_kind));
+                }
+            }
+            Action::RemoveVirtualDevice => {
+                current_list!(app).remove_virtual_device(&app.view);
+            }
             Action::Exit => {
                 app.exit(None);
             }
@@ -568,6 +958,11 @@ impl Handle for Action {
             }
         }
 
+        // Refresh history whenever the History tab is opened
+        if app.current_tab_index != previous_tab_index && app.is_history_tab() {
+            app.request_history();
+        }
+
         Ok(true)
     }
 }
@@ -611,9 +1006,17 @@ impl Handle for PipewireEvent {
         match self {
             PipewireEvent::Ready => {
                 app.is_ready = true;
+                app.state.ready();
+                if let Some(stream_restore) = &mut app.stream_restore {
+                    stream_restore.ready(&app.state);
+                }
                 Ok(true)
             }
             PipewireEvent::Error(message) => message.handle(app),
+            PipewireEvent::CommandFailed(message) => {
+                app.command_error = Some(message);
+                Ok(true)
+            }
             PipewireEvent::State(event) => event.handle(app),
         }
     }
@@ -626,7 +1029,7 @@ impl Handle for StateEvent {
         match (app.state_dirty, &self) {
             (
                 StateDirty::Clean | StateDirty::PeaksOnly,
-                StateEvent::NodePeaks { .. },
+                StateEvent::NodePeaks { .. } | StateEvent::NodeLevels { .. },
             ) => {
                 app.state_dirty = StateDirty::PeaksOnly;
             }
@@ -635,8 +1038,19 @@ impl Handle for StateEvent {
             }
         }
 
+        let change = app
+            .stream_restore
+            .as_ref()
+            .and_then(|_| stream_restore::Change::from_event(&self));
+
         app.state.update(app.wirehose, self);
 
+        if let (Some(stream_restore), Some(change)) =
+            (&mut app.stream_restore, change)
+        {
+            stream_restore.update(&app.state, app.wirehose, change);
+        }
+
         Ok(true)
     }
 }
@@ -663,11 +1077,18 @@ pub struct AppWidget<'a, 'b> {
     current_tab_index: usize,
     view: &'a View<'b>,
     config: &'a Config,
+    /// Status line text, if there's anything to report
+    status: Option<String>,
+    /// Whether history can be loaded from a database
+    is_history_enabled: bool,
 }
 
 pub struct AppWidgetState<'a> {
     mouse_areas: &'a mut Vec<MouseArea>,
     tabs: &'a mut Vec<Tab>,
+    history: &'a mut HistoryList,
+    patchbay: &'a Patchbay,
+    virtual_device_form: Option<&'a VirtualDeviceForm>,
     help_position: &'a mut Option<u16>,
 }
 
@@ -675,15 +1096,24 @@ impl<'a> StatefulWidget for AppWidget<'a, '_> {
     type State = AppWidgetState<'a>;
 
     fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        // Only show the status line if there's something to report
+        let status_height = u16::from(self.status.is_some());
         let layout = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Min(0),    // list_area
-                Constraint::Length(1), // menu_area
+                Constraint::Min(0),                // list_area
+                Constraint::Length(status_height), // status_area
+                Constraint::Length(1),             // menu_area
             ])
             .split(area);
         let list_area = layout[0];
-        let menu_area = layout[1];
+        let status_area = layout[1];
+        let menu_area = layout[2];
+
+        if let Some(status) = self.status {
+            Line::from(Span::styled(status, self.config.theme.status_error))
+                .render(status_area, buf);
+        }
 
         let constraints: Vec<_> = state
             .tabs
@@ -724,12 +1154,60 @@ impl<'a> StatefulWidget for AppWidget<'a, '_> {
             ));
         }
 
-        let mut widget = ObjectListWidget {
-            object_list: &mut state.tabs[self.current_tab_index].list,
-            view: self.view,
-            config: self.config,
-        };
-        widget.render(list_area, buf, state.mouse_areas);
+        if self.current_tab_index == TabKind::History.index() {
+            let mut widget = HistoryListWidget {
+                history: state.history,
+                config: self.config,
+                is_enabled: self.is_history_enabled,
+                now: database::unix_ms(SystemTime::now()).unwrap_or_default(),
+            };
+            widget.render(list_area, buf, state.mouse_areas);
+        } else if self.current_tab_index == TabKind::Patchbay.index() {
+            let mut widget = PatchbayWidget {
+                patchbay: state.patchbay,
+                view: self.view,
+                config: self.config,
+            };
+            widget.render(list_area, buf, state.mouse_areas);
+        } else {
+            let mut widget = ObjectListWidget {
+                object_list: &mut state.tabs[self.current_tab_index].list,
+                view: self.view,
+                config: self.config,
+            };
+            widget.render(list_area, buf, state.mouse_areas);
+        }
+
+        // Render the virtual device form if it's open
+        if let Some(form) = state.virtual_device_form {
+            // Ignore any mouse actions on the lower area
+            state.mouse_areas.clear();
+            // Close the form if clicked anywhere outside
+            state.mouse_areas.push((
+                area,
+                smallvec![MouseEventKind::Down(MouseButton::Left)],
+                smallvec![Action::CloseDropdown],
+            ));
+
+            let [form_area] = Layout::horizontal([Constraint::Max(
+                VirtualDeviceWidget::width(),
+            )])
+            .flex(Flex::Center)
+            .areas(list_area);
+            let [form_area] = Layout::vertical([Constraint::Length(
+                VirtualDeviceWidget::height(),
+            )])
+            .flex(Flex::Center)
+            .areas(form_area);
+
+            Clear.render(form_area, buf);
+
+            VirtualDeviceWidget {
+                form,
+                config: self.config,
+            }
+            .render(form_area, buf, state.mouse_areas);
+        }
 
         // Render the help menu if it's open
         if let Some(ref mut help_position) = state.help_position {
@@ -788,12 +1266,14 @@ impl<'a> StatefulWidget for AppWidget<'a, '_> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::VolumeScale;
+    use crate::db::db_channel::DatabaseThread;
     use crate::mock;
     use crate::wirehose::PropertyStore;
     use strum::IntoEnumIterator;
 
     fn fixture(wirehose: &mock::WirehoseHandle) -> App<'_> {
-        let (_, event_rx) = mpsc::channel();
+        let (event_tx, event_rx) = mpsc::channel();
 
         let config = Config {
             remote: None,
@@ -804,14 +1284,24 @@ mod tests {
             theme: Default::default(),
             max_volume_percent: Default::default(),
             enforce_max_volume: Default::default(),
+            volume_scale: Default::default(),
+            meter: Default::default(),
             keybindings: Default::default(),
             help: Default::default(),
             names: Default::default(),
             tab: Default::default(),
             database_url: None,
+            database_scope: None,
+            journal_events: Default::default(),
+            database_flush_interval: Duration::from_millis(1000),
+            database_peaks: false,
+            database_level_history: false,
+            database_queue_capacity: 10000,
+            database_queue_overflow: Default::default(),
+            stream_restore: Default::default(),
         };
 
-        let mut app = App::new(wirehose, event_rx, config);
+        let mut app = App::new(wirehose, Arc::new(event_tx), event_rx, config);
 
         // Create a node for testing
         let object_id = ObjectId::from_raw_id(0);
@@ -857,6 +1347,231 @@ mod tests {
         app
     }
 
+    #[test]
+    fn database_errors_are_counted() {
+        let wirehose = mock::WirehoseHandle::default();
+        let mut app = fixture(&wirehose);
+        assert_eq!(app.database_error_count, 0);
+
+        for error in ["first", "second"] {
+            let event = Event::DatabaseError(String::from(error));
+            assert!(event.handle(&mut app).unwrap());
+        }
+        assert_eq!(app.database_error_count, 2);
+        assert_eq!(app.database_error.as_deref(), Some("second"));
+    }
+
+    #[test]
+    fn failed_commands_are_shown() {
+        let wirehose = mock::WirehoseHandle::default();
+        let mut app = fixture(&wirehose);
+        assert_eq!(app.status(), None);
+
+        let message = String::from("Couldn't create virtual device: no memory");
+        let event = Event::Pipewire(PipewireEvent::CommandFailed(message));
+        assert!(event.handle(&mut app).unwrap());
+        assert_eq!(
+            app.status().as_deref(),
+            Some("Couldn't create virtual device: no memory")
+        );
+
+        app.database_error = Some(String::from("disk full"));
+        app.database_error_count = 1;
+        assert_eq!(
+            app.status().as_deref(),
+            Some(
+                "Couldn't create virtual device: no memory | \
+                 Database: 1 errors (latest: disk full)"
+            )
+        );
+    }
+
+    #[test]
+    fn database_backlog_is_shown() {
+        let wirehose = mock::WirehoseHandle::default();
+        let mut app = fixture(&wirehose);
+        app.config.database_queue_capacity = 10;
+
+        let db_path = std::env::temp_dir().join("test_app_backlog.db");
+        let _ = std::fs::remove_file(&db_path);
+        // The thread isn't run, so messages stay queued
+        let (_db_thread, db_handle) = DatabaseThread::new(
+            db_path.to_str().unwrap(),
+            None,
+            "test/default",
+            Duration::from_millis(1000),
+            10,
+            Default::default(),
+        )
+        .unwrap();
+        app.database_handle = Some(db_handle.clone());
+        assert_eq!(app.database_status(), None);
+
+        for _ in 0..2 {
+            db_handle.send(DbMessage::MarkAbsent).unwrap();
+        }
+        assert_eq!(
+            app.database_status().as_deref(),
+            Some("Database: 2 queued")
+        );
+
+        app.database_error = Some(String::from("disk full"));
+        app.database_error_count = 1;
+        assert_eq!(
+            app.database_status().as_deref(),
+            Some("Database: 2 queued; 1 errors (latest: disk full)")
+        );
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[test]
+    fn virtual_device_names_are_unique() {
+        let wirehose = mock::WirehoseHandle::default();
+        let mut app = fixture(&wirehose);
+
+        let object_id = ObjectId::from_raw_id(10);
+        let mut props = PropertyStore::default();
+        props.set_node_description(String::from("Bus"));
+        props.set_media_class(String::from("Audio/Sink"));
+        props.set_node_name(String::from("wiremix_virtual.bus"));
+        props.set_object_serial(10);
+        for event in [
+            StateEvent::NodeProperties { object_id, props },
+            StateEvent::NodeVolumes {
+                object_id,
+                volumes: vec![1.0, 1.0],
+            },
+            StateEvent::NodeMute {
+                object_id,
+                mute: false,
+            },
+        ] {
+            assert!(event.handle(&mut app).unwrap());
+        }
+        app.view = View::from(&wirehose, &app.state, &app.config.names);
+
+        let positions = ["FL", "FR"];
+        for description in ["Bus", "Música"] {
+            app.view.create_virtual_device(
+                DeviceKind::Sink,
+                description,
+                &positions,
+                false,
+            );
+        }
+        assert_eq!(
+            *wirehose.virtual_nodes_created.borrow(),
+            ["wiremix_virtual.bus_2", "wiremix_virtual.música"]
+        );
+    }
+
+    #[test]
+    fn only_wiremix_virtual_devices_are_removed() {
+        let wirehose = mock::WirehoseHandle::default();
+        let mut app = fixture(&wirehose);
+
+        let null_sink_id = ObjectId::from_raw_id(10);
+        let virtual_id = ObjectId::from_raw_id(11);
+        for (object_id, is_wiremix) in
+            [(null_sink_id, false), (virtual_id, true)]
+        {
+            let mut props = PropertyStore::default();
+            props.set_node_description(String::from("Null"));
+            props.set_node_name(format!("null_{}", u32::from(object_id)));
+            props.set_media_class(String::from("Audio/Sink"));
+            props.set_factory_name(String::from("support.null-audio-sink"));
+            props.set_object_serial(u64::from(u32::from(object_id)));
+            if is_wiremix {
+                props.set_wiremix_virtual(true);
+            }
+            for event in [
+                StateEvent::NodeProperties { object_id, props },
+                StateEvent::NodeVolumes {
+                    object_id,
+                    volumes: vec![1.0, 1.0],
+                },
+                StateEvent::NodeMute {
+                    object_id,
+                    mute: false,
+                },
+            ] {
+                assert!(event.handle(&mut app).unwrap());
+            }
+        }
+        app.view = View::from(&wirehose, &app.state, &app.config.names);
+
+        app.view.remove_virtual_device(null_sink_id);
+        app.view.remove_virtual_device(virtual_id);
+        assert_eq!(*wirehose.nodes_destroyed.borrow(), [virtual_id]);
+    }
+
+    #[test]
+    fn midi_nodes_listed_with_links() {
+        let wirehose = mock::WirehoseHandle::default();
+        let mut app = fixture(&wirehose);
+
+        let bridge_id = ObjectId::from_raw_id(10);
+        let stream_id = ObjectId::from_raw_id(11);
+        for (object_id, media_class, description) in [
+            (bridge_id, "Midi/Bridge", "Midi-Bridge"),
+            (stream_id, "Stream/Input/Midi", "Synth"),
+        ] {
+            let mut props = PropertyStore::default();
+            props.set_node_description(String::from(description));
+            props.set_media_class(String::from(media_class));
+            props.set_media_name(String::from(description));
+            props.set_node_name(String::from(description));
+            props.set_object_serial(u32::from(object_id).into());
+            let event = StateEvent::NodeProperties { object_id, props };
+            assert!(event.handle(&mut app).unwrap());
+        }
+        let event = StateEvent::Link {
+            object_id: ObjectId::from_raw_id(20),
+            output_id: bridge_id,
+            input_id: stream_id,
+            output_port_id: ObjectId::from_raw_id(21),
+            input_port_id: ObjectId::from_raw_id(22),
+        };
+        assert!(event.handle(&mut app).unwrap());
+        app.view = View::from(&wirehose, &app.state, &app.config.names);
+
+        // MIDI nodes have no volume, so they're only on the MIDI tab
+        assert_eq!(app.view.nodes_midi, vec![bridge_id, stream_id]);
+        assert!(app.view.nodes_video.is_empty());
+        assert!(!app.view.nodes_recording.contains(&stream_id));
+
+        let stream = &app.view.nodes[&stream_id];
+        assert!(stream.volumes.is_empty());
+        assert_eq!(stream.target_title, "Midi-Bridge");
+    }
+
+    #[test]
+    fn filter_history_toggles() {
+        let wirehose = mock::WirehoseHandle::default();
+        let mut app = fixture(&wirehose);
+
+        // Showing a node's history switches to the History tab
+        assert!(Action::FilterHistory.handle(&mut app).unwrap());
+        assert_eq!(app.current_tab_index, TabKind::History.index());
+        assert_eq!(
+            app.history.filter,
+            Some(HistoryFilter {
+                object_id: 0,
+                natural_key: Some(String::from("Node name")),
+            })
+        );
+        assert_eq!(
+            app.history.filter_title.as_deref(),
+            Some("Node name: Media name")
+        );
+
+        // Filtering again on the History tab shows everything
+        assert!(Action::FilterHistory.handle(&mut app).unwrap());
+        assert_eq!(app.history.filter, None);
+        assert_eq!(app.history.filter_title, None);
+    }
+
     #[test]
     fn select_tab_bounds() {
         let wirehose = mock::WirehoseHandle::default();
@@ -871,7 +1586,7 @@ mod tests {
         use crossterm::event::{KeyCode, KeyModifiers};
         use std::collections::HashMap;
         let wirehose = mock::WirehoseHandle::default();
-        let (_, event_rx) = mpsc::channel();
+        let (event_tx, event_rx) = mpsc::channel();
 
         let x = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE);
         let ctrl_x = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL);
@@ -889,13 +1604,23 @@ mod tests {
             theme: Default::default(),
             max_volume_percent: Default::default(),
             enforce_max_volume: Default::default(),
+            volume_scale: Default::default(),
+            meter: Default::default(),
             keybindings,
             help: Default::default(),
             names: Default::default(),
             tab: Default::default(),
             database_url: None,
+            database_scope: None,
+            journal_events: Default::default(),
+            database_flush_interval: Duration::from_millis(1000),
+            database_peaks: false,
+            database_level_history: false,
+            database_queue_capacity: 10000,
+            database_queue_overflow: Default::default(),
+            stream_restore: Default::default(),
         };
-        let mut app = App::new(&wirehose, event_rx, config);
+        let mut app = App::new(&wirehose, Arc::new(event_tx), event_rx, config);
 
         let _ = x.handle(&mut app);
         assert_eq!(app.current_tab_index, 2);
@@ -923,6 +1648,10 @@ mod tests {
                 TabKind::Output => assert_eq!(title, "Output Devices"),
                 TabKind::Input => assert_eq!(title, "Input Devices"),
                 TabKind::Configuration => assert_eq!(title, "Configuration"),
+                TabKind::History => assert_eq!(title, "History"),
+                TabKind::Patchbay => assert_eq!(title, "Patchbay"),
+                TabKind::Midi => assert_eq!(title, "MIDI"),
+                TabKind::Video => assert_eq!(title, "Video"),
             }
         }
     }
@@ -1060,4 +1789,41 @@ mod tests {
         assert!(Action::SetRelativeVolume(-0.10).handle(&mut app).unwrap());
         assert!(Action::SetAbsoluteVolume(0.90).handle(&mut app).unwrap());
     }
+
+    #[test]
+    fn volume_limit_db() {
+        let wirehose = mock::WirehoseHandle::default();
+        let mut app = fixture(&wirehose);
+        app.config.max_volume_percent = 100.0;
+        app.config.enforce_max_volume = true;
+        app.config.volume_scale = VolumeScale::Db;
+
+        // The current volume is 0 dB
+
+        // +6 dB is past 100% so is not allowed
+        assert!(!Action::SetAbsoluteVolumeDb(6.0).handle(&mut app).unwrap());
+        assert!(!Action::SetRelativeVolume(0.10).handle(&mut app).unwrap());
+
+        // -6 dB is allowed
+        assert!(Action::SetAbsoluteVolumeDb(-6.0).handle(&mut app).unwrap());
+        assert!(Action::SetAbsoluteVolumeDb(0.0).handle(&mut app).unwrap());
+    }
+
+    #[test]
+    fn volume_limit_same_gain_on_every_scale() {
+        let wirehose = mock::WirehoseHandle::default();
+        let mut app = fixture(&wirehose);
+        app.config.max_volume_percent = 150.0;
+        app.config.enforce_max_volume = true;
+
+        // 150% on the cubic scale is about +10.6 dB
+        app.config.volume_scale = VolumeScale::Db;
+        assert!(Action::SetAbsoluteVolumeDb(10.0).handle(&mut app).unwrap());
+        assert!(!Action::SetAbsoluteVolumeDb(12.0).handle(&mut app).unwrap());
+
+        // and a linear volume of 3.375
+        app.config.volume_scale = VolumeScale::Linear;
+        assert!(Action::SetAbsoluteVolume(3.3).handle(&mut app).unwrap());
+        assert!(!Action::SetAbsoluteVolume(3.5).handle(&mut app).unwrap());
+    }
 }
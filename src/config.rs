@@ -3,16 +3,20 @@
 mod char_set;
 mod help;
 mod keybinding;
+mod meter;
 mod name_template;
 mod names;
+mod stream_restore;
 mod tag;
 mod theme;
+mod volume_scale;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::Context;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
@@ -22,6 +26,7 @@ use toml;
 
 use crate::app::{Action, TabKind};
 use crate::opt::Opt;
+use crate::wirehose::StateEventKind;
 
 #[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq))]
@@ -34,11 +39,21 @@ pub struct Config {
     pub theme: Theme,
     pub max_volume_percent: f32,
     pub enforce_max_volume: bool,
+    pub volume_scale: VolumeScale,
     pub keybindings: HashMap<KeyEvent, Action>,
     pub help: help::Help,
     pub names: Names,
     pub tab: TabKind,
     pub database_url: Option<String>,
+    pub database_scope: Option<String>,
+    pub journal_events: HashSet<StateEventKind>,
+    pub database_flush_interval: Duration,
+    pub database_peaks: bool,
+    pub database_level_history: bool,
+    pub database_queue_capacity: usize,
+    pub database_queue_overflow: QueueOverflow,
+    pub stream_restore: StreamRestore,
+    pub meter: Meter,
 }
 
 /// Represents a configuration deserialized from a file. This gets baked into a
@@ -51,6 +66,20 @@ struct ConfigFile {
     fps: Option<f32>,
     #[serde(default)]
     database_url: Option<String>,
+    #[serde(default)]
+    database_scope: Option<String>,
+    #[serde(default = "default_journal_events")]
+    journal_events: Vec<StateEventKind>,
+    #[serde(default = "default_database_flush_interval_ms")]
+    database_flush_interval_ms: u64,
+    #[serde(default)]
+    database_peaks: bool,
+    #[serde(default)]
+    database_level_history: bool,
+    #[serde(default = "default_database_queue_capacity")]
+    database_queue_capacity: usize,
+    #[serde(default)]
+    database_queue_overflow: QueueOverflow,
     #[serde(default = "default_mouse")]
     mouse: bool,
     #[serde(default = "default_peaks")]
@@ -63,6 +92,8 @@ struct ConfigFile {
     max_volume_percent: Option<f32>,
     #[serde(default = "default_enforce_max_volume")]
     enforce_max_volume: bool,
+    #[serde(default)]
+    volume_scale: VolumeScale,
     #[serde(
         default = "Keybinding::defaults",
         deserialize_with = "Keybinding::merge"
@@ -70,6 +101,10 @@ struct ConfigFile {
     keybindings: HashMap<KeyEvent, Action>,
     #[serde(default)]
     names: Names,
+    #[serde(default)]
+    stream_restore: StreamRestore,
+    #[serde(default)]
+    meter: Meter,
     #[serde(
         default = "CharSet::defaults",
         deserialize_with = "CharSet::merge"
@@ -90,6 +125,33 @@ pub enum Peaks {
     Auto,
 }
 
+/// How volume sliders, volume steps and volume labels map to linear volume.
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VolumeScale {
+    /// Cube root of the linear volume, like PulseAudio and pavucontrol
+    #[default]
+    Cubic,
+    Linear,
+    /// Decibels, with 100% at 0 dB
+    Db,
+}
+
+/// What to do when the database can't keep up and its queue is full.
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum QueueOverflow {
+    /// Discard the oldest queued object update, level sample or journal event
+    DropOldest,
+    /// Replace a queued change to the same object, or else discard the oldest
+    #[default]
+    Coalesce,
+    /// Wait for the database to catch up. Changes are queued from
+    /// `State::update` on the UI thread, so this freezes the interface until
+    /// the database drains the queue.
+    Block,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct Keybinding {
@@ -113,6 +175,81 @@ pub struct Names {
     pub overrides: Vec<NameOverride>,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct StreamRestore {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "StreamRestore::default_properties")]
+    pub properties: Vec<names::Tag>,
+    #[serde(default = "StreamRestore::default_restore")]
+    pub volume: bool,
+    #[serde(default = "StreamRestore::default_restore")]
+    pub mute: bool,
+    #[serde(default = "StreamRestore::default_restore")]
+    pub target: bool,
+}
+
+/// Peak meter ballistics and scale.
+#[derive(Deserialize, Debug, Clone)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct Meter {
+    #[serde(default, rename = "type")]
+    pub meter_type: MeterType,
+    /// Overrides the meter type's attack time constant
+    #[serde(default)]
+    pub attack_ms: Option<f32>,
+    /// Overrides the meter type's release time constant
+    #[serde(default)]
+    pub release_ms: Option<f32>,
+    #[serde(default = "Meter::default_floor_db")]
+    pub floor_db: f32,
+    #[serde(default = "Meter::default_ceiling_db")]
+    pub ceiling_db: f32,
+    #[serde(default)]
+    pub overload_db: f32,
+    /// How long to hold peaks, or 0 for no peak-hold marker
+    #[serde(default)]
+    pub peak_hold_ms: u64,
+    #[serde(default)]
+    pub source: MeterSource,
+    /// Show the level as a number beside the meter
+    #[serde(default)]
+    pub readout: bool,
+}
+
+/// Meter ballistics, which determine how quickly meters rise and fall.
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MeterType {
+    /// Averaging, rising and falling slowly
+    #[default]
+    Vu,
+    /// Peak programme meter, rising quickly and falling slowly
+    Ppm,
+    /// Sample peaks, rising instantly and falling off steadily
+    Digital,
+}
+
+/// The measurement shown by meters.
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MeterSource {
+    /// Sample peaks with the meter type's ballistics
+    #[default]
+    Peak,
+    /// RMS level over 300 ms
+    Rms,
+    /// Oversampled peaks over 400 ms
+    TruePeak,
+    /// EBU R128 momentary loudness over 400 ms
+    Momentary,
+    /// EBU R128 short-term loudness over 3 s
+    ShortTerm,
+}
+
 #[derive(PartialEq, Deserialize, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum OverrideType {
@@ -154,12 +291,15 @@ pub struct CharSet {
     pub meter_center_left_active: String,
     pub meter_center_right_inactive: String,
     pub meter_center_right_active: String,
+    pub meter_hold: String,
     pub dropdown_icon: String,
     pub dropdown_selector: String,
     pub dropdown_more: String,
     pub dropdown_border: BorderType,
     pub help_more: String,
     pub help_border: BorderType,
+    pub port_linked: String,
+    pub port_unlinked: String,
 }
 
 #[derive(Deserialize, Debug)]
@@ -182,6 +322,8 @@ pub struct Theme {
     pub meter_overload: Style,
     pub meter_center_inactive: Style,
     pub meter_center_active: Style,
+    pub meter_hold: Style,
+    pub meter_readout: Style,
     pub config_device: Style,
     pub config_profile: Style,
     pub dropdown_icon: Style,
@@ -192,6 +334,10 @@ pub struct Theme {
     pub help_border: Style,
     pub help_item: Style,
     pub help_more: Style,
+    pub status_error: Style,
+    pub port_linked: Style,
+    pub port_unlinked: Style,
+    pub port_picked: Style,
 }
 
 fn default_mouse() -> bool {
@@ -222,6 +368,35 @@ fn default_enforce_max_volume() -> bool {
     false
 }
 
+fn default_database_flush_interval_ms() -> u64 {
+    1000
+}
+
+fn default_database_queue_capacity() -> usize {
+    10000
+}
+
+fn default_journal_events() -> Vec<StateEventKind> {
+    // Everything except the high-frequency capture events
+    vec![
+        StateEventKind::DeviceEnumRoute,
+        StateEventKind::DeviceEnumProfile,
+        StateEventKind::DeviceProfile,
+        StateEventKind::DeviceProperties,
+        StateEventKind::DeviceRoute,
+        StateEventKind::MetadataMetadataName,
+        StateEventKind::MetadataProperty,
+        StateEventKind::ClientProperties,
+        StateEventKind::NodePositions,
+        StateEventKind::NodeProperties,
+        StateEventKind::NodeRate,
+        StateEventKind::NodeVolumes,
+        StateEventKind::NodeMute,
+        StateEventKind::Link,
+        StateEventKind::Removed,
+    ]
+}
+
 impl ConfigFile {
     /// Override configuration with command-line arguments.
     pub fn apply_opt(&mut self, opt: &Opt) {
@@ -298,6 +473,22 @@ impl TryFrom<ConfigFile> for Config {
             }
         }
 
+        let meter = &config_file.meter;
+        if meter.floor_db >= meter.ceiling_db {
+            anyhow::bail!(
+                "meter floor_db {} is not below ceiling_db {}",
+                meter.floor_db,
+                meter.ceiling_db
+            );
+        }
+        if [meter.attack_ms, meter.release_ms]
+            .into_iter()
+            .flatten()
+            .any(|time| time < 0.0)
+        {
+            anyhow::bail!("meter attack_ms and release_ms can't be negative");
+        }
+
         // Emulate signals. This is intentionally done after generating help.
         config_file
             .keybindings
@@ -312,6 +503,7 @@ impl TryFrom<ConfigFile> for Config {
                 .max_volume_percent
                 .unwrap_or_default(),
             enforce_max_volume: config_file.enforce_max_volume,
+            volume_scale: config_file.volume_scale,
             char_set,
             theme,
             keybindings: config_file.keybindings,
@@ -319,6 +511,17 @@ impl TryFrom<ConfigFile> for Config {
             names: config_file.names,
             tab: config_file.tab.unwrap_or_default(),
             database_url: config_file.database_url,
+            database_scope: config_file.database_scope,
+            journal_events: config_file.journal_events.into_iter().collect(),
+            database_flush_interval: Duration::from_millis(
+                config_file.database_flush_interval_ms,
+            ),
+            database_peaks: config_file.database_peaks,
+            database_level_history: config_file.database_level_history,
+            database_queue_capacity: config_file.database_queue_capacity,
+            database_queue_overflow: config_file.database_queue_overflow,
+            stream_restore: config_file.stream_restore,
+            meter: config_file.meter,
         })
     }
 }
@@ -387,6 +590,7 @@ pub mod strict {
         theme: String,
         max_volume_percent: Option<f32>,
         enforce_max_volume: bool,
+        volume_scale: VolumeScale,
         #[serde(deserialize_with = "keybindings")]
         keybindings: HashMap<KeyEvent, Action>,
         names: Names,
@@ -396,6 +600,15 @@ pub mod strict {
         themes: HashMap<String, Theme>,
         tab: Option<TabKind>,
         database_url: Option<String>,
+        database_scope: Option<String>,
+        journal_events: Vec<StateEventKind>,
+        database_flush_interval_ms: u64,
+        database_peaks: bool,
+        database_level_history: bool,
+        database_queue_capacity: usize,
+        database_queue_overflow: QueueOverflow,
+        stream_restore: StreamRestore,
+        meter: Meter,
     }
 
     impl From<ConfigFile> for super::ConfigFile {
@@ -409,12 +622,22 @@ pub mod strict {
                 theme: strict.theme,
                 max_volume_percent: strict.max_volume_percent,
                 enforce_max_volume: strict.enforce_max_volume,
+                volume_scale: strict.volume_scale,
                 keybindings: strict.keybindings,
                 names: strict.names,
                 char_sets: strict.char_sets,
                 themes: strict.themes,
                 tab: strict.tab,
                 database_url: strict.database_url,
+                database_scope: strict.database_scope,
+                journal_events: strict.journal_events,
+                database_flush_interval_ms: strict.database_flush_interval_ms,
+                database_peaks: strict.database_peaks,
+                database_level_history: strict.database_level_history,
+                database_queue_capacity: strict.database_queue_capacity,
+                database_queue_overflow: strict.database_queue_overflow,
+                stream_restore: strict.stream_restore,
+                meter: strict.meter,
             }
         }
     }
@@ -33,12 +33,15 @@ pub struct CharSetOverlay {
     meter_center_left_active: Option<String>,
     meter_center_right_inactive: Option<String>,
     meter_center_right_active: Option<String>,
+    meter_hold: Option<String>,
     dropdown_icon: Option<String>,
     dropdown_selector: Option<String>,
     dropdown_more: Option<String>,
     dropdown_border: Option<BorderTypeDef>,
     help_more: Option<String>,
     help_border: Option<BorderTypeDef>,
+    port_linked: Option<String>,
+    port_unlinked: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -115,10 +118,13 @@ impl TryFrom<CharSetOverlay> for CharSet {
         validate_and_set!(meter_center_left_active, 1);
         validate_and_set!(meter_center_right_inactive, 1);
         validate_and_set!(meter_center_right_active, 1);
+        validate_and_set!(meter_hold, 1);
         validate_and_set!(dropdown_icon, 1);
         validate_and_set!(dropdown_selector, 1);
         validate_and_set!(dropdown_more, 0);
         validate_and_set!(help_more, 0);
+        validate_and_set!(port_linked, 1);
+        validate_and_set!(port_unlinked, 1);
 
         if let Some(dropdown_border) = overlay.dropdown_border {
             char_set.dropdown_border = dropdown_border.into();
@@ -155,12 +161,15 @@ impl Default for CharSet {
             meter_center_left_active: String::from("▮"),
             meter_center_right_inactive: String::from("▮"),
             meter_center_right_active: String::from("▮"),
+            meter_hold: String::from("▮"),
             dropdown_icon: String::from("▼"),
             dropdown_selector: String::from(">"),
             dropdown_more: String::from("•••"),
             dropdown_border: BorderType::Rounded,
             help_more: String::from("•••"),
             help_border: BorderType::Rounded,
+            port_linked: String::from("●"),
+            port_unlinked: String::from("○"),
         }
     }
 }
@@ -196,12 +205,15 @@ impl CharSet {
             meter_center_left_active: String::from("█"),
             meter_center_right_inactive: String::from("█"),
             meter_center_right_active: String::from("█"),
+            meter_hold: String::from("┃"),
             dropdown_icon: String::from("▼"),
             dropdown_selector: String::from(">"),
             dropdown_more: String::from("•••"),
             dropdown_border: BorderType::Plain,
             help_more: String::from("•••"),
             help_border: BorderType::Plain,
+            port_linked: String::from("●"),
+            port_unlinked: String::from("○"),
         }
     }
 
@@ -227,12 +239,15 @@ impl CharSet {
             meter_center_left_active: String::from("["),
             meter_center_right_inactive: String::from("]"),
             meter_center_right_active: String::from("]"),
+            meter_hold: String::from("|"),
             dropdown_icon: String::from("\\"),
             dropdown_selector: String::from(">"),
             dropdown_more: String::from("~~~"),
             dropdown_border: BorderType::Plain,
             help_more: String::from("~~~"),
             help_border: BorderType::Plain,
+            port_linked: String::from("*"),
+            port_unlinked: String::from("."),
         }
     }
 
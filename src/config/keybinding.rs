@@ -47,6 +47,14 @@ impl Keybinding {
             (event(KeyCode::Char('8')), Action::SetAbsoluteVolume(0.80)),
             (event(KeyCode::Char('9')), Action::SetAbsoluteVolume(0.90)),
             (event(KeyCode::Char('0')), Action::SetAbsoluteVolume(1.00)),
+            (event(KeyCode::Char('[')), Action::SetRelativeBalance(-0.05)),
+            (event(KeyCode::Char(']')), Action::SetRelativeBalance(0.05)),
+            (event(KeyCode::Char('u')), Action::ToggleChannelLock),
+            (event(KeyCode::Char(',')), Action::SelectPreviousChannel),
+            (event(KeyCode::Char('.')), Action::SelectNextChannel),
+            (event(KeyCode::Char('f')), Action::FilterHistory),
+            (event(KeyCode::Char('n')), Action::NewVirtualDevice),
+            (event(KeyCode::Char('x')), Action::RemoveVirtualDevice),
             (event(KeyCode::Char('?')), Action::Help),
         ])
     }
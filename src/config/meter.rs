@@ -0,0 +1,74 @@
+//! Implementation for [`Meter`](`crate::config::Meter`).
+//! Defines the default ballistics of each meter type.
+
+use std::time::Duration;
+
+use crate::config::{Meter, MeterSource, MeterType};
+
+impl Meter {
+    pub fn default_floor_db() -> f32 {
+        -60.0
+    }
+
+    pub fn default_ceiling_db() -> f32 {
+        6.0
+    }
+
+    /// Returns the (attack, release) time constants in seconds. An attack of
+    /// 0 follows rising peaks instantly.
+    pub fn time_constants(&self) -> (f32, f32) {
+        let (attack_ms, release_ms) = match self.meter_type {
+            MeterType::Vu => (300.0, 300.0),
+            // Falls about 20 dB in 1.7 seconds
+            MeterType::Ppm => (10.0, 750.0),
+            // Falls about 20 dB per second
+            MeterType::Digital => (0.0, 450.0),
+        };
+        (
+            self.attack_ms.unwrap_or(attack_ms) / 1000.0,
+            self.release_ms.unwrap_or(release_ms) / 1000.0,
+        )
+    }
+
+    /// How long to hold peaks for the peak-hold marker, if enabled.
+    pub fn peak_hold(&self) -> Option<Duration> {
+        (self.peak_hold_ms > 0)
+            .then(|| Duration::from_millis(self.peak_hold_ms))
+    }
+}
+
+impl Default for Meter {
+    fn default() -> Self {
+        Self {
+            meter_type: MeterType::default(),
+            attack_ms: None,
+            release_ms: None,
+            floor_db: Self::default_floor_db(),
+            ceiling_db: Self::default_ceiling_db(),
+            overload_db: 0.0,
+            peak_hold_ms: 0,
+            source: MeterSource::default(),
+            readout: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_constants_override_meter_type() {
+        let meter: Meter = toml::from_str(
+            r#"
+            type = "ppm"
+            release_ms = 1500.0
+            peak_hold_ms = 2000
+            "#,
+        )
+        .unwrap();
+        assert_eq!(meter.time_constants(), (0.01, 1.5));
+        assert_eq!(meter.peak_hold(), Some(Duration::from_secs(2)));
+        assert_eq!(Meter::default().peak_hold(), None);
+    }
+}
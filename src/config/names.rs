@@ -165,7 +165,8 @@ impl NameResolver for state::Node {
         match self.props.media_class() {
             Some(media_class)
                 if media_class::is_sink(media_class)
-                    || media_class::is_source(media_class) =>
+                    || media_class::is_source(media_class)
+                    || media_class::is_midi_or_video_device(media_class) =>
             {
                 self.name_override(
                     state,
@@ -0,0 +1,140 @@
+//! Implementation for [`StreamRestore`](`crate::config::StreamRestore`).
+//! Defines default match rules and resolves the key that identifies a stream.
+
+use crate::config::names::{Tag, TagResolver};
+use crate::config::StreamRestore;
+use crate::wirehose::state;
+
+impl StreamRestore {
+    pub fn default_properties() -> Vec<Tag> {
+        vec![
+            "client:application.name".parse().unwrap(),
+            "node:node.name".parse().unwrap(),
+        ]
+    }
+
+    pub fn default_restore() -> bool {
+        true
+    }
+
+    /// Returns the key under which a stream's settings are saved.
+    ///
+    /// The key is built from the first configured property that the stream
+    /// has, so streams with the same value for that property share settings.
+    /// Returns None while the stream's client or device hasn't arrived yet
+    /// and an earlier property could still come from it, so a stream isn't
+    /// keyed by a fallback property it won't be keyed by later.
+    pub fn match_key(
+        &self,
+        state: &state::State,
+        node: &state::Node,
+    ) -> Option<String> {
+        for tag in &self.properties {
+            if let Some(value) = node.resolve_tag(state, tag) {
+                return Some(format!("{}={value}", tag.to_string()));
+            }
+            let is_pending = match tag {
+                Tag::Node(_) => false,
+                Tag::Device(_) => node
+                    .props
+                    .device_id()
+                    .is_some_and(|id| !state.devices.contains_key(id)),
+                Tag::Client(_) => node
+                    .props
+                    .client_id()
+                    .is_some_and(|id| !state.clients.contains_key(id)),
+            };
+            if is_pending {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+impl Default for StreamRestore {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            properties: Self::default_properties(),
+            volume: Self::default_restore(),
+            mute: Self::default_restore(),
+            target: Self::default_restore(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::wirehose::{ObjectId, PropertyStore};
+
+    #[test]
+    fn match_key_uses_first_resolvable_property() {
+        let stream_restore = StreamRestore::default();
+        let state = state::State::default();
+
+        let mut props = PropertyStore::default();
+        props.set_node_name(String::from("Firefox"));
+        let node = state::Node {
+            object_id: ObjectId::from_raw_id(57),
+            props,
+            ..Default::default()
+        };
+
+        // No client, so fall back to node.name
+        assert_eq!(
+            stream_restore.match_key(&state, &node),
+            Some(String::from("node:node.name=Firefox"))
+        );
+    }
+
+    #[test]
+    fn match_key_waits_for_client() {
+        let stream_restore = StreamRestore::default();
+        let mut state = state::State::default();
+        let client_id = ObjectId::from_raw_id(40);
+
+        let mut props = PropertyStore::default();
+        props.set_node_name(String::from("Firefox"));
+        props.set_client_id(client_id);
+        let node = state::Node {
+            object_id: ObjectId::from_raw_id(57),
+            props,
+            ..Default::default()
+        };
+        assert_eq!(stream_restore.match_key(&state, &node), None);
+
+        let mut props = PropertyStore::default();
+        props.set_application_name(String::from("Firefox Web Browser"));
+        state.clients.insert(
+            client_id,
+            state::Client {
+                object_id: client_id,
+                props,
+            },
+        );
+        assert_eq!(
+            stream_restore.match_key(&state, &node),
+            Some(String::from("client:application.name=Firefox Web Browser"))
+        );
+
+        // A client without the property falls back to node.name
+        state.clients.get_mut(&client_id).unwrap().props =
+            PropertyStore::default();
+        assert_eq!(
+            stream_restore.match_key(&state, &node),
+            Some(String::from("node:node.name=Firefox"))
+        );
+    }
+
+    #[test]
+    fn match_key_none_without_properties() {
+        let stream_restore = StreamRestore::default();
+        let state = state::State::default();
+        let node = state::Node::default();
+
+        assert_eq!(stream_restore.match_key(&state, &node), None);
+    }
+}
@@ -27,6 +27,8 @@ pub struct ThemeOverlay {
     meter_overload: Option<StyleDef>,
     meter_center_inactive: Option<StyleDef>,
     meter_center_active: Option<StyleDef>,
+    meter_hold: Option<StyleDef>,
+    meter_readout: Option<StyleDef>,
     config_device: Option<StyleDef>,
     config_profile: Option<StyleDef>,
     dropdown_icon: Option<StyleDef>,
@@ -37,6 +39,10 @@ pub struct ThemeOverlay {
     help_border: Option<StyleDef>,
     help_item: Option<StyleDef>,
     help_more: Option<StyleDef>,
+    status_error: Option<StyleDef>,
+    port_linked: Option<StyleDef>,
+    port_unlinked: Option<StyleDef>,
+    port_picked: Option<StyleDef>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -106,6 +112,8 @@ impl TryFrom<ThemeOverlay> for Theme {
         set!(meter_overload);
         set!(meter_center_inactive);
         set!(meter_center_active);
+        set!(meter_hold);
+        set!(meter_readout);
         set!(config_device);
         set!(config_profile);
         set!(dropdown_icon);
@@ -116,6 +124,10 @@ impl TryFrom<ThemeOverlay> for Theme {
         set!(help_border);
         set!(help_item);
         set!(help_more);
+        set!(status_error);
+        set!(port_linked);
+        set!(port_unlinked);
+        set!(port_picked);
 
         Ok(theme)
     }
@@ -141,6 +153,8 @@ impl Default for Theme {
             meter_overload: Style::default().fg(Color::Red),
             meter_center_inactive: Style::default().fg(Color::DarkGray),
             meter_center_active: Style::default().fg(Color::LightGreen),
+            meter_hold: Style::default().fg(Color::White),
+            meter_readout: Style::default(),
             config_device: Style::default(),
             config_profile: Style::default(),
             dropdown_icon: Style::default(),
@@ -153,6 +167,12 @@ impl Default for Theme {
             help_border: Style::default(),
             help_item: Style::default(),
             help_more: Style::default().fg(Color::DarkGray),
+            status_error: Style::default().fg(Color::Red),
+            port_linked: Style::default().fg(Color::LightGreen),
+            port_unlinked: Style::default().fg(Color::DarkGray),
+            port_picked: Style::default()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::REVERSED),
         }
     }
 }
@@ -185,6 +205,8 @@ impl Theme {
             meter_overload: Style::default().add_modifier(Modifier::BOLD),
             meter_center_inactive: Style::default().add_modifier(Modifier::DIM),
             meter_center_active: Style::default().add_modifier(Modifier::BOLD),
+            meter_hold: Style::default().add_modifier(Modifier::REVERSED),
+            meter_readout: Style::default(),
             config_device: Style::default(),
             config_profile: Style::default(),
             dropdown_icon: Style::default(),
@@ -196,6 +218,11 @@ impl Theme {
             help_border: Style::default(),
             help_item: Style::default(),
             help_more: Style::default(),
+            status_error: Style::default().add_modifier(Modifier::BOLD),
+            port_linked: Style::default().add_modifier(Modifier::BOLD),
+            port_unlinked: Style::default().add_modifier(Modifier::DIM),
+            port_picked: Style::default()
+                .add_modifier(Modifier::BOLD | Modifier::REVERSED),
         }
     }
 
@@ -218,6 +245,8 @@ impl Theme {
             meter_overload: Style::default(),
             meter_center_inactive: Style::default(),
             meter_center_active: Style::default(),
+            meter_hold: Style::default(),
+            meter_readout: Style::default(),
             config_device: Style::default(),
             config_profile: Style::default(),
             dropdown_icon: Style::default(),
@@ -228,6 +257,10 @@ impl Theme {
             help_border: Style::default(),
             help_item: Style::default(),
             help_more: Style::default(),
+            status_error: Style::default(),
+            port_linked: Style::default(),
+            port_unlinked: Style::default(),
+            port_picked: Style::default(),
         }
     }
 
@@ -0,0 +1,104 @@
+//! Implementation for [`VolumeScale`](`crate::config::VolumeScale`).
+//! Converts between linear volumes and positions on the volume slider, where
+//! a position of 1.0 is 100%.
+
+use crate::config::VolumeScale;
+
+/// Decibels covered by the slider from 0% to 100% on the dB scale.
+const DB_RANGE: f32 = 60.0;
+
+/// Converts a linear volume to decibels.
+fn to_db(volume: f32) -> f32 {
+    20.0 * volume.log10()
+}
+
+/// Converts decibels to a linear volume.
+fn from_db(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+impl VolumeScale {
+    /// The slider position of a linear volume.
+    pub fn from_linear(&self, volume: f32) -> f32 {
+        match self {
+            VolumeScale::Cubic => volume.cbrt(),
+            VolumeScale::Linear => volume,
+            VolumeScale::Db => (1.0 + to_db(volume) / DB_RANGE).max(0.0),
+        }
+    }
+
+    /// The linear volume at a slider position.
+    pub fn to_linear(&self, position: f32) -> f32 {
+        let position = position.max(0.0);
+        match self {
+            VolumeScale::Cubic => position.powi(3),
+            VolumeScale::Linear => position,
+            // The bottom of the slider is silence rather than -60 dB
+            VolumeScale::Db if position == 0.0 => 0.0,
+            VolumeScale::Db => from_db((position - 1.0) * DB_RANGE),
+        }
+    }
+
+    /// The slider position of a volume percentage on the cubic scale, which
+    /// is how `max_volume_percent` is given so that it's the same gain on
+    /// every scale.
+    pub fn from_percent(&self, percent: f32) -> f32 {
+        self.from_linear(VolumeScale::Cubic.to_linear(percent / 100.0))
+    }
+
+    /// The slider position of a volume in decibels.
+    pub fn from_db(&self, db: f32) -> f32 {
+        self.from_linear(from_db(db))
+    }
+
+    /// Formats a linear volume for the volume label. dB volumes are written
+    /// like the meter readout, without a space before the unit.
+    pub fn label(&self, volume: f32) -> String {
+        match self {
+            VolumeScale::Db if volume <= 0.0 => String::from("-infdB"),
+            VolumeScale::Db => format!("{:.1}dB", to_db(volume)),
+            _ => {
+                let percent = (self.from_linear(volume) * 100.0).round();
+                format!("{percent}%")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(scale: VolumeScale, position: f32) -> f32 {
+        let position = scale.from_linear(scale.to_linear(position));
+        (position * 1000.0).round() / 1000.0
+    }
+
+    #[test]
+    fn positions_round_trip() {
+        for scale in [VolumeScale::Cubic, VolumeScale::Linear, VolumeScale::Db]
+        {
+            for position in [0.0, 0.25, 0.5, 1.0, 1.5] {
+                assert_eq!(round_trip(scale, position), position);
+            }
+        }
+    }
+
+    #[test]
+    fn db_scale() {
+        let scale = VolumeScale::Db;
+        assert_eq!(scale.to_linear(1.0), 1.0);
+        assert_eq!(scale.to_linear(0.0), 0.0);
+        assert_eq!(scale.from_linear(from_db(-30.0)), 0.5);
+        assert_eq!(scale.from_linear(from_db(-90.0)), 0.0);
+
+        // 150% on the cubic scale is about +10.6 dB, not +30 dB
+        let max = scale.from_percent(150.0);
+        assert!((scale.to_linear(max) - 3.375).abs() < 1e-4);
+
+        assert_eq!(scale.label(1.0), "0.0dB");
+        assert_eq!(scale.label(from_db(-6.0)), "-6.0dB");
+        assert_eq!(scale.label(0.0), "-infdB");
+        assert_eq!(VolumeScale::Cubic.label(0.125), "50%");
+    }
+}
@@ -0,0 +1,313 @@
+//! Headless `wiremix db` subcommands for inspecting and maintaining the
+//! database.
+
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Result};
+use serde_json::json;
+
+use super::Database;
+use crate::opt::{DbCommand, ExportFormat, ExportTable};
+
+/// Run a database subcommand, writing its output to `out`. History and
+/// exports are read from the given scope.
+pub fn run(
+    database_url: &str,
+    scope: &str,
+    command: &DbCommand,
+    out: &mut dyn Write,
+) -> Result<()> {
+    // Don't create a new, empty database just to inspect it
+    if !Path::new(database_url).exists() {
+        bail!("Database '{database_url}' does not exist");
+    }
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let db = Database::new(database_url).await?.with_scope(scope);
+        run_command(&db, command, out).await
+    })
+}
+
+async fn run_command(
+    db: &Database,
+    command: &DbCommand,
+    out: &mut dyn Write,
+) -> Result<()> {
+    match command {
+        DbCommand::History { object, limit } => {
+            for event in db.load_object_events(object, *limit).await? {
+                writeln!(
+                    out,
+                    "{}\t{}\t{}\t{}",
+                    event.recorded_at,
+                    event.kind,
+                    event.object_id,
+                    event.payload_json
+                )?;
+            }
+        }
+        DbCommand::Export { format, table } => {
+            export(db, *format, *table, out).await?;
+        }
+        DbCommand::Prune { older_than } => {
+            let before = SystemTime::now()
+                .checked_sub(*older_than)
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            let pruned = db.prune(before).await?;
+            writeln!(
+                out,
+                "Removed {} events, {} level buckets, and {} sessions",
+                pruned.events, pruned.levels, pruned.sessions
+            )?;
+        }
+        DbCommand::Checkpoint => {
+            db.checkpoint().await?;
+        }
+        DbCommand::Schema => {
+            writeln!(out, "-- Schema version {}", db.schema_version().await?)?;
+            for sql in db.schema().await? {
+                writeln!(out, "{sql};")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn export(
+    db: &Database,
+    format: ExportFormat,
+    table: ExportTable,
+    out: &mut dyn Write,
+) -> Result<()> {
+    match (table, format) {
+        (ExportTable::Events, ExportFormat::Jsonl) => {
+            for event in db.load_events().await? {
+                // Embed the payload rather than nesting a JSON string
+                let payload: serde_json::Value =
+                    serde_json::from_str(&event.payload_json)?;
+                let line = json!({
+                    "id": event.id,
+                    "recorded_at": event.recorded_at,
+                    "session_id": event.session_id,
+                    "kind": event.kind,
+                    "object_id": event.object_id,
+                    "object_type": event.object_type,
+                    "object_serial": event.object_serial,
+                    "natural_key": event.natural_key,
+                    "payload": payload,
+                });
+                writeln!(out, "{line}")?;
+            }
+        }
+        (ExportTable::Events, ExportFormat::Csv) => {
+            writeln!(
+                out,
+                "id,recorded_at,session_id,kind,object_id,object_type,object_serial,natural_key,payload"
+            )?;
+            for event in db.load_events().await? {
+                write_csv_row(
+                    out,
+                    &[
+                        event.id.to_string(),
+                        event.recorded_at.to_string(),
+                        optional(event.session_id),
+                        event.kind,
+                        event.object_id.to_string(),
+                        event.object_type.unwrap_or_default(),
+                        optional(event.object_serial),
+                        event.natural_key.unwrap_or_default(),
+                        event.payload_json,
+                    ],
+                )?;
+            }
+        }
+        (ExportTable::Levels, ExportFormat::Jsonl) => {
+            for level in db.load_level_history().await? {
+                writeln!(out, "{}", serde_json::to_string(&level)?)?;
+            }
+        }
+        (ExportTable::Levels, ExportFormat::Csv) => {
+            writeln!(
+                out,
+                "node_id,natural_key,session_id,bucket_start,bucket_ms,channel,min,max,mean,samples"
+            )?;
+            for level in db.load_level_history().await? {
+                write_csv_row(
+                    out,
+                    &[
+                        level.node_id.to_string(),
+                        level.natural_key.unwrap_or_default(),
+                        optional(level.session_id),
+                        level.bucket_start.to_string(),
+                        level.bucket_ms.to_string(),
+                        level.channel.to_string(),
+                        level.min.to_string(),
+                        level.max.to_string(),
+                        level.mean.to_string(),
+                        level.samples.to_string(),
+                    ],
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// Write a CSV row, quoting fields as described in RFC 4180.
+fn write_csv_row(out: &mut dyn Write, fields: &[String]) -> Result<()> {
+    let row = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    writeln!(out, "{row}")?;
+    Ok(())
+}
+
+/// Parse an age such as "30d", "12h", "15m", "90s", or "2w".
+pub fn parse_age(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("\"{s}\" does not start with a number"))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("\"{s}\" needs a unit of s, m, h, d, or w")),
+    };
+
+    Ok(Duration::from_secs(number.saturating_mul(seconds)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::db::db::ObjectIdentity;
+    use crate::wirehose::{ObjectId, PropertyStore, StateEventKind};
+
+    #[test]
+    fn parse_age_units() {
+        assert_eq!(parse_age("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_age("30d"), Ok(Duration::from_secs(30 * 86400)));
+        assert_eq!(parse_age("2w"), Ok(Duration::from_secs(14 * 86400)));
+        assert!(parse_age("30").is_err());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("30y").is_err());
+    }
+
+    #[test]
+    fn csv_quoting() {
+        let mut out = Vec::new();
+        write_csv_row(
+            &mut out,
+            &[String::from("plain"), String::from(r#"{"a":1,"b":"c"}"#)],
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "plain,\"{\"\"a\"\":1,\"\"b\"\":\"\"c\"\"}\"\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn history_export_and_prune() {
+        let db_path = std::env::temp_dir().join("test_db_cli.db");
+        let _ = std::fs::remove_file(&db_path);
+        let db = Database::new(db_path.to_str().unwrap())
+            .await
+            .unwrap()
+            .with_scope("test/default");
+
+        let now = SystemTime::now();
+        let old = now - Duration::from_secs(60 * 86400);
+        // 57 is reused for a different node after 58 appears
+        for (recorded_at, object_id, serial, name) in [
+            (old, 57, 1, "alsa_output.usb"),
+            (now, 57, 1, "alsa_output.usb"),
+            (now, 58, 2, "alsa_output.hdmi"),
+            (now, 57, 3, "alsa_input.usb"),
+        ] {
+            let mut props = PropertyStore::default();
+            props.set_node_name(String::from(name));
+            props.set_object_serial(serial);
+            db.append_event(
+                recorded_at,
+                StateEventKind::NodeMute,
+                ObjectId::from_raw_id(object_id),
+                &ObjectIdentity::node(&props),
+                r#"{"kind":"NodeMute","object_id":57,"mute":true}"#,
+            )
+            .await
+            .unwrap();
+        }
+
+        let history = |object: &str| DbCommand::History {
+            object: String::from(object),
+            limit: 10,
+        };
+        let mut out = Vec::new();
+        run_command(&db, &history("57"), &mut out).await.unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 1);
+
+        let mut out = Vec::new();
+        run_command(&db, &history("alsa_output.usb"), &mut out)
+            .await
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 2);
+
+        let mut out = Vec::new();
+        let command = DbCommand::Export {
+            format: ExportFormat::Jsonl,
+            table: ExportTable::Events,
+        };
+        run_command(&db, &command, &mut out).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let first: serde_json::Value =
+            serde_json::from_str(out.lines().next().unwrap()).unwrap();
+        assert_eq!(first["kind"], "NodeMute");
+        assert_eq!(first["payload"]["mute"], true);
+        assert_eq!(first["natural_key"], "alsa_output.usb");
+
+        let mut out = Vec::new();
+        let command = DbCommand::Prune {
+            older_than: Duration::from_secs(30 * 86400),
+        };
+        run_command(&db, &command, &mut out).await.unwrap();
+        assert_eq!(db.load_events().await.unwrap().len(), 3);
+
+        let mut out = Vec::new();
+        run_command(&db, &DbCommand::Schema, &mut out)
+            .await
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("CREATE TABLE state_events"));
+
+        run_command(&db, &DbCommand::Checkpoint, &mut Vec::new())
+            .await
+            .unwrap();
+
+        let _ = std::fs::remove_file(db_path);
+    }
+}
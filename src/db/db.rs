@@ -1,74 +1,462 @@
 //! Database persistence for PipeWire state.
 
-use anyhow::Result;
-use turso::{Database as TursoDatabase, params};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Result};
+use nix::errno::Errno;
+use nix::fcntl::{Flock, FlockArg};
+use turso::{params, Connection};
+
+use super::levels::{
+    LevelBucket, COARSE_BUCKET_MS, FINE_BUCKET_MS, FINE_RETENTION_MS,
+};
+use super::migrations;
 
 use crate::wirehose::{
-    state::{Client as WireClient, Device, EnumRoute, Link, Metadata, Node, Profile, Route},
-    ObjectId,
+    state::{
+        Client as WireClient, Device, EnumRoute, Link, Metadata, Node, Port,
+        Profile, Route,
+    },
+    ObjectId, PropertyStore, StateEventKind,
 };
 
+/// How long to wait for another process to finish with the database file
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often to check whether another process has finished with the database
+/// file
+const BUSY_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
 /// Database operations that can be called from within an async context
+///
+/// The file can be shared by several processes, such as wiremix instances for
+/// different hosts or PipeWire remotes and `wiremix db` commands. They take
+/// turns: a process holds the file from [`Self::open()`] until
+/// [`Self::close()`], and other processes wait for it.
 pub struct Database {
-    db: TursoDatabase,
+    database_url: String,
+    /// A single connection shared by all operations, while the file is open
+    conn: Option<Connection>,
+    /// Excludes other processes while the file is open. Declared after the
+    /// connection so that it's released after the connection is closed.
+    lock: Option<Flock<File>>,
+    /// Identifies the instance and PipeWire remote that rows belong to
+    scope: String,
+    /// The current session, if one has begun
+    session: Option<Session>,
+}
+
+/// A row in the sessions table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub id: i64,
+    /// Unix time in milliseconds
+    pub started_at: i64,
+}
+
+/// Saved settings for streams matching a stream restore key.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamSettings {
+    pub volumes: Option<Vec<f32>>,
+    pub mute: Option<bool>,
+    /// node.name of the stream's target, if it has one
+    pub target: Option<String>,
+}
+
+/// A row from the state event journal.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct EventRecord {
+    pub id: i64,
+    /// Unix time in milliseconds
+    pub recorded_at: i64,
+    pub session_id: Option<i64>,
+    pub kind: String,
+    pub object_id: u32,
+    pub payload_json: String,
+    /// "node", "device" or "client", if known
+    pub object_type: Option<String>,
+    pub object_serial: Option<i64>,
+    pub natural_key: Option<String>,
+    /// A description of the object when the event was recorded
+    pub title: Option<String>,
+}
+
+/// Limits the history to one object.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryFilter {
+    pub object_id: u32,
+    /// Matches the object across restarts, and default node changes which
+    /// name it
+    pub natural_key: Option<String>,
+}
+
+/// A row from the level history.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct LevelRecord {
+    pub node_id: u32,
+    pub natural_key: Option<String>,
+    pub session_id: Option<i64>,
+    /// Unix time in milliseconds
+    pub bucket_start: i64,
+    pub bucket_ms: i64,
+    pub channel: u32,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub samples: i64,
+}
+
+/// Number of rows removed from each table by [`Database::prune()`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Pruned {
+    pub events: u64,
+    pub levels: u64,
+    pub sessions: u64,
+}
+
+/// Deserialize an optional JSON column.
+fn from_json_column<T: serde::de::DeserializeOwned>(
+    json: Option<String>,
+) -> Result<Option<T>> {
+    Ok(json.map(|json| serde_json::from_str(&json)).transpose()?)
+}
+
+/// Collect rows of state_events into records.
+async fn event_records(mut rows: turso::Rows) -> Result<Vec<EventRecord>> {
+    let mut events = Vec::new();
+    while let Some(row) = rows.next().await? {
+        events.push(EventRecord {
+            id: row.get(0)?,
+            recorded_at: row.get(1)?,
+            session_id: row.get(2)?,
+            kind: row.get(3)?,
+            object_id: row.get(4)?,
+            payload_json: row.get(5)?,
+            object_type: row.get(6)?,
+            object_serial: row.get(7)?,
+            natural_key: row.get(8)?,
+            title: row.get(9)?,
+        });
+    }
+
+    Ok(events)
+}
+
+/// Milliseconds since the Unix epoch, as stored in the database.
+/// Lock a file, creating it if needed, retrying while another process holds
+/// it.
+fn lock_file(path: &str) -> Result<Flock<File>> {
+    let deadline = Instant::now() + BUSY_TIMEOUT;
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    loop {
+        match Flock::lock(file, FlockArg::LockExclusiveNonblock) {
+            Ok(lock) => return Ok(lock),
+            Err((unlocked, Errno::EWOULDBLOCK)) => {
+                if Instant::now() >= deadline {
+                    bail!("{path} is held by another process");
+                }
+                file = unlocked;
+                thread::sleep(BUSY_RETRY_INTERVAL);
+            }
+            Err((_, errno)) => return Err(errno.into()),
+        }
+    }
+}
+
+pub fn unix_ms(time: SystemTime) -> Result<i64> {
+    Ok(time.duration_since(UNIX_EPOCH)?.as_millis() as i64)
+}
+
+/// The name of this host, for telling apart rows written by different hosts.
+pub fn host_name() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| String::from("localhost"))
+}
+
+/// The scope used for rows written by this host for the given PipeWire
+/// remote, unless one is configured.
+pub fn default_scope(remote: Option<&str>) -> String {
+    format!("{}/{}", host_name(), remote.unwrap_or("default"))
+}
+
+/// Stable key for a client, which stays the same across PipeWire restarts.
+fn client_key(props: &PropertyStore) -> Option<String> {
+    let name = props.application_name();
+    let binary = props.application_process_binary();
+    if name.is_none() && binary.is_none() {
+        return None;
+    }
+    Some(format!(
+        "{}|{}",
+        name.map(String::as_str).unwrap_or_default(),
+        binary.map(String::as_str).unwrap_or_default()
+    ))
+}
+
+/// Stable key for a node, which stays the same across PipeWire restarts.
+pub fn node_key(props: &PropertyStore) -> Option<String> {
+    props.node_name().cloned()
+}
+
+/// Stable key for a device, which stays the same across PipeWire restarts.
+fn device_key(props: &PropertyStore) -> Option<String> {
+    props.device_name().cloned()
+}
+
+/// Identifies the object a journaled event pertains to. PipeWire reuses
+/// object IDs, so the serial and natural key are recorded with the event to
+/// attach it to the same logical object across restarts.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjectIdentity {
+    /// "node", "device" or "client", if known
+    pub object_type: Option<&'static str>,
+    pub object_serial: Option<i64>,
+    pub natural_key: Option<String>,
+    pub title: Option<String>,
+}
+
+impl ObjectIdentity {
+    pub fn node(props: &PropertyStore) -> Self {
+        Self::new("node", props, node_key(props))
+    }
+
+    pub fn device(props: &PropertyStore) -> Self {
+        Self::new("device", props, device_key(props))
+    }
+
+    pub fn client(props: &PropertyStore) -> Self {
+        Self::new("client", props, client_key(props))
+    }
+
+    fn new(
+        object_type: &'static str,
+        props: &PropertyStore,
+        natural_key: Option<String>,
+    ) -> Self {
+        Self {
+            object_type: Some(object_type),
+            object_serial: props.object_serial().map(|s| *s as i64),
+            natural_key,
+            title: [
+                "node.description",
+                "device.description",
+                "media.name",
+                "node.name",
+                "device.name",
+                "application.name",
+            ]
+            .iter()
+            .find_map(|key| props.raw(key))
+            .map(String::from),
+        }
+    }
 }
 
 impl Database {
     /// Create a new database connection (this should be called from within an async context)
+    ///
+    /// The file is left open, waiting for any other process using it first.
     pub async fn new(database_url: &str) -> Result<Self> {
-        let db = turso::Builder::new_local(database_url).build().await?;
-        
+        let mut db = Self {
+            database_url: database_url.to_string(),
+            conn: None,
+            lock: None,
+            scope: String::new(),
+            session: None,
+        };
+        db.open().await?;
+
         // Run migrations
-        Self::run_migrations(&db).await?;
+        migrations::migrate(db.conn()?).await?;
 
-        Ok(Self { db })
+        Ok(db)
     }
-    
-    async fn run_migrations(db: &TursoDatabase) -> Result<()> {
-        // Read migration file
-        let up_sql = include_str!("../../migrations/0001_initial_schema.up.sql");
-        
-        // Execute migration
-        let conn = db.connect()?;
-        for statement in up_sql.split(';') {
-            let trimmed = statement.trim();
-            if !trimmed.is_empty() {
-                conn.execute(trimmed, ()).await?;
-            }
+
+    /// Open the database file if it isn't already open, waiting up to
+    /// [`BUSY_TIMEOUT`] for another process to close it.
+    pub async fn open(&mut self) -> Result<()> {
+        if self.conn.is_some() {
+            return Ok(());
         }
-        
+
+        let lock = lock_file(&format!("{}.lock", self.database_url))?;
+        let db = turso::Builder::new_local(&self.database_url)
+            .build()
+            .await?;
+        self.conn = Some(db.connect()?);
+        self.lock = Some(lock);
+
         Ok(())
     }
 
+    /// Close the database file so that other processes can use it.
+    pub fn close(&mut self) {
+        self.conn = None;
+        self.lock = None;
+    }
+
+    fn conn(&self) -> Result<&Connection> {
+        self.conn
+            .as_ref()
+            .ok_or_else(|| anyhow!("Database is not open"))
+    }
+
+    /// Read and write rows in the given scope. Rows in other scopes are left
+    /// alone.
+    pub fn with_scope(mut self, scope: &str) -> Self {
+        self.scope = scope.to_string();
+        self
+    }
+
+    fn session_id(&self) -> Option<i64> {
+        self.session.as_ref().map(|session| session.id)
+    }
+
+    /// Begin a transaction. Writes are not visible to other connections until
+    /// [`Self::commit()`] is called.
+    pub async fn begin(&self) -> Result<()> {
+        self.conn()?.execute("BEGIN", ()).await?;
+        Ok(())
+    }
+
+    /// Commit the current transaction.
+    pub async fn commit(&self) -> Result<()> {
+        self.conn()?.execute("COMMIT", ()).await?;
+        Ok(())
+    }
+
+    /// Roll back the current transaction.
+    pub async fn rollback(&self) -> Result<()> {
+        self.conn()?.execute("ROLLBACK", ()).await?;
+        Ok(())
+    }
+
+    /// Record the start of a wiremix session. Rows written afterwards are
+    /// tagged with the new session's ID.
+    pub async fn begin_session(&mut self, remote: Option<&str>) -> Result<i64> {
+        let conn = self.conn()?;
+
+        let started_at = unix_ms(SystemTime::now())?;
+        conn.execute(
+            "INSERT INTO sessions (started_at, remote, scope, host) VALUES (?, ?, ?, ?)",
+            params![started_at, remote, self.scope.as_str(), host_name()],
+        )
+        .await?;
+        let id = conn.last_insert_rowid();
+
+        self.session = Some(Session { id, started_at });
+        Ok(id)
+    }
+
+    /// Record the end of the current session, if any.
+    pub async fn end_session(&self) -> Result<()> {
+        let Some(session_id) = self.session_id() else {
+            return Ok(());
+        };
+
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE sessions SET ended_at = ? WHERE id = ?",
+            params![unix_ms(SystemTime::now())?, session_id],
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Mark objects which haven't been written since the current session began
+    /// as absent. This should be called once the initial objects have been
+    /// enumerated, so that objects removed while wiremix wasn't running aren't
+    /// mistaken for live ones. Returns the number of rows marked absent.
+    pub async fn mark_absent(&self) -> Result<u64> {
+        let Some(started_at) = self.session.as_ref().map(|s| s.started_at)
+        else {
+            return Ok(0);
+        };
+
+        let conn = self.conn()?;
+        let mut absent = 0;
+        for table in
+            ["clients", "nodes", "devices", "links", "ports", "metadata"]
+        {
+            absent += conn
+                .execute(
+                    &format!(
+                        "UPDATE {table} SET present = 0 WHERE scope = ? AND present AND (last_seen_at IS NULL OR last_seen_at < ?)"
+                    ),
+                    params![self.scope.as_str(), started_at],
+                )
+                .await?;
+        }
+
+        Ok(absent)
+    }
+
+    /// Returns the schema version of the database.
+    pub async fn schema_version(&self) -> Result<u32> {
+        migrations::current_version(self.conn()?).await
+    }
+
+    /// Migrate the schema up or down to the given version.
+    pub async fn migrate_to(&self, version: u32) -> Result<()> {
+        migrations::migrate_to(self.conn()?, version).await
+    }
+
     /// Insert or update a client.
     pub async fn upsert_client(&self, client: &WireClient) -> Result<()> {
-        let conn = self.db.connect()?;
-        
-        // PropertyStore doesn't implement Serialize, so we'll store as debug string for now
-        let props_debug = format!("{:?}", client.props);
+        let conn = self.conn()?;
+
+        let props_json = serde_json::to_string(&client.props)?;
         let object_id: u32 = client.object_id.into();
-        
+        let object_serial = client.props.object_serial().map(|s| *s as i64);
+
         conn.execute(
             r#"
-            INSERT INTO clients (object_id, props_json, updated_at)
-            VALUES (?, ?, CURRENT_TIMESTAMP)
-            ON CONFLICT(object_id) DO UPDATE SET
+            INSERT INTO clients (scope, object_id, props_json, object_serial, natural_key, session_id, present, last_seen_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, 1, ?, CURRENT_TIMESTAMP)
+            ON CONFLICT(scope, object_id) DO UPDATE SET
                 props_json = excluded.props_json,
+                object_serial = excluded.object_serial,
+                natural_key = excluded.natural_key,
+                session_id = excluded.session_id,
+                present = 1,
+                last_seen_at = excluded.last_seen_at,
                 updated_at = CURRENT_TIMESTAMP
             "#,
-            params![object_id, props_debug],
-        ).await?;
+            params![
+                self.scope.as_str(),
+                object_id,
+                props_json,
+                object_serial,
+                client_key(&client.props),
+                self.session_id(),
+                unix_ms(SystemTime::now())?,
+            ],
+        )
+        .await?;
 
         Ok(())
     }
 
     /// Insert or update a node.
     pub async fn upsert_node(&self, node: &Node) -> Result<()> {
-        let conn = self.db.connect()?;
-        
-        let props_debug = format!("{:?}", node.props);
+        let conn = self.conn()?;
+
+        let props_json = serde_json::to_string(&node.props)?;
         let object_id: u32 = node.object_id.into();
-        
+        let object_serial = node.props.object_serial().map(|s| *s as i64);
+
         // Convert optional fields to JSON strings
         let volumes_json = node.volumes.as_ref().map(|v| {
             serde_json::to_string(v).unwrap_or_else(|_| "[]".to_string())
@@ -82,10 +470,15 @@ impl Database {
 
         conn.execute(
             r#"
-            INSERT INTO nodes (object_id, props_json, volumes_json, mute, peaks_json, rate, positions_json, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP)
-            ON CONFLICT(object_id) DO UPDATE SET
+            INSERT INTO nodes (scope, object_id, props_json, volumes_json, mute, peaks_json, rate, positions_json, object_serial, natural_key, session_id, present, last_seen_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, ?, CURRENT_TIMESTAMP)
+            ON CONFLICT(scope, object_id) DO UPDATE SET
                 props_json = excluded.props_json,
+                object_serial = excluded.object_serial,
+                natural_key = excluded.natural_key,
+                session_id = excluded.session_id,
+                present = 1,
+                last_seen_at = excluded.last_seen_at,
                 volumes_json = excluded.volumes_json,
                 mute = excluded.mute,
                 peaks_json = excluded.peaks_json,
@@ -94,13 +487,18 @@ impl Database {
                 updated_at = CURRENT_TIMESTAMP
             "#,
             params![
+                self.scope.as_str(),
                 object_id,
-                props_debug,
+                props_json,
                 volumes_json,
                 node.mute,
                 peaks_json,
                 node.rate,
                 positions_json,
+                object_serial,
+                node_key(&node.props),
+                self.session_id(),
+                unix_ms(SystemTime::now())?,
             ],
         ).await?;
 
@@ -109,21 +507,36 @@ impl Database {
 
     /// Insert or update a device.
     pub async fn upsert_device(&self, device: &Device) -> Result<()> {
-        let conn = self.db.connect()?;
-        
-        let props_debug = format!("{:?}", device.props);
+        let conn = self.conn()?;
+
+        let props_json = serde_json::to_string(&device.props)?;
         let object_id: u32 = device.object_id.into();
-        
+        let object_serial = device.props.object_serial().map(|s| *s as i64);
+
         conn.execute(
             r#"
-            INSERT INTO devices (object_id, props_json, profile_index, updated_at)
-            VALUES (?, ?, ?, CURRENT_TIMESTAMP)
-            ON CONFLICT(object_id) DO UPDATE SET
+            INSERT INTO devices (scope, object_id, props_json, profile_index, object_serial, natural_key, session_id, present, last_seen_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, 1, ?, CURRENT_TIMESTAMP)
+            ON CONFLICT(scope, object_id) DO UPDATE SET
                 props_json = excluded.props_json,
                 profile_index = excluded.profile_index,
+                object_serial = excluded.object_serial,
+                natural_key = excluded.natural_key,
+                session_id = excluded.session_id,
+                present = 1,
+                last_seen_at = excluded.last_seen_at,
                 updated_at = CURRENT_TIMESTAMP
             "#,
-            params![object_id, props_debug, device.profile_index],
+            params![
+                self.scope.as_str(),
+                object_id,
+                props_json,
+                device.profile_index,
+                object_serial,
+                device_key(&device.props),
+                self.session_id(),
+                unix_ms(SystemTime::now())?,
+            ],
         ).await?;
 
         // Update profiles
@@ -145,23 +558,29 @@ impl Database {
     }
 
     /// Insert or update a device profile.
-    async fn upsert_device_profile(&self, device_id: ObjectId, profile_index: i32, profile: &Profile) -> Result<()> {
-        let conn = self.db.connect()?;
-        
+    async fn upsert_device_profile(
+        &self,
+        device_id: ObjectId,
+        profile_index: i32,
+        profile: &Profile,
+    ) -> Result<()> {
+        let conn = self.conn()?;
+
         let device_id_raw: u32 = device_id.into();
         let classes_json = serde_json::to_string(&profile.classes)
             .unwrap_or_else(|_| "[]".to_string());
 
         conn.execute(
             r#"
-            INSERT INTO device_profiles (device_id, profile_index, description, available, classes_json)
-            VALUES (?, ?, ?, ?, ?)
-            ON CONFLICT(device_id, profile_index) DO UPDATE SET
+            INSERT INTO device_profiles (scope, device_id, profile_index, description, available, classes_json)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(scope, device_id, profile_index) DO UPDATE SET
                 description = excluded.description,
                 available = excluded.available,
                 classes_json = excluded.classes_json
             "#,
             params![
+                self.scope.as_str(),
                 device_id_raw,
                 profile_index,
                 profile.description.clone(),
@@ -174,9 +593,14 @@ impl Database {
     }
 
     /// Insert or update a device route.
-    async fn upsert_device_route(&self, device_id: ObjectId, route_device: i32, route: &Route) -> Result<()> {
-        let conn = self.db.connect()?;
-        
+    async fn upsert_device_route(
+        &self,
+        device_id: ObjectId,
+        route_device: i32,
+        route: &Route,
+    ) -> Result<()> {
+        let conn = self.conn()?;
+
         let device_id_raw: u32 = device_id.into();
         let profiles_json = serde_json::to_string(&route.profiles)
             .unwrap_or_else(|_| "[]".to_string());
@@ -185,9 +609,9 @@ impl Database {
 
         conn.execute(
             r#"
-            INSERT INTO device_routes (device_id, route_index, route_device, profiles_json, description, available, volumes_json, mute)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
-            ON CONFLICT(device_id, route_index) DO UPDATE SET
+            INSERT INTO device_routes (scope, device_id, route_index, route_device, profiles_json, description, available, volumes_json, mute)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(scope, device_id, route_index) DO UPDATE SET
                 route_device = excluded.route_device,
                 profiles_json = excluded.profiles_json,
                 description = excluded.description,
@@ -196,6 +620,7 @@ impl Database {
                 mute = excluded.mute
             "#,
             params![
+                self.scope.as_str(),
                 device_id_raw,
                 route.index,
                 route_device,
@@ -211,9 +636,14 @@ impl Database {
     }
 
     /// Insert or update a device enum route.
-    async fn upsert_device_enum_route(&self, device_id: ObjectId, enum_route_index: i32, enum_route: &EnumRoute) -> Result<()> {
-        let conn = self.db.connect()?;
-        
+    async fn upsert_device_enum_route(
+        &self,
+        device_id: ObjectId,
+        enum_route_index: i32,
+        enum_route: &EnumRoute,
+    ) -> Result<()> {
+        let conn = self.conn()?;
+
         let device_id_raw: u32 = device_id.into();
         let profiles_json = serde_json::to_string(&enum_route.profiles)
             .unwrap_or_else(|_| "[]".to_string());
@@ -222,15 +652,16 @@ impl Database {
 
         conn.execute(
             r#"
-            INSERT INTO device_enum_routes (device_id, enum_route_index, description, available, profiles_json, devices_json)
-            VALUES (?, ?, ?, ?, ?, ?)
-            ON CONFLICT(device_id, enum_route_index) DO UPDATE SET
+            INSERT INTO device_enum_routes (scope, device_id, enum_route_index, description, available, profiles_json, devices_json)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(scope, device_id, enum_route_index) DO UPDATE SET
                 description = excluded.description,
                 available = excluded.available,
                 profiles_json = excluded.profiles_json,
                 devices_json = excluded.devices_json
             "#,
             params![
+                self.scope.as_str(),
                 device_id_raw,
                 enum_route_index,
                 enum_route.description.clone(),
@@ -244,56 +675,117 @@ impl Database {
     }
 
     /// Insert or update a link.
-    pub async fn upsert_link(&self, object_id: ObjectId, link: &Link) -> Result<()> {
-        let conn = self.db.connect()?;
-        
+    pub async fn upsert_link(
+        &self,
+        object_id: ObjectId,
+        link: &Link,
+    ) -> Result<()> {
+        let conn = self.conn()?;
+
         let object_id_raw: u32 = object_id.into();
         let output_id: u32 = link.output_id.into();
         let input_id: u32 = link.input_id.into();
-        
+        let output_port_id: u32 = link.output_port_id.into();
+        let input_port_id: u32 = link.input_port_id.into();
+
         conn.execute(
             r#"
-            INSERT INTO links (object_id, output_id, input_id, updated_at)
-            VALUES (?, ?, ?, CURRENT_TIMESTAMP)
-            ON CONFLICT(object_id) DO UPDATE SET
+            INSERT INTO links (scope, object_id, output_id, input_id, output_port_id, input_port_id, present, last_seen_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, 1, ?, CURRENT_TIMESTAMP)
+            ON CONFLICT(scope, object_id) DO UPDATE SET
                 output_id = excluded.output_id,
                 input_id = excluded.input_id,
+                output_port_id = excluded.output_port_id,
+                input_port_id = excluded.input_port_id,
+                present = 1,
+                last_seen_at = excluded.last_seen_at,
                 updated_at = CURRENT_TIMESTAMP
             "#,
-            params![object_id_raw, output_id, input_id],
-        ).await?;
+            params![
+                self.scope.as_str(),
+                object_id_raw,
+                output_id,
+                input_id,
+                output_port_id,
+                input_port_id,
+                unix_ms(SystemTime::now())?
+            ],
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Insert or update a port.
+    pub async fn upsert_port(&self, port: &Port) -> Result<()> {
+        let conn = self.conn()?;
+
+        let props_json = serde_json::to_string(&port.props)?;
+        let object_id: u32 = port.object_id.into();
+        let node_id: Option<u32> = port.node_id().map(Into::into);
+
+        conn.execute(
+            r#"
+            INSERT INTO ports (scope, object_id, props_json, direction, node_id, present, last_seen_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, 1, ?, CURRENT_TIMESTAMP)
+            ON CONFLICT(scope, object_id) DO UPDATE SET
+                props_json = excluded.props_json,
+                direction = excluded.direction,
+                node_id = excluded.node_id,
+                present = 1,
+                last_seen_at = excluded.last_seen_at,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+            params![
+                self.scope.as_str(),
+                object_id,
+                props_json,
+                port.direction.as_str(),
+                node_id,
+                unix_ms(SystemTime::now())?,
+            ],
+        )
+        .await?;
 
         Ok(())
     }
 
     /// Insert or update metadata.
     pub async fn upsert_metadata(&self, metadata: &Metadata) -> Result<()> {
-        let conn = self.db.connect()?;
-        
+        let conn = self.conn()?;
+
         let object_id: u32 = metadata.object_id.into();
         
         conn.execute(
             r#"
-            INSERT INTO metadata (object_id, metadata_name, updated_at)
-            VALUES (?, ?, CURRENT_TIMESTAMP)
-            ON CONFLICT(object_id) DO UPDATE SET
+            INSERT INTO metadata (scope, object_id, metadata_name, present, last_seen_at, updated_at)
+            VALUES (?, ?, ?, 1, ?, CURRENT_TIMESTAMP)
+            ON CONFLICT(scope, object_id) DO UPDATE SET
                 metadata_name = excluded.metadata_name,
+                present = 1,
+                last_seen_at = excluded.last_seen_at,
                 updated_at = CURRENT_TIMESTAMP
             "#,
-            params![object_id, metadata.metadata_name.clone()],
-        ).await?;
+            params![
+                self.scope.as_str(),
+                object_id,
+                metadata.metadata_name.clone(),
+                unix_ms(SystemTime::now())?
+            ],
+        )
+        .await?;
 
         // Update properties
         for (subject, properties) in &metadata.properties {
             for (key, value) in properties {
                 conn.execute(
                     r#"
-                    INSERT INTO metadata_properties (metadata_id, subject, key, value)
-                    VALUES (?, ?, ?, ?)
-                    ON CONFLICT(metadata_id, subject, key) DO UPDATE SET
+                    INSERT INTO metadata_properties (scope, metadata_id, subject, key, value)
+                    VALUES (?, ?, ?, ?, ?)
+                    ON CONFLICT(scope, metadata_id, subject, key) DO UPDATE SET
                         value = excluded.value
                     "#,
-                    params![object_id, subject, key.clone(), value.clone()],
+                    params![self.scope.as_str(), object_id, subject, key.clone(), value.clone()],
                 ).await?;
             }
         }
@@ -302,46 +794,1393 @@ impl Database {
     }
 
     /// Remove a specific metadata property.
-    pub async fn remove_metadata_property(&self, object_id: ObjectId, subject: u32, key: &str) -> Result<()> {
-        let conn = self.db.connect()?;
-        
+    pub async fn remove_metadata_property(
+        &self,
+        object_id: ObjectId,
+        subject: u32,
+        key: &str,
+    ) -> Result<()> {
+        let conn = self.conn()?;
+
         let object_id_raw: u32 = object_id.into();
         
         conn.execute(
-            "DELETE FROM metadata_properties WHERE metadata_id = ? AND subject = ? AND key = ?",
-            params![object_id_raw, subject, key],
+            "DELETE FROM metadata_properties WHERE scope = ? AND metadata_id = ? AND subject = ? AND key = ?",
+            params![self.scope.as_str(), object_id_raw, subject, key],
         ).await?;
 
         Ok(())
     }
 
     /// Clear all properties for a metadata subject.
-    pub async fn clear_metadata_properties(&self, object_id: ObjectId, subject: u32) -> Result<()> {
-        let conn = self.db.connect()?;
-        
+    pub async fn clear_metadata_properties(
+        &self,
+        object_id: ObjectId,
+        subject: u32,
+    ) -> Result<()> {
+        let conn = self.conn()?;
+
         let object_id_raw: u32 = object_id.into();
         
         conn.execute(
-            "DELETE FROM metadata_properties WHERE metadata_id = ? AND subject = ?",
-            params![object_id_raw, subject],
+            "DELETE FROM metadata_properties WHERE scope = ? AND metadata_id = ? AND subject = ?",
+            params![self.scope.as_str(), object_id_raw, subject],
         ).await?;
 
         Ok(())
     }
 
-    /// Remove an object from the database.
+    /// Append an event to the state event journal.
+    pub async fn append_event(
+        &self,
+        recorded_at: SystemTime,
+        kind: StateEventKind,
+        object_id: ObjectId,
+        identity: &ObjectIdentity,
+        payload_json: &str,
+    ) -> Result<()> {
+        let conn = self.conn()?;
+
+        let recorded_at = unix_ms(recorded_at)?;
+        let object_id_raw: u32 = object_id.into();
+
+        conn.execute(
+            r#"
+            INSERT INTO state_events (scope, recorded_at, kind, object_id, payload_json, session_id, object_type, object_serial, natural_key, title)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            params![
+                self.scope.as_str(),
+                recorded_at,
+                kind.as_str(),
+                object_id_raw,
+                payload_json,
+                self.session_id(),
+                identity.object_type,
+                identity.object_serial,
+                identity.natural_key.as_deref(),
+                identity.title.as_deref(),
+            ],
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Insert finished level buckets into the level history.
+    pub async fn insert_level_buckets(
+        &self,
+        buckets: &[LevelBucket],
+    ) -> Result<()> {
+        let conn = self.conn()?;
+
+        // A bucket can be written twice, such as when the channel count
+        // changes or a late reading reopens a bucket which was already
+        // written, so merge them
+        for bucket in buckets {
+            let node_id: u32 = bucket.node_id.into();
+            conn.execute(
+                r#"
+                INSERT INTO level_history (scope, node_id, natural_key, session_id, bucket_start, bucket_ms, channel, min, max, mean, samples)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(scope, node_id, session_id, bucket_ms, bucket_start, channel) DO UPDATE SET
+                    min = MIN(min, excluded.min),
+                    max = MAX(max, excluded.max),
+                    mean = (mean * samples + excluded.mean * excluded.samples)
+                        / (samples + excluded.samples),
+                    samples = samples + excluded.samples
+                "#,
+                params![
+                    self.scope.as_str(),
+                    node_id,
+                    bucket.natural_key.clone(),
+                    self.session_id(),
+                    bucket.bucket_start,
+                    FINE_BUCKET_MS,
+                    bucket.channel,
+                    bucket.min as f64,
+                    bucket.max as f64,
+                    bucket.mean as f64,
+                    bucket.samples,
+                ],
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Roll up fine level buckets which are older than the retention period
+    /// into coarse buckets.
+    pub async fn roll_up_levels(&self, now: SystemTime) -> Result<()> {
+        let conn = self.conn()?;
+
+        // Only roll up whole coarse buckets
+        let cutoff = unix_ms(now)? - FINE_RETENTION_MS;
+        let cutoff = cutoff - cutoff.rem_euclid(COARSE_BUCKET_MS);
+
+        conn.execute(
+            r#"
+            INSERT INTO level_history (scope, node_id, natural_key, session_id, bucket_start, bucket_ms, channel, min, max, mean, samples)
+            SELECT
+                scope,
+                node_id,
+                natural_key,
+                session_id,
+                bucket_start - (bucket_start % ?1),
+                ?1,
+                channel,
+                MIN(min),
+                MAX(max),
+                SUM(mean * samples) / SUM(samples),
+                SUM(samples)
+            FROM level_history
+            WHERE bucket_ms = ?2 AND bucket_start < ?3
+            GROUP BY scope, node_id, session_id, bucket_start - (bucket_start % ?1), channel
+            ON CONFLICT(scope, node_id, session_id, bucket_ms, bucket_start, channel) DO UPDATE SET
+                min = MIN(min, excluded.min),
+                max = MAX(max, excluded.max),
+                mean = (mean * samples + excluded.mean * excluded.samples)
+                    / (samples + excluded.samples),
+                samples = samples + excluded.samples
+            "#,
+            params![COARSE_BUCKET_MS, FINE_BUCKET_MS, cutoff],
+        )
+        .await?;
+
+        conn.execute(
+            "DELETE FROM level_history WHERE bucket_ms = ? AND bucket_start < ?",
+            params![FINE_BUCKET_MS, cutoff],
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Load all saved stream settings, keyed by match key.
+    pub async fn load_stream_settings(
+        &self,
+    ) -> Result<HashMap<String, StreamSettings>> {
+        let conn = self.conn()?;
+
+        let mut rows = conn
+            .query(
+                "SELECT match_key, volumes_json, mute, target FROM stream_restore WHERE scope = ?",
+                params![self.scope.as_str()],
+            )
+            .await?;
+
+        let mut settings = HashMap::new();
+        while let Some(row) = rows.next().await? {
+            settings.insert(
+                row.get::<String>(0)?,
+                StreamSettings {
+                    volumes: from_json_column(row.get(1)?)?,
+                    mute: row.get::<Option<bool>>(2)?,
+                    target: row.get::<Option<String>>(3)?,
+                },
+            );
+        }
+
+        Ok(settings)
+    }
+
+    /// Insert or update the saved settings for a stream restore key.
+    pub async fn save_stream_settings(
+        &self,
+        key: &str,
+        settings: &StreamSettings,
+    ) -> Result<()> {
+        let conn = self.conn()?;
+
+        let volumes_json = settings
+            .volumes
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+
+        conn.execute(
+            r#"
+            INSERT INTO stream_restore (scope, match_key, volumes_json, mute, target, updated_at)
+            VALUES (?, ?, ?, ?, ?, CURRENT_TIMESTAMP)
+            ON CONFLICT(scope, match_key) DO UPDATE SET
+                volumes_json = excluded.volumes_json,
+                mute = excluded.mute,
+                target = excluded.target,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+            params![self.scope.as_str(), key, volumes_json, settings.mute, settings.target.clone()],
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Load all clients which are present.
+    pub async fn load_clients(&self) -> Result<Vec<WireClient>> {
+        let conn = self.conn()?;
+
+        let mut rows = conn
+            .query(
+                "SELECT object_id, props_json FROM clients WHERE scope = ? AND present",
+                params![self.scope.as_str()],
+            )
+            .await?;
+
+        let mut clients = Vec::new();
+        while let Some(row) = rows.next().await? {
+            clients.push(WireClient {
+                object_id: ObjectId::from_raw_id(row.get(0)?),
+                props: serde_json::from_str(&row.get::<String>(1)?)?,
+            });
+        }
+
+        Ok(clients)
+    }
+
+    /// Load all nodes which are present.
+    pub async fn load_nodes(&self) -> Result<Vec<Node>> {
+        let conn = self.conn()?;
+
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT object_id, props_json, volumes_json, mute, peaks_json, rate, positions_json
+                FROM nodes WHERE scope = ? AND present
+                "#,
+                params![self.scope.as_str()],
+            )
+            .await?;
+
+        let mut nodes = Vec::new();
+        while let Some(row) = rows.next().await? {
+            nodes.push(Node {
+                object_id: ObjectId::from_raw_id(row.get(0)?),
+                props: serde_json::from_str(&row.get::<String>(1)?)?,
+                volumes: from_json_column(row.get(2)?)?,
+                mute: row.get(3)?,
+                peaks: from_json_column(row.get(4)?)?,
+                peak_holds: None,
+                levels: None,
+                rate: row.get(5)?,
+                positions: from_json_column(row.get(6)?)?,
+            });
+        }
+
+        Ok(nodes)
+    }
+
+    /// Load all devices which are present along with their profiles, routes,
+    /// and enum routes.
+    pub async fn load_devices(&self) -> Result<Vec<Device>> {
+        let conn = self.conn()?;
+
+        let mut devices = HashMap::new();
+
+        let mut rows = conn
+            .query(
+                "SELECT object_id, props_json, profile_index FROM devices WHERE scope = ? AND present",
+                params![self.scope.as_str()],
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let object_id = ObjectId::from_raw_id(row.get(0)?);
+            devices.insert(
+                object_id,
+                Device {
+                    object_id,
+                    props: serde_json::from_str(&row.get::<String>(1)?)?,
+                    profile_index: row.get(2)?,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT device_id, profile_index, description, available, classes_json
+                FROM device_profiles WHERE scope = ?
+                "#,
+                params![self.scope.as_str()],
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let Some(device) =
+                devices.get_mut(&ObjectId::from_raw_id(row.get(0)?))
+            else {
+                continue;
+            };
+            let index: i32 = row.get(1)?;
+            device.profiles.insert(
+                index,
+                Profile {
+                    index,
+                    description: row.get(2)?,
+                    available: row.get(3)?,
+                    classes: serde_json::from_str(&row.get::<String>(4)?)?,
+                },
+            );
+        }
+
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT device_id, route_index, route_device, profiles_json, description, available, volumes_json, mute
+                FROM device_routes WHERE scope = ?
+                "#,
+                params![self.scope.as_str()],
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let Some(device) =
+                devices.get_mut(&ObjectId::from_raw_id(row.get(0)?))
+            else {
+                continue;
+            };
+            let route_device: i32 = row.get(2)?;
+            device.routes.insert(
+                route_device,
+                Route {
+                    index: row.get(1)?,
+                    device: route_device,
+                    profiles: serde_json::from_str(&row.get::<String>(3)?)?,
+                    description: row.get(4)?,
+                    available: row.get(5)?,
+                    volumes: serde_json::from_str(&row.get::<String>(6)?)?,
+                    mute: row.get(7)?,
+                },
+            );
+        }
+
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT device_id, enum_route_index, description, available, profiles_json, devices_json
+                FROM device_enum_routes WHERE scope = ?
+                "#,
+                params![self.scope.as_str()],
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let Some(device) =
+                devices.get_mut(&ObjectId::from_raw_id(row.get(0)?))
+            else {
+                continue;
+            };
+            let index: i32 = row.get(1)?;
+            device.enum_routes.insert(
+                index,
+                EnumRoute {
+                    index,
+                    description: row.get(2)?,
+                    available: row.get(3)?,
+                    profiles: serde_json::from_str(&row.get::<String>(4)?)?,
+                    devices: serde_json::from_str(&row.get::<String>(5)?)?,
+                },
+            );
+        }
+
+        Ok(devices.into_values().collect())
+    }
+
+    /// Load all links which are present, along with their object IDs.
+    pub async fn load_links(&self) -> Result<Vec<(ObjectId, Link)>> {
+        let conn = self.conn()?;
+
+        let mut rows = conn
+            .query(
+                "SELECT object_id, output_id, input_id, output_port_id, input_port_id FROM links WHERE scope = ? AND present",
+                params![self.scope.as_str()],
+            )
+            .await?;
+
+        let mut links = Vec::new();
+        while let Some(row) = rows.next().await? {
+            links.push((
+                ObjectId::from_raw_id(row.get(0)?),
+                Link {
+                    output_id: ObjectId::from_raw_id(row.get(1)?),
+                    input_id: ObjectId::from_raw_id(row.get(2)?),
+                    output_port_id: ObjectId::from_raw_id(row.get(3)?),
+                    input_port_id: ObjectId::from_raw_id(row.get(4)?),
+                },
+            ));
+        }
+
+        Ok(links)
+    }
+
+    /// Load all ports which are present.
+    pub async fn load_ports(&self) -> Result<Vec<Port>> {
+        let conn = self.conn()?;
+
+        let mut rows = conn
+            .query(
+                "SELECT object_id, direction, props_json FROM ports WHERE scope = ? AND present",
+                params![self.scope.as_str()],
+            )
+            .await?;
+
+        let mut ports = Vec::new();
+        while let Some(row) = rows.next().await? {
+            ports.push(Port {
+                object_id: ObjectId::from_raw_id(row.get(0)?),
+                direction: serde_json::from_value(
+                    row.get::<String>(1)?.into(),
+                )?,
+                props: serde_json::from_str(&row.get::<String>(2)?)?,
+            });
+        }
+
+        Ok(ports)
+    }
+
+    /// Load all metadata objects which are present and their properties.
+    pub async fn load_metadatas(&self) -> Result<Vec<Metadata>> {
+        let conn = self.conn()?;
+
+        let mut metadatas = HashMap::new();
+
+        let mut rows = conn
+            .query(
+                "SELECT object_id, metadata_name FROM metadata WHERE scope = ? AND present",
+                params![self.scope.as_str()],
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let object_id = ObjectId::from_raw_id(row.get(0)?);
+            metadatas.insert(
+                object_id,
+                Metadata {
+                    object_id,
+                    metadata_name: row.get(1)?,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let mut rows = conn
+            .query(
+                "SELECT metadata_id, subject, key, value FROM metadata_properties WHERE scope = ?",
+                params![self.scope.as_str()],
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let Some(metadata) =
+                metadatas.get_mut(&ObjectId::from_raw_id(row.get(0)?))
+            else {
+                continue;
+            };
+            metadata
+                .properties
+                .entry(row.get(1)?)
+                .or_default()
+                .insert(row.get(2)?, row.get(3)?);
+        }
+
+        Ok(metadatas.into_values().collect())
+    }
+
+    /// Load journaled events for an object, most recent first. The object can
+    /// be an object ID or a natural key such as a node.name. Events are
+    /// matched on the identity recorded with them, so an object ID only
+    /// matches the object most recently journaled with that ID, not earlier
+    /// objects which PipeWire reused it for.
+    pub async fn load_object_events(
+        &self,
+        object: &str,
+        limit: u32,
+    ) -> Result<Vec<EventRecord>> {
+        let conn = self.conn()?;
+
+        let object_id = object.parse::<u32>().ok();
+        let natural_key = object_id.is_none().then_some(object);
+
+        // The session and serial of the object which last had the ID
+        let (session_id, object_serial) = match object_id {
+            Some(object_id) => {
+                let mut rows = conn
+                    .query(
+                        r#"
+                        SELECT session_id, object_serial
+                        FROM state_events
+                        WHERE scope = ? AND object_id = ?
+                        ORDER BY id DESC
+                        LIMIT 1
+                        "#,
+                        params![self.scope.as_str(), object_id],
+                    )
+                    .await?;
+                match rows.next().await? {
+                    Some(row) => {
+                        (row.get::<Option<i64>>(0)?, row.get::<Option<i64>>(1)?)
+                    }
+                    None => return Ok(Vec::new()),
+                }
+            }
+            None => (None, None),
+        };
+
+        let rows = conn
+            .query(
+                r#"
+                SELECT id, recorded_at, session_id, kind, object_id, payload_json,
+                    object_type, object_serial, natural_key, title
+                FROM state_events
+                WHERE scope = ?1
+                    AND (
+                        natural_key = ?2
+                        OR (object_id = ?3
+                            AND session_id IS ?4
+                            AND (?5 IS NULL OR object_serial IS NULL OR object_serial = ?5))
+                    )
+                ORDER BY recorded_at DESC, id DESC
+                LIMIT ?6
+                "#,
+                params![
+                    self.scope.as_str(),
+                    natural_key,
+                    object_id,
+                    session_id,
+                    object_serial,
+                    limit
+                ],
+            )
+            .await?;
+
+        event_records(rows).await
+    }
+
+    /// Load recent volume changes, default node changes, device additions
+    /// and removals, and profile changes, most recent first.
+    pub async fn load_history(
+        &self,
+        filter: Option<&HistoryFilter>,
+        limit: u32,
+    ) -> Result<Vec<EventRecord>> {
+        let conn = self.conn()?;
+
+        let object_id = filter
+            .filter(|filter| filter.natural_key.is_none())
+            .map(|filter| filter.object_id);
+        let natural_key =
+            filter.and_then(|filter| filter.natural_key.as_deref());
+        // Default node changes name the node inside a JSON-encoded value
+        let default_name = natural_key
+            .map(|name| {
+                serde_json::to_string(&serde_json::json!({ "name": name }))
+            })
+            .transpose()?
+            .and_then(|name| {
+                let quoted = serde_json::to_string(&name).ok()?;
+                Some(quoted[1..quoted.len() - 1].to_string())
+            });
+
+        // Devices are announced again whenever their properties change, so
+        // only the first announcement of a device in a session is an addition
+        let rows = conn
+            .query(
+                r#"
+                SELECT e.id, e.recorded_at, e.session_id, e.kind, e.object_id, e.payload_json,
+                    e.object_type, e.object_serial, e.natural_key, e.title
+                FROM state_events e
+                WHERE e.scope = ?1
+                    AND (
+                        e.kind IN ('NodeVolumes', 'NodeMute', 'DeviceProfile')
+                        OR (e.kind = 'Removed' AND e.object_type = 'device')
+                        OR (e.kind = 'DeviceProperties'
+                            AND NOT EXISTS (
+                                SELECT 1 FROM state_events p
+                                WHERE p.scope = e.scope
+                                    AND p.kind = 'DeviceProperties'
+                                    AND p.session_id IS e.session_id
+                                    AND p.object_id = e.object_id
+                                    AND p.object_serial IS e.object_serial
+                                    AND p.id < e.id
+                            ))
+                        OR (e.kind = 'MetadataProperty'
+                            AND json_extract(e.payload_json, '$.key') IN ('default.audio.sink', 'default.audio.source'))
+                    )
+                    AND (
+                        (?2 IS NULL AND ?3 IS NULL)
+                        OR e.object_id = ?2
+                        OR e.natural_key = ?3
+                        OR (e.kind = 'MetadataProperty' AND instr(e.payload_json, ?4) > 0)
+                    )
+                ORDER BY e.recorded_at DESC, e.id DESC
+                LIMIT ?5
+                "#,
+                params![
+                    self.scope.as_str(),
+                    object_id,
+                    natural_key,
+                    default_name,
+                    limit
+                ],
+            )
+            .await?;
+
+        event_records(rows).await
+    }
+
+    /// Load the scope's journaled events in the order they were recorded.
+    pub async fn load_events(&self) -> Result<Vec<EventRecord>> {
+        let conn = self.conn()?;
+
+        let rows = conn
+            .query(
+                r#"
+                SELECT id, recorded_at, session_id, kind, object_id, payload_json,
+                    object_type, object_serial, natural_key, title
+                FROM state_events
+                WHERE scope = ?
+                ORDER BY id
+                "#,
+                params![self.scope.as_str()],
+            )
+            .await?;
+
+        event_records(rows).await
+    }
+
+    /// Load the scope's level history in time order.
+    pub async fn load_level_history(&self) -> Result<Vec<LevelRecord>> {
+        let conn = self.conn()?;
+
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT node_id, natural_key, session_id, bucket_start, bucket_ms, channel, min, max, mean, samples
+                FROM level_history
+                WHERE scope = ?
+                ORDER BY bucket_start, node_id, channel
+                "#,
+                params![self.scope.as_str()],
+            )
+            .await?;
+
+        let mut levels = Vec::new();
+        while let Some(row) = rows.next().await? {
+            levels.push(LevelRecord {
+                node_id: row.get(0)?,
+                natural_key: row.get(1)?,
+                session_id: row.get(2)?,
+                bucket_start: row.get(3)?,
+                bucket_ms: row.get(4)?,
+                channel: row.get(5)?,
+                min: row.get(6)?,
+                max: row.get(7)?,
+                mean: row.get(8)?,
+                samples: row.get(9)?,
+            });
+        }
+
+        Ok(levels)
+    }
+
+    /// Remove history recorded before the given time, along with sessions
+    /// which ended before then and are no longer referenced.
+    pub async fn prune(&self, before: SystemTime) -> Result<Pruned> {
+        let conn = self.conn()?;
+        let before = unix_ms(before)?;
+
+        self.begin().await?;
+        let pruned = async {
+            let events = conn
+                .execute(
+                    "DELETE FROM state_events WHERE recorded_at < ?",
+                    params![before],
+                )
+                .await?;
+            let levels = conn
+                .execute(
+                    "DELETE FROM level_history WHERE bucket_start < ?",
+                    params![before],
+                )
+                .await?;
+            // Sessions which are still referenced are kept
+            let mut referenced = std::collections::HashSet::<i64>::new();
+            for table in [
+                "clients",
+                "nodes",
+                "devices",
+                "state_events",
+                "level_history",
+            ] {
+                let mut rows = conn
+                    .query(
+                        &format!(
+                            "SELECT DISTINCT session_id FROM {table} WHERE session_id IS NOT NULL"
+                        ),
+                        (),
+                    )
+                    .await?;
+                while let Some(row) = rows.next().await? {
+                    referenced.insert(row.get(0)?);
+                }
+            }
+
+            let mut ended = Vec::<i64>::new();
+            let mut rows = conn
+                .query(
+                    "SELECT id FROM sessions WHERE ended_at < ?",
+                    params![before],
+                )
+                .await?;
+            while let Some(row) = rows.next().await? {
+                ended.push(row.get(0)?);
+            }
+
+            let mut sessions = 0;
+            for session_id in ended {
+                if !referenced.contains(&session_id) {
+                    sessions += conn
+                        .execute(
+                            "DELETE FROM sessions WHERE id = ?",
+                            params![session_id],
+                        )
+                        .await?;
+                }
+            }
+
+            anyhow::Ok(Pruned {
+                events,
+                levels,
+                sessions,
+            })
+        }
+        .await;
+
+        match pruned {
+            Ok(pruned) => {
+                self.commit().await?;
+                Ok(pruned)
+            }
+            Err(e) => {
+                let _ = self.rollback().await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Copy the write-ahead log into the database file and truncate it. The
+    /// database file itself isn't compacted, since the engine doesn't
+    /// support VACUUM.
+    pub async fn checkpoint(&self) -> Result<()> {
+        let conn = self.conn()?;
+
+        let mut rows =
+            conn.query("PRAGMA wal_checkpoint(TRUNCATE)", ()).await?;
+        while rows.next().await?.is_some() {}
+
+        Ok(())
+    }
+
+    /// Returns the SQL definitions of all tables and indexes.
+    pub async fn schema(&self) -> Result<Vec<String>> {
+        let conn = self.conn()?;
+
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT sql FROM sqlite_schema
+                WHERE sql IS NOT NULL
+                ORDER BY type DESC, name
+                "#,
+                (),
+            )
+            .await?;
+
+        let mut schema = Vec::new();
+        while let Some(row) = rows.next().await? {
+            schema.push(row.get(0)?);
+        }
+
+        Ok(schema)
+    }
+
+    /// Mark a removed object as absent. Its last-known state is kept, as for
+    /// objects found missing by [`Self::mark_absent()`].
     pub async fn remove_object(&self, object_id: ObjectId) -> Result<()> {
-        let conn = self.db.connect()?;
-        
+        let conn = self.conn()?;
+
         let object_id_raw: u32 = object_id.into();
-        
-        // Try to delete from each table (cascading foreign keys will handle related records)
-        let _ = conn.execute("DELETE FROM clients WHERE object_id = ?", params![object_id_raw]).await;
-        let _ = conn.execute("DELETE FROM nodes WHERE object_id = ?", params![object_id_raw]).await;
-        let _ = conn.execute("DELETE FROM devices WHERE object_id = ?", params![object_id_raw]).await;
-        let _ = conn.execute("DELETE FROM links WHERE object_id = ?", params![object_id_raw]).await;
-        let _ = conn.execute("DELETE FROM metadata WHERE object_id = ?", params![object_id_raw]).await;
+        for table in
+            ["clients", "nodes", "devices", "links", "ports", "metadata"]
+        {
+            conn.execute(
+                &format!(
+                    "UPDATE {table} SET present = 0 WHERE scope = ? AND object_id = ?"
+                ),
+                params![self.scope.as_str(), object_id_raw],
+            )
+            .await?;
+        }
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn props_json_is_queryable() {
+        let db_path = std::env::temp_dir().join("test_props_json.db");
+        let _ = std::fs::remove_file(&db_path);
+        let db = Database::new(db_path.to_str().unwrap()).await.unwrap();
+
+        let mut props = PropertyStore::default();
+        props.set_node_name(String::from("alsa_output.usb"));
+        props.set_object_serial(57);
+        let node = Node {
+            object_id: ObjectId::from_raw_id(57),
+            props,
+            ..Default::default()
+        };
+        db.upsert_node(&node).await.unwrap();
+
+        let conn = db.conn().unwrap();
+        let mut rows = conn
+            .query(
+                r#"SELECT json_extract(props_json, '$."node.name"'), props_json
+                FROM nodes WHERE object_id = ?"#,
+                params![57],
+            )
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get::<String>(0).unwrap(), "alsa_output.usb");
+
+        let props: PropertyStore =
+            serde_json::from_str(&row.get::<String>(1).unwrap()).unwrap();
+        assert_eq!(props.node_name(), Some(&String::from("alsa_output.usb")));
+        assert_eq!(props.object_serial(), Some(&57));
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[tokio::test]
+    async fn append_event_is_journaled() {
+        let db_path = std::env::temp_dir().join("test_append_event.db");
+        let _ = std::fs::remove_file(&db_path);
+        let db = Database::new(db_path.to_str().unwrap()).await.unwrap();
+
+        let event = crate::wirehose::StateEvent::NodeMute {
+            object_id: ObjectId::from_raw_id(42),
+            mute: true,
+        };
+        let payload = serde_json::to_string(&event).unwrap();
+        let mut props = PropertyStore::default();
+        props.set_node_name(String::from("alsa_output.usb"));
+        props.set_object_serial(570);
+        db.append_event(
+            SystemTime::now(),
+            event.kind(),
+            event.object_id(),
+            &ObjectIdentity::node(&props),
+            &payload,
+        )
+        .await
+        .unwrap();
+
+        let conn = db.conn().unwrap();
+        let mut rows = conn
+            .query("SELECT kind, object_id, payload_json FROM state_events", ())
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get::<String>(0).unwrap(), "NodeMute");
+        assert_eq!(row.get::<i64>(1).unwrap(), 42);
+        assert_eq!(row.get::<String>(2).unwrap(), payload);
+        assert!(rows.next().await.unwrap().is_none());
+
+        let events = db.load_events().await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].object_type.as_deref(), Some("node"));
+        assert_eq!(events[0].object_serial, Some(570));
+        assert_eq!(events[0].natural_key.as_deref(), Some("alsa_output.usb"));
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[tokio::test]
+    async fn rows_carry_stable_identity() {
+        let db_path = std::env::temp_dir().join("test_stable_identity.db");
+        let _ = std::fs::remove_file(&db_path);
+        let mut db = Database::new(db_path.to_str().unwrap()).await.unwrap();
+        let session_id = db.begin_session(Some("test-remote")).await.unwrap();
+
+        let mut props = PropertyStore::default();
+        props.set_application_name(String::from("Firefox"));
+        props.set_application_process_binary(String::from("firefox"));
+        props.set_object_serial(1234);
+        let client = WireClient {
+            object_id: ObjectId::from_raw_id(57),
+            props,
+        };
+        db.upsert_client(&client).await.unwrap();
+        db.end_session().await.unwrap();
+
+        let conn = db.conn().unwrap();
+        let mut rows = conn
+            .query(
+                "SELECT object_serial, natural_key, session_id FROM clients",
+                (),
+            )
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get::<i64>(0).unwrap(), 1234);
+        assert_eq!(row.get::<String>(1).unwrap(), "Firefox|firefox");
+        assert_eq!(row.get::<i64>(2).unwrap(), session_id);
+
+        let mut rows = conn
+            .query(
+                "SELECT remote, ended_at FROM sessions WHERE id = ?",
+                params![session_id],
+            )
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get::<String>(0).unwrap(), "test-remote");
+        assert!(row.get::<i64>(1).is_ok());
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[tokio::test]
+    async fn stale_rows_marked_absent() {
+        let db_path = std::env::temp_dir().join("test_stale_rows.db");
+        let _ = std::fs::remove_file(&db_path);
+        let mut db = Database::new(db_path.to_str().unwrap()).await.unwrap();
+
+        let node = |id| Node {
+            object_id: ObjectId::from_raw_id(id),
+            ..Default::default()
+        };
+
+        db.begin_session(None).await.unwrap();
+        db.upsert_node(&node(57)).await.unwrap();
+        db.upsert_node(&node(58)).await.unwrap();
+        db.end_session().await.unwrap();
+
+        // Only 57 is announced in the next session
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        db.begin_session(None).await.unwrap();
+        db.upsert_node(&node(57)).await.unwrap();
+        assert_eq!(db.mark_absent().await.unwrap(), 1);
+
+        let nodes = db.load_nodes().await.unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].object_id, ObjectId::from_raw_id(57));
+
+        // The absent row is kept until the object reappears
+        let mut rows = db
+            .conn()
+            .unwrap()
+            .query(
+                "SELECT present, last_seen_at FROM nodes WHERE object_id = 58",
+                (),
+            )
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert!(!row.get::<bool>(0).unwrap());
+        assert!(row.get::<i64>(1).is_ok());
+
+        db.upsert_node(&node(58)).await.unwrap();
+        assert_eq!(db.load_nodes().await.unwrap().len(), 2);
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    fn rates(nodes: Vec<Node>) -> Vec<(u32, Option<u32>)> {
+        let mut rates: Vec<_> = nodes
+            .into_iter()
+            .map(|node| (u32::from(node.object_id), node.rate))
+            .collect();
+        rates.sort();
+        rates
+    }
+
+    fn node_with_rate(id: u32, rate: u32) -> Node {
+        Node {
+            object_id: ObjectId::from_raw_id(id),
+            rate: Some(rate),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn scopes_are_separate() {
+        let db_path = std::env::temp_dir().join("test_scopes.db");
+        let _ = std::fs::remove_file(&db_path);
+        let url = db_path.to_str().unwrap();
+
+        let mut laptop = Database::new(url)
+            .await
+            .unwrap()
+            .with_scope("laptop/default");
+        laptop.begin_session(None).await.unwrap();
+        laptop
+            .upsert_node(&node_with_rate(57, 44100))
+            .await
+            .unwrap();
+        laptop.close();
+
+        // The same object ID on a different host
+        let mut desktop = Database::new(url)
+            .await
+            .unwrap()
+            .with_scope("desktop/default");
+        desktop.begin_session(None).await.unwrap();
+        desktop
+            .upsert_node(&node_with_rate(57, 48000))
+            .await
+            .unwrap();
+        desktop
+            .upsert_node(&node_with_rate(58, 48000))
+            .await
+            .unwrap();
+        assert_eq!(
+            rates(desktop.load_nodes().await.unwrap()),
+            vec![(57, Some(48000)), (58, Some(48000))]
+        );
+        desktop.close();
+
+        // Removing an object leaves other scopes alone
+        laptop.open().await.unwrap();
+        assert_eq!(
+            rates(laptop.load_nodes().await.unwrap()),
+            vec![(57, Some(44100))]
+        );
+        laptop
+            .remove_object(ObjectId::from_raw_id(57))
+            .await
+            .unwrap();
+        assert!(laptop.load_nodes().await.unwrap().is_empty());
+        // The removed node's last-known state is kept
+        let mut rows = laptop
+            .conn()
+            .unwrap()
+            .query(
+                "SELECT present FROM nodes WHERE scope = ? AND object_id = 57",
+                params!["laptop/default"],
+            )
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert!(!row.get::<bool>(0).unwrap());
+        drop(rows);
+        laptop.close();
+
+        desktop.open().await.unwrap();
+        assert_eq!(desktop.load_nodes().await.unwrap().len(), 2);
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[tokio::test]
+    async fn history_is_scoped() {
+        let db_path = std::env::temp_dir().join("test_history_scopes.db");
+        let _ = std::fs::remove_file(&db_path);
+        let url = db_path.to_str().unwrap();
+
+        let object_id = ObjectId::from_raw_id(57);
+        async fn append(db: &Database) -> Result<()> {
+            db.append_event(
+                SystemTime::now(),
+                StateEventKind::NodeMute,
+                ObjectId::from_raw_id(57),
+                &ObjectIdentity::default(),
+                r#"{"kind":"NodeMute","object_id":57,"mute":true}"#,
+            )
+            .await
+        }
+
+        let mut laptop = Database::new(url)
+            .await
+            .unwrap()
+            .with_scope("laptop/default");
+        append(&laptop).await.unwrap();
+        laptop
+            .insert_level_buckets(&[LevelBucket {
+                node_id: object_id,
+                natural_key: None,
+                bucket_start: 1000,
+                channel: 0,
+                min: 0.1,
+                max: 0.2,
+                mean: 0.15,
+                samples: 10,
+            }])
+            .await
+            .unwrap();
+        laptop.close();
+
+        let desktop = Database::new(url)
+            .await
+            .unwrap()
+            .with_scope("desktop/default");
+        append(&desktop).await.unwrap();
+        assert_eq!(desktop.load_events().await.unwrap().len(), 1);
+        assert!(desktop.load_level_history().await.unwrap().is_empty());
+        drop(desktop);
+
+        laptop.open().await.unwrap();
+        assert_eq!(laptop.load_events().await.unwrap().len(), 1);
+        assert_eq!(laptop.load_level_history().await.unwrap().len(), 1);
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    /// Set in the child process of [`processes_take_turns`]
+    const CHILD_DATABASE_URL: &str = "WIREMIX_TEST_CHILD_DATABASE_URL";
+
+    #[tokio::test]
+    async fn processes_take_turns() {
+        if let Ok(url) = std::env::var(CHILD_DATABASE_URL) {
+            let mut desktop = Database::new(&url)
+                .await
+                .unwrap()
+                .with_scope("desktop/default");
+            desktop.begin_session(None).await.unwrap();
+            desktop
+                .upsert_node(&node_with_rate(57, 48000))
+                .await
+                .unwrap();
+            return;
+        }
+
+        let db_path = std::env::temp_dir().join("test_processes.db");
+        let _ = std::fs::remove_file(&db_path);
+        let url = db_path.to_str().unwrap();
+
+        let mut laptop = Database::new(url)
+            .await
+            .unwrap()
+            .with_scope("laptop/default");
+        laptop.begin_session(None).await.unwrap();
+
+        let mut child =
+            std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "db::db::tests::processes_take_turns"])
+                .env(CHILD_DATABASE_URL, url)
+                .stdout(std::process::Stdio::null())
+                .spawn()
+                .unwrap();
+
+        // The other process waits while the file is open
+        std::thread::sleep(Duration::from_millis(500));
+        assert!(child.try_wait().unwrap().is_none());
+        laptop
+            .upsert_node(&node_with_rate(57, 44100))
+            .await
+            .unwrap();
+        laptop.close();
+
+        assert!(child.wait().unwrap().success());
+
+        laptop.open().await.unwrap();
+        assert_eq!(
+            rates(laptop.load_nodes().await.unwrap()),
+            vec![(57, Some(44100))]
+        );
+        laptop.close();
+
+        let desktop = Database::new(url)
+            .await
+            .unwrap()
+            .with_scope("desktop/default");
+        assert_eq!(
+            rates(desktop.load_nodes().await.unwrap()),
+            vec![(57, Some(48000))]
+        );
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[tokio::test]
+    async fn history_follows_removed_devices() {
+        let db_path = std::env::temp_dir().join("test_history_devices.db");
+        let _ = std::fs::remove_file(&db_path);
+        let mut db = Database::new(db_path.to_str().unwrap())
+            .await
+            .unwrap()
+            .with_scope("test/default");
+        db.begin_session(None).await.unwrap();
+
+        let mut device = PropertyStore::default();
+        device.set_device_name(String::from("alsa_card.usb"));
+        device.set_device_description(String::from("USB Audio"));
+        device.set_object_serial(40);
+        let mut node = PropertyStore::default();
+        node.set_node_name(String::from("alsa_output.hdmi"));
+        node.set_node_description(String::from("HDMI Output"));
+        node.set_object_serial(41);
+
+        // The device is announced twice, removed, then its ID is reused
+        let object_id = ObjectId::from_raw_id(40);
+        for (kind, identity) in [
+            (
+                StateEventKind::DeviceProperties,
+                ObjectIdentity::device(&device),
+            ),
+            (
+                StateEventKind::DeviceProperties,
+                ObjectIdentity::device(&device),
+            ),
+            (StateEventKind::Removed, ObjectIdentity::device(&device)),
+            (StateEventKind::NodeMute, ObjectIdentity::node(&node)),
+        ] {
+            db.append_event(
+                SystemTime::now(),
+                kind,
+                object_id,
+                &identity,
+                "{}",
+            )
+            .await
+            .unwrap();
+        }
+
+        let events = db.load_history(None, 10).await.unwrap();
+        let kinds: Vec<_> =
+            events.iter().map(|event| event.kind.as_str()).collect();
+        assert_eq!(kinds, ["NodeMute", "Removed", "DeviceProperties"]);
+        assert_eq!(events[1].title.as_deref(), Some("USB Audio"));
+        assert_eq!(events[1].object_type.as_deref(), Some("device"));
+
+        let filter = HistoryFilter {
+            object_id: 40,
+            natural_key: Some(String::from("alsa_card.usb")),
+        };
+        let events = db.load_history(Some(&filter), 10).await.unwrap();
+        let kinds: Vec<_> =
+            events.iter().map(|event| event.kind.as_str()).collect();
+        assert_eq!(kinds, ["Removed", "DeviceProperties"]);
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[tokio::test]
+    async fn level_history_rolls_up() {
+        let db_path = std::env::temp_dir().join("test_level_history.db");
+        let _ = std::fs::remove_file(&db_path);
+        let db = Database::new(db_path.to_str().unwrap()).await.unwrap();
+
+        let bucket = |bucket_start, min, max, mean| LevelBucket {
+            node_id: ObjectId::from_raw_id(57),
+            natural_key: Some(String::from("alsa_output.usb")),
+            bucket_start,
+            channel: 0,
+            min,
+            max,
+            mean,
+            samples: 10,
+        };
+        // Two buckets in the first minute, one in the second
+        db.insert_level_buckets(&[
+            bucket(0, 0.1, 0.5, 0.2),
+            bucket(1000, 0.2, 0.9, 0.4),
+            bucket(60_000, 0.0, 0.1, 0.1),
+        ])
+        .await
+        .unwrap();
+
+        // One minute is past retention, the other is not
+        let now = UNIX_EPOCH
+            + std::time::Duration::from_millis(
+                (FINE_RETENTION_MS + COARSE_BUCKET_MS) as u64,
+            );
+        db.roll_up_levels(now).await.unwrap();
+
+        let conn = db.conn().unwrap();
+        let mut rows = conn
+            .query(
+                "SELECT bucket_start, bucket_ms, min, max, mean, samples
+                FROM level_history ORDER BY bucket_ms, bucket_start",
+                (),
+            )
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get::<i64>(0).unwrap(), 60_000);
+        assert_eq!(row.get::<i64>(1).unwrap(), FINE_BUCKET_MS);
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get::<i64>(0).unwrap(), 0);
+        assert_eq!(row.get::<i64>(1).unwrap(), COARSE_BUCKET_MS);
+        assert!((row.get::<f64>(2).unwrap() - 0.1).abs() < 1e-6);
+        assert!((row.get::<f64>(3).unwrap() - 0.9).abs() < 1e-6);
+        assert!((row.get::<f64>(4).unwrap() - 0.3).abs() < 1e-6);
+        assert_eq!(row.get::<i64>(5).unwrap(), 20);
+        assert!(rows.next().await.unwrap().is_none());
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[tokio::test]
+    async fn level_buckets_written_twice_are_merged() {
+        let db_path = std::env::temp_dir().join("test_level_merge.db");
+        let _ = std::fs::remove_file(&db_path);
+        let mut db = Database::new(db_path.to_str().unwrap()).await.unwrap();
+        db.begin_session(None).await.unwrap();
+
+        let bucket = |min, max, mean, samples| LevelBucket {
+            node_id: ObjectId::from_raw_id(57),
+            natural_key: None,
+            bucket_start: 1000,
+            channel: 0,
+            min,
+            max,
+            mean,
+            samples,
+        };
+        db.insert_level_buckets(&[bucket(0.2, 0.5, 0.3, 10)])
+            .await
+            .unwrap();
+        // The same bucket again, in the same batch as another
+        db.insert_level_buckets(&[
+            bucket(0.1, 0.4, 0.2, 20),
+            bucket(0.3, 0.9, 0.6, 10),
+        ])
+        .await
+        .unwrap();
+
+        let levels = db.load_level_history().await.unwrap();
+        assert_eq!(levels.len(), 1);
+        assert!((levels[0].min - 0.1).abs() < 1e-6);
+        assert!((levels[0].max - 0.9).abs() < 1e-6);
+        assert!((levels[0].mean - 0.325).abs() < 1e-6);
+        assert_eq!(levels[0].samples, 40);
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[tokio::test]
+    async fn stream_settings_round_trip() {
+        let db_path = std::env::temp_dir().join("test_stream_settings.db");
+        let _ = std::fs::remove_file(&db_path);
+        let db = Database::new(db_path.to_str().unwrap()).await.unwrap();
+
+        let key = "client:application.name=Firefox";
+        let settings = StreamSettings {
+            volumes: Some(vec![0.5, 0.25]),
+            mute: Some(true),
+            target: Some(String::from("alsa_output.usb")),
+        };
+        db.save_stream_settings(key, &StreamSettings::default())
+            .await
+            .unwrap();
+        db.save_stream_settings(key, &settings).await.unwrap();
+
+        let loaded = db.load_stream_settings().await.unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded.get(key), Some(&settings));
+
+        let _ = std::fs::remove_file(db_path);
+    }
+}
@@ -1,16 +1,24 @@
 //! Database persistence using MPSC channels for async communication.
 
-use std::sync::mpsc::{self, Receiver, Sender};
-use std::thread;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use futures::executor::block_on;
 
+use super::db::{
+    unix_ms, Database, EventRecord, HistoryFilter, ObjectIdentity,
+    StreamSettings,
+};
+use super::levels::{LevelAccumulator, LevelBucket, COARSE_BUCKET_MS};
+use super::queue::{self, QueueReceiver, QueueSender, QueueStats};
+use crate::config::QueueOverflow;
 use crate::wirehose::{
-    state::{Client as WireClient, Device, Link, Metadata, Node},
-    ObjectId,
+    state::{Client as WireClient, Device, Link, Metadata, Node, Port},
+    ObjectId, StateEventKind,
 };
-use super::db::Database;
 
 /// Messages sent to the database thread
 #[derive(Debug)]
@@ -25,8 +33,14 @@ pub enum DbMessage {
     UpsertDevice(Device),
     
     // Link operations
-    UpsertLink { object_id: ObjectId, link: Link },
-    
+    UpsertLink {
+        object_id: ObjectId,
+        link: Link,
+    },
+
+    // Port operations
+    UpsertPort(Port),
+
     // Metadata operations
     UpsertMetadata(Metadata),
     RemoveMetadataProperty { object_id: ObjectId, subject: u32, key: String },
@@ -34,15 +48,162 @@ pub enum DbMessage {
     
     // Object removal
     RemoveObject(ObjectId),
-    
+    /// Sent once the initial objects have been enumerated. Objects which
+    /// haven't been written since the session began are marked absent.
+    MarkAbsent,
+
+    // Event journal
+    AppendEvent {
+        recorded_at: SystemTime,
+        kind: StateEventKind,
+        object_id: ObjectId,
+        identity: ObjectIdentity,
+        payload_json: String,
+    },
+
+    // Stream restore
+    SaveStreamSettings {
+        key: String,
+        settings: StreamSettings,
+    },
+
+    // Level history
+    RecordLevels {
+        recorded_at: SystemTime,
+        object_id: ObjectId,
+        natural_key: Option<String>,
+        peaks: Vec<f32>,
+    },
+
+    // History
+    /// Load recent history, which is passed to the history handler after
+    /// pending changes are written.
+    LoadHistory {
+        filter: Option<HistoryFilter>,
+        limit: u32,
+    },
+
     // Shutdown
     Shutdown,
 }
 
+/// How long to wait for the database thread to write pending changes when
+/// shutting down.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often to roll up level history
+const ROLL_UP_INTERVAL: Duration =
+    Duration::from_millis(COARSE_BUCKET_MS as u64);
+
+/// What an upsert message replaces
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) enum UpsertKey {
+    Object(ObjectId),
+    StreamSettings(String),
+}
+
+impl DbMessage {
+    /// What this message replaces if it is an upsert. Only the latest upsert
+    /// of each key in a batch needs to be written.
+    pub(super) fn upsert_key(&self) -> Option<UpsertKey> {
+        match self {
+            DbMessage::UpsertClient(client) => {
+                Some(UpsertKey::Object(client.object_id))
+            }
+            DbMessage::UpsertNode(node) => {
+                Some(UpsertKey::Object(node.object_id))
+            }
+            DbMessage::UpsertDevice(device) => {
+                Some(UpsertKey::Object(device.object_id))
+            }
+            DbMessage::UpsertLink { object_id, .. } => {
+                Some(UpsertKey::Object(*object_id))
+            }
+            DbMessage::UpsertPort(port) => {
+                Some(UpsertKey::Object(port.object_id))
+            }
+            DbMessage::UpsertMetadata(metadata) => {
+                Some(UpsertKey::Object(metadata.object_id))
+            }
+            DbMessage::SaveStreamSettings { key, .. } => {
+                Some(UpsertKey::StreamSettings(key.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether this message can be discarded when the queue is full. Object
+    /// updates are sent again when the object next changes, and level samples
+    /// and journal events only leave a gap in the history. Other messages are
+    /// never discarded.
+    pub(super) fn is_droppable(&self) -> bool {
+        matches!(
+            self,
+            DbMessage::UpsertClient(_)
+                | DbMessage::UpsertNode(_)
+                | DbMessage::UpsertDevice(_)
+                | DbMessage::UpsertLink { .. }
+                | DbMessage::UpsertPort(_)
+                | DbMessage::UpsertMetadata(_)
+                | DbMessage::RecordLevels { .. }
+                | DbMessage::AppendEvent { .. }
+        )
+    }
+
+    /// Whether this message removes or marks absent the rows that an upsert
+    /// with the given key writes, so that the upsert can't be moved past it.
+    pub(super) fn removes(&self, key: &UpsertKey) -> bool {
+        match (self, key) {
+            (DbMessage::RemoveObject(object_id), UpsertKey::Object(key)) => {
+                object_id == key
+            }
+            (DbMessage::MarkAbsent, UpsertKey::Object(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Messages waiting to be written in a single transaction.
+///
+/// Upserts are coalesced so that each object or stream restore key is written
+/// at most once per batch, at the position of its latest upsert. Everything
+/// else is kept in order.
+#[derive(Default)]
+struct Batch {
+    messages: Vec<Option<DbMessage>>,
+    upserts: HashMap<UpsertKey, usize>,
+}
+
+impl Batch {
+    fn push(&mut self, message: DbMessage) {
+        if let Some(key) = message.upsert_key() {
+            if let Some(index) = self.upserts.insert(key, self.messages.len()) {
+                self.messages[index] = None;
+            }
+        } else if let DbMessage::RemoveObject(object_id) = &message {
+            if let Some(index) =
+                self.upserts.remove(&UpsertKey::Object(*object_id))
+            {
+                self.messages[index] = None;
+            }
+        }
+        self.messages.push(Some(message));
+    }
+
+    fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    fn take(&mut self) -> impl Iterator<Item = DbMessage> {
+        self.upserts.clear();
+        std::mem::take(&mut self.messages).into_iter().flatten()
+    }
+}
+
 /// Database handle that sends messages to the database thread
 #[derive(Clone)]
 pub struct DatabaseHandle {
-    sender: Sender<DbMessage>,
+    sender: QueueSender,
 }
 
 impl DatabaseHandle {
@@ -51,68 +212,484 @@ impl DatabaseHandle {
         self.sender.send(message)?;
         Ok(())
     }
+
+    /// Returns the depth of the database thread's queue and how many messages
+    /// have been dropped or coalesced because it was full.
+    pub fn stats(&self) -> QueueStats {
+        self.sender.stats()
+    }
+}
+
+/// Counts of database operations performed by the database thread.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ShutdownReport {
+    /// Operations which were written
+    pub written: u64,
+    /// Operations which failed and were discarded
+    pub failed: u64,
+}
+
+/// A running database thread, which can be shut down to make sure that queued
+/// changes are written.
+pub struct DatabaseWorker {
+    sender: QueueSender,
+    /// Receives the report once the thread has written everything
+    done: Receiver<ShutdownReport>,
+    thread: JoinHandle<()>,
+}
+
+impl DatabaseWorker {
+    /// Write all queued changes, end the session, and wait for the thread to
+    /// exit, giving up after the timeout.
+    pub fn shutdown(self, timeout: Duration) -> Result<ShutdownReport> {
+        // If the thread has already exited, this fails and so does the
+        // receive below
+        let _ = self.sender.send(DbMessage::Shutdown);
+
+        match self.done.recv_timeout(timeout) {
+            Ok(report) => {
+                let _ = self.thread.join();
+                Ok(report)
+            }
+            Err(RecvTimeoutError::Timeout) => bail!(
+                "Timed out after {:.1}s writing pending changes",
+                timeout.as_secs_f32()
+            ),
+            Err(RecvTimeoutError::Disconnected) => {
+                bail!("Database thread exited unexpectedly")
+            }
+        }
+    }
 }
 
 /// Database thread that processes messages and performs actual database operations
 pub struct DatabaseThread {
-    receiver: Receiver<DbMessage>,
+    /// Handed to the worker when the thread runs. The thread doesn't keep a
+    /// sender, so the queue disconnects once every handle and the worker
+    /// have been dropped.
+    sender: Option<QueueSender>,
+    receiver: QueueReceiver,
+    /// The thread's database, which is only open while it's being read or
+    /// written so that other processes can take turns with the file
     db: Database,
+    /// How long to collect messages before writing them
+    flush_interval: Duration,
+    /// When fine level buckets were last rolled up into coarse ones
+    rolled_up_at: Option<Instant>,
+    /// Called with a description of each failed operation
+    error_handler: Option<Box<dyn Fn(String) + Send>>,
+    /// Called with the results of each history request
+    history_handler: Option<Box<dyn Fn(Vec<EventRecord>) + Send>>,
 }
 
 impl DatabaseThread {
-    /// Create a new database thread
-    pub fn new(database_url: &str) -> Result<(Self, DatabaseHandle)> {
-        let db = block_on(Database::new(database_url))?;
-        
-        let (sender, receiver) = mpsc::channel();
-        let handle = DatabaseHandle { sender };
-        
-        Ok((Self { receiver, db }, handle))
+    /// Create a new database thread, beginning a new session for the given
+    /// PipeWire remote. Rows are read and written in the given scope. Up to
+    /// `queue_capacity` messages can wait to be written before the overflow
+    /// policy applies.
+    pub fn new(
+        database_url: &str,
+        remote: Option<&str>,
+        scope: &str,
+        flush_interval: Duration,
+        queue_capacity: usize,
+        overflow: QueueOverflow,
+    ) -> Result<(Self, DatabaseHandle)> {
+        let mut db = block_on(Database::new(database_url))?.with_scope(scope);
+        block_on(db.begin_session(remote))?;
+
+        let (sender, receiver) = queue::bounded(queue_capacity, overflow);
+        let handle = DatabaseHandle {
+            sender: sender.clone(),
+        };
+
+        Ok((
+            Self {
+                sender: Some(sender),
+                receiver,
+                db,
+                flush_interval,
+                rolled_up_at: None,
+                error_handler: None,
+                history_handler: None,
+            },
+            handle,
+        ))
     }
-    
-    /// Run the database event loop
-    pub fn run(self) {
-        thread::spawn(move || {
+
+    /// Set a function to call when a database operation fails.
+    pub fn with_error_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(String) + Send + 'static,
+    {
+        self.error_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Set a function to call with the results of history requests.
+    pub fn with_history_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(Vec<EventRecord>) + Send + 'static,
+    {
+        self.history_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Load the saved stream settings for restoring streams
+    pub fn stream_settings(&self) -> Result<HashMap<String, StreamSettings>> {
+        block_on(self.db.load_stream_settings())
+    }
+
+    /// Run the database event loop on a new thread
+    pub fn run(mut self) -> DatabaseWorker {
+        let sender = self.sender.take().expect("Thread has already run");
+        let (done_sender, done) = mpsc::channel();
+
+        let thread = thread::spawn(move || {
             log::info!("Database thread started");
-            
-            let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
-            
+
+            let runtime = tokio::runtime::Runtime::new()
+                .expect("Failed to create Tokio runtime");
+
+            // Let other processes use the file until there's something to do
+            self.db.close();
+
+            let mut report = ShutdownReport::default();
+            let mut batch = Batch::default();
+            let mut levels = LevelAccumulator::default();
+            let mut deadline: Option<Instant> = None;
+
             loop {
-                match self.receiver.recv() {
-                    Ok(message) => {
-                        if let Err(e) = runtime.block_on(self.handle_message(message)) {
-                            log::error!("Database operation failed: {}", e);
+                let received = match deadline {
+                    Some(deadline) => self.receiver.recv_timeout(
+                        deadline.saturating_duration_since(Instant::now()),
+                    ),
+                    None => self.receiver.recv().map_err(Into::into),
+                };
+
+                let mut flush = false;
+                match received {
+                    Ok(DbMessage::Shutdown) => {
+                        log::info!("Received shutdown signal");
+                        // Anything sent before the signal is already queued
+                        while let Ok(message) = self.receiver.try_recv() {
+                            if !matches!(message, DbMessage::Shutdown) {
+                                batch.push(message);
+                            }
                         }
+                        let buckets = levels.take_finished(i64::MAX);
+                        runtime.block_on(self.flush(
+                            &mut batch,
+                            buckets,
+                            &mut report,
+                        ));
+                        break;
+                    }
+                    Ok(DbMessage::RecordLevels {
+                        recorded_at,
+                        object_id,
+                        natural_key,
+                        peaks,
+                    }) => match unix_ms(recorded_at) {
+                        Ok(recorded_at) => levels.record(
+                            object_id,
+                            natural_key,
+                            recorded_at,
+                            &peaks,
+                        ),
+                        Err(e) => self
+                            .report_error(format!("Invalid level time: {e}")),
+                    },
+                    Ok(DbMessage::LoadHistory { filter, limit }) => {
+                        // Include changes which haven't been written yet
+                        runtime.block_on(self.flush(
+                            &mut batch,
+                            Vec::new(),
+                            &mut report,
+                        ));
+                        runtime.block_on(
+                            self.load_history(filter.as_ref(), limit),
+                        );
                     }
-                    Err(mpsc::RecvError) => {
+                    Ok(message) => batch.push(message),
+                    Err(RecvTimeoutError::Timeout) => flush = true,
+                    Err(RecvTimeoutError::Disconnected) => {
                         log::info!("Database channel closed, shutting down");
+                        let buckets = levels.take_finished(i64::MAX);
+                        runtime.block_on(self.flush(
+                            &mut batch,
+                            buckets,
+                            &mut report,
+                        ));
                         break;
                     }
                 }
+
+                // Flush even if messages keep arriving
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    flush = true;
+                }
+
+                if flush {
+                    let now = unix_ms(SystemTime::now()).unwrap_or_default();
+                    let buckets = levels.take_finished(now);
+                    runtime.block_on(self.flush(
+                        &mut batch,
+                        buckets,
+                        &mut report,
+                    ));
+                    deadline = None;
+                }
+
+                if deadline.is_none()
+                    && !(batch.is_empty() && levels.is_empty())
+                {
+                    deadline = Some(Instant::now() + self.flush_interval);
+                }
+            }
+
+            // Anything left couldn't be written
+            report.failed += batch.take().count() as u64;
+
+            if let Err(e) = runtime.block_on(self.end_session()) {
+                self.report_error(format!("Failed to end session: {e}"));
+                report.failed += 1;
             }
             
             log::info!("Database thread stopped");
+            let _ = done_sender.send(report);
         });
+
+        DatabaseWorker {
+            sender,
+            done,
+            thread,
+        }
     }
-    
-    async fn handle_message(&self, message: DbMessage) -> Result<()> {
+
+    async fn load_history(
+        &mut self,
+        filter: Option<&HistoryFilter>,
+        limit: u32,
+    ) {
+        if self.history_handler.is_none() {
+            return;
+        }
+        let history = match self.db.open().await {
+            Ok(()) => self.db.load_history(filter, limit).await,
+            Err(e) => Err(e),
+        };
+        self.db.close();
+        match history {
+            Ok(history) => {
+                if let Some(history_handler) = &self.history_handler {
+                    history_handler(history);
+                }
+            }
+            Err(e) => self.report_error(format!("Failed to load history: {e}")),
+        }
+    }
+
+    async fn end_session(&mut self) -> Result<()> {
+        self.db.open().await?;
+        let result = self.db.end_session().await;
+        self.db.close();
+        result
+    }
+
+    fn report_error(&self, message: String) {
+        log::error!("Database: {}", message);
+        if let Some(error_handler) = &self.error_handler {
+            error_handler(message);
+        }
+    }
+
+    /// Write all pending messages and finished level buckets in a single
+    /// transaction, adding the results to the report. If the database can't be
+    /// opened, the messages are kept to be written by the next flush.
+    async fn flush(
+        &mut self,
+        batch: &mut Batch,
+        buckets: Vec<LevelBucket>,
+        report: &mut ShutdownReport,
+    ) {
+        if batch.is_empty() && buckets.is_empty() {
+            return;
+        }
+
+        if let Err(e) = self.db.open().await {
+            self.report_error(format!("Failed to open database: {e}"));
+            if !buckets.is_empty() {
+                report.failed += 1;
+            }
+            return;
+        }
+        self.write(batch, buckets, report).await;
+        self.db.close();
+    }
+
+    async fn write(
+        &mut self,
+        batch: &mut Batch,
+        buckets: Vec<LevelBucket>,
+        report: &mut ShutdownReport,
+    ) {
+        let db = &self.db;
+        if let Err(e) = db.begin().await {
+            self.report_error(format!("Failed to begin transaction: {e}"));
+            report.failed += batch.take().count() as u64;
+            return;
+        }
+
+        let mut flushed = ShutdownReport::default();
+
+        for message in batch.take() {
+            match self.handle_message(db, message).await {
+                Ok(()) => flushed.written += 1,
+                Err(e) => {
+                    self.report_error(format!("Operation failed: {e}"));
+                    flushed.failed += 1;
+                }
+            }
+        }
+
+        if !buckets.is_empty() {
+            match db.insert_level_buckets(&buckets).await {
+                Ok(()) => flushed.written += 1,
+                Err(e) => {
+                    self.report_error(format!(
+                        "Failed to write level history: {e}"
+                    ));
+                    flushed.failed += 1;
+                }
+            }
+            // Buckets are rolled up a coarse bucket at a time
+            let roll_up = self.rolled_up_at.map_or(true, |rolled_up_at| {
+                rolled_up_at.elapsed() >= ROLL_UP_INTERVAL
+            });
+            if roll_up {
+                match db.roll_up_levels(SystemTime::now()).await {
+                    Ok(()) => self.rolled_up_at = Some(Instant::now()),
+                    Err(e) => self.report_error(format!(
+                        "Failed to roll up level history: {e}"
+                    )),
+                }
+            }
+        }
+
+        if let Err(e) = db.commit().await {
+            self.report_error(format!("Failed to commit transaction: {e}"));
+            let _ = db.rollback().await;
+            flushed.failed += flushed.written;
+            flushed.written = 0;
+        }
+
+        report.written += flushed.written;
+        report.failed += flushed.failed;
+    }
+
+    async fn handle_message(
+        &self,
+        db: &Database,
+        message: DbMessage,
+    ) -> Result<()> {
         match message {
-            DbMessage::UpsertClient(client) => self.db.upsert_client(&client).await,
-            DbMessage::UpsertNode(node) => self.db.upsert_node(&node).await,
-            DbMessage::UpsertDevice(device) => self.db.upsert_device(&device).await,
-            DbMessage::UpsertLink { object_id, link } => self.db.upsert_link(object_id, &link).await,
-            DbMessage::UpsertMetadata(metadata) => self.db.upsert_metadata(&metadata).await,
-            DbMessage::RemoveMetadataProperty { object_id, subject, key } => {
-                self.db.remove_metadata_property(object_id, subject, &key).await
+            DbMessage::UpsertClient(client) => db.upsert_client(&client).await,
+            DbMessage::UpsertNode(node) => db.upsert_node(&node).await,
+            DbMessage::UpsertDevice(device) => db.upsert_device(&device).await,
+            DbMessage::UpsertLink { object_id, link } => {
+                db.upsert_link(object_id, &link).await
+            }
+            DbMessage::UpsertPort(port) => db.upsert_port(&port).await,
+            DbMessage::UpsertMetadata(metadata) => {
+                db.upsert_metadata(&metadata).await
             }
+            DbMessage::RemoveMetadataProperty {
+                object_id,
+                subject,
+                key,
+            } => db.remove_metadata_property(object_id, subject, &key).await,
             DbMessage::ClearMetadataProperties { object_id, subject } => {
-                self.db.clear_metadata_properties(object_id, subject).await
+                db.clear_metadata_properties(object_id, subject).await
             }
-            DbMessage::RemoveObject(object_id) => self.db.remove_object(object_id).await,
-            DbMessage::Shutdown => {
-                log::info!("Received shutdown signal");
+            DbMessage::RemoveObject(object_id) => {
+                db.remove_object(object_id).await
+            }
+            DbMessage::MarkAbsent => {
+                let absent = db.mark_absent().await?;
+                log::info!("Marked {} stale database rows absent", absent);
                 Ok(())
             }
+            DbMessage::AppendEvent {
+                recorded_at,
+                kind,
+                object_id,
+                identity,
+                payload_json,
+            } => {
+                db.append_event(
+                    recorded_at,
+                    kind,
+                    object_id,
+                    &identity,
+                    &payload_json,
+                )
+                .await
+            }
+            DbMessage::SaveStreamSettings { key, settings } => {
+                db.save_stream_settings(&key, &settings).await
+            }
+            // Handled by the event loop
+            DbMessage::RecordLevels { .. }
+            | DbMessage::LoadHistory { .. }
+            | DbMessage::Shutdown => Ok(()),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: u32, rate: u32) -> DbMessage {
+        DbMessage::UpsertNode(Node {
+            object_id: ObjectId::from_raw_id(id),
+            rate: Some(rate),
+            ..Default::default()
+        })
+    }
+
+    fn rates(batch: &mut Batch) -> Vec<(u32, Option<u32>)> {
+        batch
+            .take()
+            .map(|message| match message {
+                DbMessage::UpsertNode(node) => {
+                    (node.object_id.into(), node.rate)
+                }
+                DbMessage::RemoveObject(object_id) => (object_id.into(), None),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn batch_coalesces_upserts() {
+        let mut batch = Batch::default();
+        batch.push(node(1, 44100));
+        batch.push(node(2, 44100));
+        batch.push(node(1, 48000));
+
+        assert_eq!(rates(&mut batch), vec![(2, Some(44100)), (1, Some(48000))]);
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn batch_remove_discards_pending_upsert() {
+        let mut batch = Batch::default();
+        batch.push(node(1, 44100));
+        batch.push(DbMessage::RemoveObject(ObjectId::from_raw_id(1)));
+        batch.push(node(1, 48000));
+
+        assert_eq!(rates(&mut batch), vec![(1, None), (1, Some(48000))]);
+    }
+}
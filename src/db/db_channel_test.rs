@@ -2,12 +2,16 @@
 
 #[cfg(test)]
 mod tests {
+    use crate::config::QueueOverflow;
+    use crate::db::db_channel::{
+        DatabaseThread, DbMessage, ShutdownReport, SHUTDOWN_TIMEOUT,
+    };
+    use crate::db::Database;
+    use crate::mock::WirehoseHandle;
     use crate::wirehose::state::{Client, State};
     use crate::wirehose::{ObjectId, PropertyStore, StateEvent};
-    use crate::db::db_channel::{DatabaseThread, DbMessage};
-    use crate::mock::WirehoseHandle;
-    use std::time::Duration;
     use std::thread;
+    use std::time::Duration;
 
     fn create_test_property_store() -> PropertyStore {
         let mut props = PropertyStore::default();
@@ -23,11 +27,19 @@ mod tests {
         let db_url = db_path.to_str().unwrap();
 
         // Create database thread and handle
-        let (db_thread, db_handle) = DatabaseThread::new(db_url).expect("Failed to create database");
-        
+        let (db_thread, db_handle) = DatabaseThread::new(
+            db_url,
+            None,
+            "test/default",
+            Duration::ZERO,
+            100,
+            QueueOverflow::default(),
+        )
+        .expect("Failed to create database");
+
         // Start the database thread
         db_thread.run();
-        
+
         // Give the thread time to start
         thread::sleep(Duration::from_millis(100));
 
@@ -38,7 +50,7 @@ mod tests {
         // Create a test client
         let object_id = ObjectId::from_raw_id(42);
         let props = create_test_property_store();
-        
+
         // Send client update through state (which should use channels)
         state.update(
             &wirehose,
@@ -54,7 +66,10 @@ mod tests {
         // Verify the client was persisted by checking if we can send a shutdown message
         // (this tests that the channel is still working)
         let result = db_handle.send(DbMessage::Shutdown);
-        assert!(result.is_ok(), "Failed to send shutdown message to database thread");
+        assert!(
+            result.is_ok(),
+            "Failed to send shutdown message to database thread"
+        );
 
         // Clean up
         let _ = std::fs::remove_file(db_path);
@@ -68,17 +83,25 @@ mod tests {
         let db_url = db_path.to_str().unwrap();
 
         // Create database thread and handle
-        let (db_thread, db_handle) = DatabaseThread::new(db_url).expect("Failed to create database");
-        
+        let (db_thread, db_handle) = DatabaseThread::new(
+            db_url,
+            None,
+            "test/default",
+            Duration::ZERO,
+            100,
+            QueueOverflow::default(),
+        )
+        .expect("Failed to create database");
+
         // Start the database thread
         db_thread.run();
-        
+
         // Give the thread time to start
         thread::sleep(Duration::from_millis(100));
 
         // Test sending different types of messages
         let object_id = ObjectId::from_raw_id(123);
-        
+
         // Test client message
         let client = Client {
             object_id,
@@ -94,6 +117,8 @@ mod tests {
             volumes: None,
             mute: None,
             peaks: None,
+            peak_holds: None,
+            levels: None,
             rate: None,
             positions: None,
         };
@@ -122,4 +147,105 @@ mod tests {
         // Clean up
         let _ = std::fs::remove_file(db_path);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_shutdown_flushes_queued_writes() {
+        let temp_dir = std::env::temp_dir();
+        let db_path = temp_dir.join("test_shutdown_flush.db");
+        let _ = std::fs::remove_file(&db_path);
+        let db_url = db_path.to_str().unwrap();
+
+        // Long enough that nothing is written before shutdown
+        let (db_thread, db_handle) = DatabaseThread::new(
+            db_url,
+            None,
+            "test/default",
+            Duration::from_secs(60),
+            100,
+            QueueOverflow::default(),
+        )
+        .expect("Failed to create database");
+        let worker = db_thread.run();
+
+        for id in [42, 43] {
+            let client = Client {
+                object_id: ObjectId::from_raw_id(id),
+                props: create_test_property_store(),
+            };
+            db_handle.send(DbMessage::UpsertClient(client)).unwrap();
+        }
+
+        let report = worker
+            .shutdown(SHUTDOWN_TIMEOUT)
+            .expect("Failed to shut down database thread");
+        assert_eq!(
+            report,
+            ShutdownReport {
+                written: 2,
+                failed: 0
+            }
+        );
+
+        // The thread has exited
+        assert!(db_handle.send(DbMessage::Shutdown).is_err());
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let clients = runtime.block_on(async {
+            let db = Database::new(db_url)
+                .await
+                .unwrap()
+                .with_scope("test/default");
+            db.load_clients().await.unwrap()
+        });
+        assert_eq!(clients.len(), 2);
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[test]
+    fn test_disconnect_flushes_queued_writes() {
+        let temp_dir = std::env::temp_dir();
+        let db_path = temp_dir.join("test_disconnect_flush.db");
+        let _ = std::fs::remove_file(&db_path);
+        let db_url = db_path.to_str().unwrap();
+
+        let (db_thread, db_handle) = DatabaseThread::new(
+            db_url,
+            None,
+            "test/default",
+            Duration::from_secs(60),
+            100,
+            QueueOverflow::default(),
+        )
+        .expect("Failed to create database");
+        let worker = db_thread.run();
+
+        let client = Client {
+            object_id: ObjectId::from_raw_id(42),
+            props: create_test_property_store(),
+        };
+        db_handle.send(DbMessage::UpsertClient(client)).unwrap();
+
+        // Without any senders left, the thread writes what's queued and exits
+        drop(db_handle);
+        drop(worker);
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let written = runtime.block_on(async {
+            for _ in 0..50 {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                let db = Database::new(db_url)
+                    .await
+                    .unwrap()
+                    .with_scope("test/default");
+                if db.load_clients().await.unwrap().len() == 1 {
+                    return true;
+                }
+            }
+            false
+        });
+        assert!(written, "Queued writes weren't flushed");
+
+        let _ = std::fs::remove_file(db_path);
+    }
+}
@@ -0,0 +1,198 @@
+//! Downsampling of peak levels into fixed-width buckets for the level_history
+//! table.
+
+use std::collections::HashMap;
+
+use crate::wirehose::ObjectId;
+
+/// Width of the finest buckets, in milliseconds.
+pub const FINE_BUCKET_MS: i64 = 1000;
+
+/// Width of the buckets that fine buckets are rolled up into, in milliseconds.
+pub const COARSE_BUCKET_MS: i64 = 60 * 1000;
+
+/// How long fine buckets are kept before being rolled up, in milliseconds.
+pub const FINE_RETENTION_MS: i64 = 60 * 60 * 1000;
+
+/// Summary of one channel's peak levels within a bucket.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelBucket {
+    pub node_id: ObjectId,
+    pub natural_key: Option<String>,
+    /// Start of the bucket in Unix time in milliseconds
+    pub bucket_start: i64,
+    pub channel: u32,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub samples: u32,
+}
+
+#[derive(Debug)]
+struct ChannelLevels {
+    min: f32,
+    max: f32,
+    sum: f64,
+    samples: u32,
+}
+
+impl ChannelLevels {
+    fn new(peak: f32) -> Self {
+        Self {
+            min: peak,
+            max: peak,
+            sum: peak as f64,
+            samples: 1,
+        }
+    }
+
+    fn add(&mut self, peak: f32) {
+        self.min = self.min.min(peak);
+        self.max = self.max.max(peak);
+        self.sum += peak as f64;
+        self.samples += 1;
+    }
+}
+
+#[derive(Debug)]
+struct OpenBucket {
+    natural_key: Option<String>,
+    bucket_start: i64,
+    channels: Vec<ChannelLevels>,
+}
+
+/// Accumulates peak readings into per-node fine buckets.
+#[derive(Debug, Default)]
+pub struct LevelAccumulator {
+    open: HashMap<ObjectId, OpenBucket>,
+    finished: Vec<LevelBucket>,
+}
+
+impl LevelAccumulator {
+    /// Add a peak reading for a node at the given Unix time in milliseconds.
+    pub fn record(
+        &mut self,
+        node_id: ObjectId,
+        natural_key: Option<String>,
+        recorded_at: i64,
+        peaks: &[f32],
+    ) {
+        let bucket_start = recorded_at - recorded_at.rem_euclid(FINE_BUCKET_MS);
+
+        if let Some(open) = self.open.get(&node_id) {
+            if open.bucket_start != bucket_start
+                || open.channels.len() != peaks.len()
+            {
+                self.finish(node_id);
+            }
+        }
+
+        match self.open.get_mut(&node_id) {
+            Some(open) => {
+                for (levels, peak) in open.channels.iter_mut().zip(peaks) {
+                    levels.add(*peak);
+                }
+            }
+            None => {
+                self.open.insert(
+                    node_id,
+                    OpenBucket {
+                        natural_key,
+                        bucket_start,
+                        channels: peaks
+                            .iter()
+                            .map(|peak| ChannelLevels::new(*peak))
+                            .collect(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Whether there are no open or finished buckets.
+    pub fn is_empty(&self) -> bool {
+        self.open.is_empty() && self.finished.is_empty()
+    }
+
+    /// Take all buckets which ended at or before the given Unix time in
+    /// milliseconds. Passing `i64::MAX` takes everything.
+    pub fn take_finished(&mut self, now: i64) -> Vec<LevelBucket> {
+        let ended: Vec<ObjectId> = self
+            .open
+            .iter()
+            .filter(|(_, open)| {
+                open.bucket_start.saturating_add(FINE_BUCKET_MS) <= now
+            })
+            .map(|(node_id, _)| *node_id)
+            .collect();
+        for node_id in ended {
+            self.finish(node_id);
+        }
+
+        std::mem::take(&mut self.finished)
+    }
+
+    fn finish(&mut self, node_id: ObjectId) {
+        let Some(open) = self.open.remove(&node_id) else {
+            return;
+        };
+
+        self.finished
+            .extend(open.channels.into_iter().enumerate().map(
+                |(channel, levels)| LevelBucket {
+                    node_id,
+                    natural_key: open.natural_key.clone(),
+                    bucket_start: open.bucket_start,
+                    channel: channel as u32,
+                    min: levels.min,
+                    max: levels.max,
+                    mean: (levels.sum / levels.samples as f64) as f32,
+                    samples: levels.samples,
+                },
+            ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulate_bucket() {
+        let mut levels = LevelAccumulator::default();
+        let node_id = ObjectId::from_raw_id(57);
+        levels.record(node_id, None, 10_100, &[0.2, 0.5]);
+        levels.record(node_id, None, 10_900, &[0.4, 0.1]);
+
+        // Still open
+        assert!(levels.take_finished(10_999).is_empty());
+
+        let buckets = levels.take_finished(11_000);
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[0].bucket_start, 10_000);
+        assert_eq!(buckets[0].channel, 0);
+        assert_eq!(buckets[0].min, 0.2);
+        assert_eq!(buckets[0].max, 0.4);
+        assert!((buckets[0].mean - 0.3).abs() < 1e-6);
+        assert_eq!(buckets[0].samples, 2);
+        assert_eq!(buckets[1].channel, 1);
+        assert_eq!(buckets[1].min, 0.1);
+        assert_eq!(buckets[1].max, 0.5);
+    }
+
+    #[test]
+    fn new_bucket_finishes_previous() {
+        let mut levels = LevelAccumulator::default();
+        let node_id = ObjectId::from_raw_id(57);
+        levels.record(node_id, None, 10_100, &[0.2]);
+        levels.record(node_id, None, 11_100, &[0.4]);
+
+        let buckets = levels.take_finished(11_000);
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].bucket_start, 10_000);
+
+        let buckets = levels.take_finished(i64::MAX);
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].bucket_start, 11_000);
+    }
+}
@@ -0,0 +1,296 @@
+//! Versioned schema migrations.
+//!
+//! Migrations are embedded from the `migrations` directory at build time and
+//! applied in order. Applied versions are recorded in the `schema_migrations`
+//! table so that each migration runs exactly once.
+
+use anyhow::{bail, Result};
+use turso::{params, Connection};
+
+/// An embedded migration with its up and down SQL.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    up: &'static str,
+    down: &'static str,
+}
+
+macro_rules! migration {
+    ($version:literal, $name:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            up: include_str!(concat!("../../migrations/", $name, ".up.sql")),
+            down: include_str!(concat!(
+                "../../migrations/",
+                $name,
+                ".down.sql"
+            )),
+        }
+    };
+}
+
+/// All known migrations, in ascending version order.
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001_initial_schema"),
+    migration!(2, "0002_state_events"),
+    migration!(3, "0003_stable_identity"),
+    migration!(4, "0004_level_history"),
+    migration!(5, "0005_stream_restore"),
+    migration!(6, "0006_presence"),
+    migration!(7, "0007_scope"),
+    migration!(8, "0008_ports"),
+    migration!(9, "0009_event_identity"),
+    migration!(10, "0010_event_title"),
+    migration!(11, "0011_legacy_props"),
+];
+
+/// The schema version this build of wiremix writes.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+async fn ensure_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+        (),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Returns the highest applied migration version, or 0 for a fresh database.
+pub async fn current_version(conn: &Connection) -> Result<u32> {
+    ensure_table(conn).await?;
+
+    let mut rows = conn
+        .query("SELECT MAX(version) FROM schema_migrations", ())
+        .await?;
+    let version = match rows.next().await? {
+        Some(row) => row.get::<Option<i64>>(0)?.unwrap_or(0),
+        None => 0,
+    };
+
+    Ok(u32::try_from(version)?)
+}
+
+/// Migrate the schema up or down to the given version.
+///
+/// Fails without touching the schema if the database was written by a newer
+/// wiremix than this one.
+pub async fn migrate_to(conn: &Connection, target: u32) -> Result<()> {
+    let current = current_version(conn).await?;
+    let latest = latest_version();
+
+    if current > latest {
+        bail!(
+            "Database schema version {current} is newer than the latest \
+             version supported by this wiremix ({latest})"
+        );
+    }
+    if target > latest {
+        bail!("Unknown schema version {target} (latest is {latest})");
+    }
+
+    if target >= current {
+        for migration in MIGRATIONS
+            .iter()
+            .filter(|m| m.version > current && m.version <= target)
+        {
+            apply(conn, migration).await?;
+        }
+    } else {
+        for migration in MIGRATIONS
+            .iter()
+            .rev()
+            .filter(|m| m.version > target && m.version <= current)
+        {
+            revert(conn, migration).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply all pending migrations.
+pub async fn migrate(conn: &Connection) -> Result<()> {
+    migrate_to(conn, latest_version()).await
+}
+
+async fn apply(conn: &Connection, migration: &Migration) -> Result<()> {
+    log::info!("Applying migration {}", migration.name);
+
+    let tx = conn.unchecked_transaction().await?;
+    tx.execute_batch(migration.up).await?;
+    tx.execute(
+        "INSERT INTO schema_migrations (version, name) VALUES (?, ?)",
+        params![migration.version, migration.name],
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+async fn revert(conn: &Connection, migration: &Migration) -> Result<()> {
+    log::info!("Reverting migration {}", migration.name);
+
+    let tx = conn.unchecked_transaction().await?;
+    tx.execute_batch(migration.down).await?;
+    tx.execute(
+        "DELETE FROM schema_migrations WHERE version = ?",
+        params![migration.version],
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn connect(name: &str) -> (turso::Database, std::path::PathBuf) {
+        let db_path = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_file(&db_path);
+        let db = turso::Builder::new_local(db_path.to_str().unwrap())
+            .build()
+            .await
+            .unwrap();
+        (db, db_path)
+    }
+
+    async fn table_exists(conn: &Connection, table: &str) -> bool {
+        let mut rows = conn
+            .query(
+                "SELECT name FROM sqlite_schema WHERE type = 'table' AND name = ?",
+                params![table],
+            )
+            .await
+            .unwrap();
+        rows.next().await.unwrap().is_some()
+    }
+
+    #[tokio::test]
+    async fn migrate_applies_once() {
+        let (db, db_path) = connect("test_migrate_applies_once.db").await;
+        let conn = db.connect().unwrap();
+
+        assert_eq!(current_version(&conn).await.unwrap(), 0);
+        migrate(&conn).await.unwrap();
+        assert_eq!(current_version(&conn).await.unwrap(), latest_version());
+        assert!(table_exists(&conn, "nodes").await);
+
+        // Running again is a no-op.
+        migrate(&conn).await.unwrap();
+        let mut rows = conn
+            .query("SELECT COUNT(*) FROM schema_migrations", ())
+            .await
+            .unwrap();
+        let count: i64 = rows.next().await.unwrap().unwrap().get(0).unwrap();
+        assert_eq!(count, MIGRATIONS.len() as i64);
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[tokio::test]
+    async fn migrate_rollback() {
+        let (db, db_path) = connect("test_migrate_rollback.db").await;
+        let conn = db.connect().unwrap();
+
+        migrate_to(&conn, 3).await.unwrap();
+        conn.execute("INSERT INTO sessions (id, started_at) VALUES (1, 0)", ())
+            .await
+            .unwrap();
+        conn.execute(
+            "INSERT INTO nodes (object_id, props_json, session_id) VALUES (57, '{}', 1)",
+            (),
+        )
+        .await
+        .unwrap();
+
+        // Rows survive a rollback past the session_id foreign keys
+        migrate_to(&conn, 2).await.unwrap();
+        let mut rows =
+            conn.query("SELECT object_id FROM nodes", ()).await.unwrap();
+        let object_id: i64 =
+            rows.next().await.unwrap().unwrap().get(0).unwrap();
+        assert_eq!(object_id, 57);
+        drop(rows);
+        let mut rows = conn
+            .query("SELECT sql FROM sqlite_schema WHERE name = 'nodes'", ())
+            .await
+            .unwrap();
+        let sql: String = rows.next().await.unwrap().unwrap().get(0).unwrap();
+        assert!(!sql.contains("session_id"));
+        drop(rows);
+        assert!(!table_exists(&conn, "sessions").await);
+
+        migrate(&conn).await.unwrap();
+        migrate_to(&conn, 0).await.unwrap();
+        assert_eq!(current_version(&conn).await.unwrap(), 0);
+        assert!(!table_exists(&conn, "nodes").await);
+
+        migrate(&conn).await.unwrap();
+        assert!(table_exists(&conn, "nodes").await);
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[tokio::test]
+    async fn migrate_clears_legacy_props() {
+        let (db, db_path) = connect("test_migrate_legacy_props.db").await;
+        let conn = db.connect().unwrap();
+
+        migrate_to(&conn, 10).await.unwrap();
+        for (object_id, props_json) in [
+            (1, r#"PropertyStore { properties: {"node.name": ..} }"#),
+            (2, r#"{"node.name":"alsa_output.usb"}"#),
+        ] {
+            conn.execute(
+                "INSERT INTO nodes (scope, object_id, props_json) VALUES ('test', ?, ?)",
+                params![object_id, props_json],
+            )
+            .await
+            .unwrap();
+        }
+        migrate(&conn).await.unwrap();
+
+        let mut rows = conn
+            .query("SELECT props_json FROM nodes ORDER BY object_id", ())
+            .await
+            .unwrap();
+        let mut props = Vec::new();
+        while let Some(row) = rows.next().await.unwrap() {
+            props.push(row.get::<String>(0).unwrap());
+        }
+        assert_eq!(props, ["{}", r#"{"node.name":"alsa_output.usb"}"#]);
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[tokio::test]
+    async fn migrate_refuses_newer_schema() {
+        let (db, db_path) = connect("test_migrate_newer.db").await;
+        let conn = db.connect().unwrap();
+
+        migrate(&conn).await.unwrap();
+        conn.execute(
+            "INSERT INTO schema_migrations (version, name) VALUES (?, ?)",
+            params![latest_version() + 1, "from_the_future"],
+        )
+        .await
+        .unwrap();
+
+        assert!(migrate(&conn).await.is_err());
+
+        let _ = std::fs::remove_file(db_path);
+    }
+}
@@ -1,10 +1,15 @@
 //! Database persistence module for PipeWire state.
 
+pub mod cli;
+#[allow(clippy::module_inception)]
 pub mod db;
 pub mod db_channel;
+pub mod levels;
+pub mod migrations;
+pub mod queue;
 
 #[cfg(test)]
 pub mod db_channel_test;
 
 // Re-export the Database struct for convenience
-pub use db::Database;
\ No newline at end of file
+pub use db::Database;
@@ -0,0 +1,469 @@
+//! Bounded queue of messages for the database thread.
+//!
+//! This works like a [`std::sync::mpsc`] channel, except that it holds at
+//! most a fixed number of messages. What happens when an object update, level
+//! sample or journal event is sent to a full queue depends on the
+//! [`QueueOverflow`] policy. Other messages, such as removals, are never
+//! discarded. They have a separate budget of the same size, and sending one
+//! waits while that is full.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+
+use super::db_channel::DbMessage;
+use crate::config::QueueOverflow;
+
+/// Snapshot of a queue's counters.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct QueueStats {
+    /// Messages waiting to be received
+    pub depth: usize,
+    /// Messages discarded because the queue was full
+    pub dropped: u64,
+    /// Messages which replaced a queued message because the queue was full
+    pub coalesced: u64,
+}
+
+struct Messages {
+    queue: VecDeque<DbMessage>,
+    /// How many of the queued messages can't be discarded
+    undroppable: usize,
+    /// Whether the receiver still exists
+    receiving: bool,
+}
+
+struct Shared {
+    messages: Mutex<Messages>,
+    /// Signalled when a message is pushed or the last sender is dropped
+    pushed: Condvar,
+    /// Signalled when a message is popped or the receiver is dropped
+    popped: Condvar,
+    capacity: usize,
+    overflow: QueueOverflow,
+    senders: AtomicUsize,
+    dropped: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Messages> {
+        // A panic while holding the lock can't leave the queue inconsistent
+        self.messages.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Create a queue which holds up to `capacity` messages.
+pub fn bounded(
+    capacity: usize,
+    overflow: QueueOverflow,
+) -> (QueueSender, QueueReceiver) {
+    let shared = Arc::new(Shared {
+        messages: Mutex::new(Messages {
+            queue: VecDeque::new(),
+            undroppable: 0,
+            receiving: true,
+        }),
+        pushed: Condvar::new(),
+        popped: Condvar::new(),
+        capacity: capacity.max(1),
+        overflow,
+        senders: AtomicUsize::new(1),
+        dropped: AtomicU64::new(0),
+        coalesced: AtomicU64::new(0),
+    });
+
+    (
+        QueueSender {
+            shared: Arc::clone(&shared),
+        },
+        QueueReceiver { shared },
+    )
+}
+
+pub struct QueueSender {
+    shared: Arc<Shared>,
+}
+
+impl QueueSender {
+    /// Send a message, applying the overflow policy if the queue is full.
+    /// Fails if the receiver has been dropped.
+    pub fn send(&self, message: DbMessage) -> Result<()> {
+        let shared = &self.shared;
+        let mut messages = shared.lock();
+
+        let droppable = message.is_droppable();
+        if droppable {
+            while messages.receiving
+                && messages.queue.len() - messages.undroppable
+                    >= shared.capacity
+            {
+                match shared.overflow {
+                    QueueOverflow::Block => {
+                        messages = shared
+                            .popped
+                            .wait(messages)
+                            .unwrap_or_else(|e| e.into_inner());
+                    }
+                    QueueOverflow::Coalesce => {
+                        if let Some(queued) =
+                            coalesce_target(&mut messages, &message)
+                        {
+                            *queued = message;
+                            shared.coalesced.fetch_add(1, Ordering::Relaxed);
+                            return Ok(());
+                        }
+                        if !drop_oldest(shared, &mut messages) {
+                            break;
+                        }
+                    }
+                    QueueOverflow::DropOldest => {
+                        if !drop_oldest(shared, &mut messages) {
+                            break;
+                        }
+                    }
+                }
+            }
+        } else {
+            // Never wait to send a shutdown signal
+            let must_send = matches!(message, DbMessage::Shutdown);
+            while messages.receiving
+                && !must_send
+                && messages.undroppable >= shared.capacity
+            {
+                messages = shared
+                    .popped
+                    .wait(messages)
+                    .unwrap_or_else(|e| e.into_inner());
+            }
+        }
+
+        if !messages.receiving {
+            bail!("Database thread has stopped");
+        }
+
+        if !droppable {
+            messages.undroppable += 1;
+        }
+        messages.queue.push_back(message);
+        shared.pushed.notify_one();
+        Ok(())
+    }
+
+    /// Returns the queue's counters.
+    pub fn stats(&self) -> QueueStats {
+        let shared = &self.shared;
+        QueueStats {
+            depth: shared.lock().queue.len(),
+            dropped: shared.dropped.load(Ordering::Relaxed),
+            coalesced: shared.coalesced.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The latest queued upsert which the message can replace. Replacing it moves
+/// the message ahead of anything queued after it, so it can't be replaced if
+/// that includes a removal of the same object.
+fn coalesce_target<'a>(
+    messages: &'a mut Messages,
+    message: &DbMessage,
+) -> Option<&'a mut DbMessage> {
+    let key = message.upsert_key()?;
+    messages
+        .queue
+        .iter_mut()
+        .rev()
+        .take_while(|queued| !queued.removes(&key))
+        .find(|queued| queued.upsert_key().as_ref() == Some(&key))
+}
+
+/// Discard the oldest object update, level sample or journal event. Returns
+/// false if there was nothing to discard.
+fn drop_oldest(shared: &Shared, messages: &mut Messages) -> bool {
+    let Some(oldest) = messages.queue.iter().position(DbMessage::is_droppable)
+    else {
+        return false;
+    };
+
+    messages.queue.remove(oldest);
+    shared.dropped.fetch_add(1, Ordering::Relaxed);
+    true
+}
+
+impl Clone for QueueSender {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Drop for QueueSender {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // Take the lock so that a waiting receiver doesn't miss this
+            let _messages = self.shared.lock();
+            self.shared.pushed.notify_all();
+        }
+    }
+}
+
+pub struct QueueReceiver {
+    shared: Arc<Shared>,
+}
+
+impl QueueReceiver {
+    /// Take the next message, failing if the queue is empty.
+    pub fn try_recv(&self) -> Result<DbMessage, TryRecvError> {
+        let mut messages = self.shared.lock();
+        match self.pop(&mut messages) {
+            Some(message) => Ok(message),
+            None if self.is_disconnected() => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Wait for the next message.
+    pub fn recv(&self) -> Result<DbMessage, RecvError> {
+        let mut messages = self.shared.lock();
+        loop {
+            if let Some(message) = self.pop(&mut messages) {
+                return Ok(message);
+            }
+            if self.is_disconnected() {
+                return Err(RecvError);
+            }
+            messages = self
+                .shared
+                .pushed
+                .wait(messages)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Wait for the next message, giving up after the timeout.
+    pub fn recv_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<DbMessage, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut messages = self.shared.lock();
+        loop {
+            if let Some(message) = self.pop(&mut messages) {
+                return Ok(message);
+            }
+            if self.is_disconnected() {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            messages = self
+                .shared
+                .pushed
+                .wait_timeout(messages, remaining)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    fn pop(&self, messages: &mut Messages) -> Option<DbMessage> {
+        let message = messages.queue.pop_front()?;
+        if !message.is_droppable() {
+            messages.undroppable -= 1;
+        }
+        self.shared.popped.notify_one();
+        Some(message)
+    }
+
+    fn is_disconnected(&self) -> bool {
+        self.shared.senders.load(Ordering::Acquire) == 0
+    }
+}
+
+impl Drop for QueueReceiver {
+    fn drop(&mut self) {
+        let mut messages = self.shared.lock();
+        messages.receiving = false;
+        messages.queue.clear();
+        messages.undroppable = 0;
+        // Wake blocked senders so that they fail
+        self.shared.popped.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::wirehose::{state::Node, ObjectId};
+
+    fn node(id: u32, rate: u32) -> DbMessage {
+        DbMessage::UpsertNode(Node {
+            object_id: ObjectId::from_raw_id(id),
+            rate: Some(rate),
+            ..Default::default()
+        })
+    }
+
+    fn rates(receiver: &QueueReceiver) -> Vec<(u32, Option<u32>)> {
+        std::iter::from_fn(|| receiver.try_recv().ok())
+            .map(|message| match message {
+                DbMessage::UpsertNode(node) => {
+                    (node.object_id.into(), node.rate)
+                }
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drop_oldest_when_full() {
+        let (sender, receiver) = bounded(2, QueueOverflow::DropOldest);
+        for id in 1..=3 {
+            sender.send(node(id, 48000)).unwrap();
+        }
+
+        assert_eq!(
+            sender.stats(),
+            QueueStats {
+                depth: 2,
+                dropped: 1,
+                coalesced: 0
+            }
+        );
+        assert_eq!(rates(&receiver), vec![(2, Some(48000)), (3, Some(48000))]);
+    }
+
+    #[test]
+    fn coalesce_when_full() {
+        let (sender, receiver) = bounded(2, QueueOverflow::Coalesce);
+        sender.send(node(1, 44100)).unwrap();
+        sender.send(node(2, 44100)).unwrap();
+        // Replaces the queued upsert of the same node
+        sender.send(node(1, 48000)).unwrap();
+        // Nothing to replace, so the oldest is dropped
+        sender.send(node(3, 48000)).unwrap();
+
+        let stats = sender.stats();
+        assert_eq!((stats.coalesced, stats.dropped), (1, 1));
+        assert_eq!(rates(&receiver), vec![(2, Some(44100)), (3, Some(48000))]);
+    }
+
+    #[test]
+    fn block_when_full() {
+        let (sender, receiver) = bounded(1, QueueOverflow::Block);
+        sender.send(node(1, 48000)).unwrap();
+
+        let thread = std::thread::spawn(move || {
+            sender.send(node(2, 48000)).unwrap();
+            sender.stats()
+        });
+
+        assert_eq!(rates(&receiver), vec![(1, Some(48000))]);
+        let message = receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(message.upsert_key(), node(2, 0).upsert_key());
+        assert_eq!(thread.join().unwrap().dropped, 0);
+    }
+
+    #[test]
+    fn shutdown_is_never_dropped() {
+        let (sender, receiver) = bounded(1, QueueOverflow::DropOldest);
+        sender.send(DbMessage::Shutdown).unwrap();
+        sender.send(node(1, 48000)).unwrap();
+
+        assert!(matches!(receiver.try_recv(), Ok(DbMessage::Shutdown)));
+        assert!(matches!(receiver.try_recv(), Ok(DbMessage::UpsertNode(_))));
+    }
+
+    #[test]
+    fn coalesce_keeps_upserts_after_removals() {
+        let (sender, receiver) = bounded(2, QueueOverflow::Coalesce);
+        sender.send(node(1, 44100)).unwrap();
+        sender
+            .send(DbMessage::RemoveObject(ObjectId::from_raw_id(1)))
+            .unwrap();
+        sender.send(node(2, 44100)).unwrap();
+        // Replacing the first upsert would write it before the removal, so
+        // the oldest is dropped instead
+        sender.send(node(1, 48000)).unwrap();
+
+        let stats = sender.stats();
+        assert_eq!((stats.coalesced, stats.dropped), (0, 1));
+        assert!(matches!(
+            receiver.try_recv(),
+            Ok(DbMessage::RemoveObject(_))
+        ));
+        assert_eq!(rates(&receiver), vec![(2, Some(44100)), (1, Some(48000))]);
+    }
+
+    #[test]
+    fn control_messages_are_never_dropped() {
+        let (sender, receiver) = bounded(2, QueueOverflow::DropOldest);
+        let remove = DbMessage::RemoveObject(ObjectId::from_raw_id(1));
+        sender.send(remove).unwrap();
+        sender.send(DbMessage::MarkAbsent).unwrap();
+        // Control messages don't count against the capacity for updates
+        for id in 1..=3 {
+            sender.send(node(id, 48000)).unwrap();
+        }
+
+        assert_eq!(
+            sender.stats(),
+            QueueStats {
+                depth: 4,
+                dropped: 1,
+                coalesced: 0
+            }
+        );
+        assert!(matches!(
+            receiver.try_recv(),
+            Ok(DbMessage::RemoveObject(_))
+        ));
+        assert!(matches!(receiver.try_recv(), Ok(DbMessage::MarkAbsent)));
+        assert_eq!(rates(&receiver), vec![(2, Some(48000)), (3, Some(48000))]);
+    }
+
+    #[test]
+    fn control_messages_wait_when_full() {
+        let (sender, receiver) = bounded(1, QueueOverflow::DropOldest);
+        sender.send(DbMessage::MarkAbsent).unwrap();
+
+        let thread = std::thread::spawn(move || {
+            let load = DbMessage::LoadHistory {
+                filter: None,
+                limit: 10,
+            };
+            sender.send(load).unwrap();
+            sender.stats()
+        });
+
+        assert!(matches!(receiver.try_recv(), Ok(DbMessage::MarkAbsent)));
+        let message = receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(matches!(message, DbMessage::LoadHistory { .. }));
+        assert_eq!(thread.join().unwrap().dropped, 0);
+    }
+
+    #[test]
+    fn disconnect() {
+        let (sender, receiver) = bounded(1, QueueOverflow::Block);
+        sender.send(node(1, 48000)).unwrap();
+        drop(sender);
+
+        assert!(receiver.recv().is_ok());
+        assert!(matches!(
+            receiver.recv_timeout(Duration::from_secs(1)),
+            Err(RecvTimeoutError::Disconnected)
+        ));
+
+        let (sender, receiver) = bounded(1, QueueOverflow::Block);
+        drop(receiver);
+        assert!(sender.send(node(1, 48000)).is_err());
+    }
+}
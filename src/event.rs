@@ -1,14 +1,20 @@
 //! Input events for the application.
 //!
-//! These come from [`wirehose`](`crate::wirehose`) (PipeWire events) and from
-//! [`input`](`crate::input`) (terminal input events).
+//! These come from [`wirehose`](`crate::wirehose`) (PipeWire events), from
+//! [`input`](`crate::input`) (terminal input events), and from the
+//! [database thread](`crate::db::db_channel`) (errors and history).
 
+use crate::db::db::EventRecord;
 use crate::wirehose::Event as PipewireEvent;
 
 #[derive(Debug)]
 pub enum Event {
     Input(crossterm::event::Event),
     Pipewire(PipewireEvent),
+    /// A database operation failed
+    DatabaseError(String),
+    /// History requested from the database has been loaded
+    History(Vec<EventRecord>),
 }
 
 impl From<crossterm::event::Event> for Event {
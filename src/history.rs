@@ -0,0 +1,531 @@
+//! A Ratatui widget for the history of changes recorded in the database.
+//!
+//! The history is loaded by the
+//! [database thread](`crate::db::db_channel::DatabaseThread`) and delivered as
+//! an [`Event::History`](`crate::event::Event::History`).
+
+use ratatui::{
+    prelude::{Alignment, Buffer, Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{StatefulWidget, Widget},
+};
+
+use crossterm::event::{MouseButton, MouseEventKind};
+use smallvec::smallvec;
+
+use crate::app::{Action, MouseArea};
+use crate::config::{Config, VolumeScale};
+use crate::db::db::{EventRecord, HistoryFilter};
+use crate::view;
+use crate::wirehose::ObjectId;
+
+/// The number of entries to load at once
+pub const HISTORY_LIMIT: u32 = 500;
+
+/// A recorded change, formatted for display.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub id: i64,
+    /// Unix time in milliseconds
+    pub recorded_at: i64,
+    pub title: String,
+    pub description: String,
+    /// Whether the object is a device rather than a node
+    pub is_device: bool,
+    /// Identifies the object for filtering and finding it in the live tabs
+    pub filter: HistoryFilter,
+}
+
+impl HistoryEntry {
+    /// Describe an event, with volumes labeled on the given scale.
+    pub fn from(
+        event: &EventRecord,
+        view: &view::View,
+        scale: VolumeScale,
+    ) -> Self {
+        let payload: serde_json::Value =
+            serde_json::from_str(&event.payload_json).unwrap_or_default();
+
+        // Default node changes are about the node they name rather than the
+        // metadata object which holds them
+        if event.kind == "MetadataProperty" {
+            let which = match payload["key"].as_str() {
+                Some("default.audio.source") => "source",
+                _ => "sink",
+            };
+            let name = payload["value"]
+                .as_str()
+                .and_then(|value| {
+                    serde_json::from_str::<serde_json::Value>(value).ok()
+                })
+                .and_then(|value| value["name"].as_str().map(String::from));
+            let title = name
+                .as_ref()
+                .map(|name| node_title(view, name).unwrap_or(name.clone()))
+                .unwrap_or_default();
+            let description = match name {
+                Some(_) => format!("Made default {which}"),
+                None => format!("Default {which} unset"),
+            };
+            return Self {
+                id: event.id,
+                recorded_at: event.recorded_at,
+                title,
+                description,
+                is_device: false,
+                filter: HistoryFilter {
+                    object_id: event.object_id,
+                    natural_key: name,
+                },
+            };
+        }
+
+        let object_id = ObjectId::from_raw_id(event.object_id);
+        let is_device = event.object_type.as_deref() == Some("device");
+
+        let live_title = event.natural_key.as_ref().and_then(|name| {
+            if is_device {
+                device_title(view, name)
+            } else {
+                node_title(view, name)
+            }
+        });
+        let title = live_title
+            .or_else(|| event.title.clone())
+            .unwrap_or_else(|| format!("Object {}", event.object_id));
+
+        let description = match event.kind.as_str() {
+            "NodeVolumes" => {
+                let volumes: Vec<f32> =
+                    serde_json::from_value(payload["volumes"].clone())
+                        .unwrap_or_default();
+                let mean =
+                    volumes.iter().sum::<f32>() / volumes.len().max(1) as f32;
+                format!("Volume set to {}", scale.label(mean))
+            }
+            "NodeMute" => match payload["mute"].as_bool() {
+                Some(true) => String::from("Muted"),
+                _ => String::from("Unmuted"),
+            },
+            "DeviceProfile" => {
+                let index = payload["index"].as_i64().unwrap_or_default();
+                let profile = view.devices.get(&object_id).and_then(|device| {
+                    device.profiles.iter().find_map(|(target, title)| {
+                        matches!(target, view::Target::Profile(_, i)
+                            if i64::from(*i) == index)
+                        .then(|| title.clone())
+                    })
+                });
+                match profile {
+                    Some(profile) => format!("Profile set to {profile}"),
+                    None => format!("Profile set to #{index}"),
+                }
+            }
+            "DeviceProperties" => String::from("Added"),
+            "Removed" => String::from("Removed"),
+            kind => String::from(kind),
+        };
+
+        Self {
+            id: event.id,
+            recorded_at: event.recorded_at,
+            title,
+            description,
+            is_device,
+            filter: HistoryFilter {
+                object_id: event.object_id,
+                natural_key: event.natural_key.clone(),
+            },
+        }
+    }
+
+    /// Find the live object this entry is about.
+    pub fn live_object(&self, view: &view::View) -> Option<ObjectId> {
+        if let Some(name) = &self.filter.natural_key {
+            return if self.is_device {
+                view.devices
+                    .values()
+                    .find(|device| device.name.as_ref() == Some(name))
+                    .map(|device| device.object_id)
+            } else {
+                view.nodes
+                    .values()
+                    .find(|node| &node.name == name)
+                    .map(|node| node.object_id)
+            };
+        }
+
+        let object_id = ObjectId::from_raw_id(self.filter.object_id);
+        let exists = if self.is_device {
+            view.devices.contains_key(&object_id)
+        } else {
+            view.nodes.contains_key(&object_id)
+        };
+        exists.then_some(object_id)
+    }
+}
+
+fn node_title(view: &view::View, name: &str) -> Option<String> {
+    view.nodes
+        .values()
+        .find(|node| node.name == name)
+        .map(|node| node.title.clone())
+}
+
+fn device_title(view: &view::View, name: &str) -> Option<String> {
+    view.devices
+        .values()
+        .find(|device| device.name.as_deref() == Some(name))
+        .map(|device| device.title.clone())
+}
+
+/// Describe how long ago something happened, such as "5m ago".
+fn format_age(now: i64, then: i64) -> String {
+    let seconds = now.saturating_sub(then).max(0) / 1000;
+    match seconds {
+        0..=59 => format!("{seconds}s ago"),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+/// HistoryList stores the loaded history and the viewport and selection for
+/// displaying it.
+#[derive(Default)]
+pub struct HistoryList {
+    pub entries: Vec<HistoryEntry>,
+    /// Index of the first entry in viewport
+    top: usize,
+    /// Index of the currently selected entry
+    selected: usize,
+    /// Only show history for this object
+    pub filter: Option<HistoryFilter>,
+    /// A description of the filtered object
+    pub filter_title: Option<String>,
+    /// Whether history has been received since it was last requested
+    pub is_loaded: bool,
+}
+
+impl HistoryList {
+    /// Replace the entries, keeping the selected entry if it still exists.
+    pub fn set_records(
+        &mut self,
+        records: &[EventRecord],
+        view: &view::View,
+        scale: VolumeScale,
+    ) {
+        let selected_id = self.selected_entry().map(|entry| entry.id);
+        self.entries = records
+            .iter()
+            .map(|record| HistoryEntry::from(record, view, scale))
+            .collect();
+        self.selected = selected_id
+            .and_then(|id| self.entries.iter().position(|entry| entry.id == id))
+            .unwrap_or(0);
+        self.is_loaded = true;
+    }
+
+    /// Limit the history to one object, or show everything.
+    pub fn set_filter(
+        &mut self,
+        filter: Option<HistoryFilter>,
+        filter_title: Option<String>,
+    ) {
+        self.filter = filter;
+        self.filter_title = filter_title;
+        self.entries.clear();
+        self.top = 0;
+        self.selected = 0;
+        self.is_loaded = false;
+    }
+
+    pub fn selected_entry(&self) -> Option<&HistoryEntry> {
+        self.entries.get(self.selected)
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if index < self.entries.len() {
+            self.selected = index;
+        }
+    }
+
+    pub fn down(&mut self) {
+        self.select(self.selected.saturating_add(1));
+    }
+
+    pub fn up(&mut self) {
+        self.select(self.selected.saturating_sub(1));
+    }
+
+    /// Reconciles the viewport and selection.
+    pub fn update(&mut self, area: Rect) {
+        let (_, list_area, _) = self.areas(&area);
+        let entries_visible = list_area.height as usize;
+
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+        if self.top >= self.entries.len() {
+            self.top = self.entries.len().saturating_sub(entries_visible);
+        }
+
+        if self.selected >= self.top.saturating_add(entries_visible) {
+            self.top = self
+                .selected
+                .saturating_sub(entries_visible.saturating_sub(1));
+        } else if self.selected < self.top {
+            self.top = self.selected;
+        }
+    }
+
+    fn areas(&self, area: &Rect) -> (Rect, Rect, Rect) {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1), // header_area
+                Constraint::Min(0),    // list_area
+                Constraint::Length(1), // footer_area
+            ])
+            .split(*area);
+
+        (layout[0], layout[1], layout[2])
+    }
+}
+
+pub struct HistoryListWidget<'a> {
+    pub history: &'a mut HistoryList,
+    pub config: &'a Config,
+    /// Whether a database is configured
+    pub is_enabled: bool,
+    /// The current time, in Unix time in milliseconds
+    pub now: i64,
+}
+
+impl StatefulWidget for &mut HistoryListWidget<'_> {
+    type State = Vec<MouseArea>;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let mouse_areas = state;
+
+        let (header_area, list_area, footer_area) = self.history.areas(&area);
+
+        mouse_areas.extend([
+            (
+                header_area,
+                smallvec![MouseEventKind::Down(MouseButton::Left)],
+                smallvec![Action::MoveUp],
+            ),
+            (
+                footer_area,
+                smallvec![MouseEventKind::Down(MouseButton::Left)],
+                smallvec![Action::MoveDown],
+            ),
+            (
+                list_area,
+                smallvec![MouseEventKind::ScrollUp],
+                smallvec![Action::MoveUp],
+            ),
+            (
+                list_area,
+                smallvec![MouseEventKind::ScrollDown],
+                smallvec![Action::MoveDown],
+            ),
+        ]);
+
+        let message = if !self.is_enabled {
+            Some("History requires database_url to be set")
+        } else if !self.history.is_loaded {
+            Some("Loading...")
+        } else if self.history.entries.is_empty() {
+            Some("No changes recorded")
+        } else {
+            None
+        };
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1), // filter_area
+                Constraint::Min(0),    // entries_area
+            ])
+            .split(list_area);
+        let (filter_area, entries_area) = match &self.history.filter_title {
+            Some(_) => (layout[0], layout[1]),
+            None => (Rect::default(), list_area),
+        };
+
+        if let Some(filter_title) = &self.history.filter_title {
+            Line::from(vec![
+                Span::from("   History for "),
+                Span::styled(filter_title, self.config.theme.node_title),
+            ])
+            .render(filter_area, buf);
+        }
+
+        if let Some(message) = message {
+            Line::from(format!("   {message}")).render(entries_area, buf);
+            return;
+        }
+
+        let entries_visible = entries_area.height as usize;
+
+        if self.history.top > 0 {
+            Line::from(Span::styled(
+                &self.config.char_set.list_more,
+                self.config.theme.list_more,
+            ))
+            .alignment(Alignment::Center)
+            .render(header_area, buf);
+        }
+
+        if self.history.top.saturating_add(entries_visible)
+            < self.history.entries.len()
+        {
+            Line::from(Span::styled(
+                &self.config.char_set.list_more,
+                self.config.theme.list_more,
+            ))
+            .alignment(Alignment::Center)
+            .render(footer_area, buf);
+        }
+
+        let ages: Vec<_> = self
+            .history
+            .entries
+            .iter()
+            .skip(self.history.top)
+            .take(entries_visible)
+            .map(|entry| format_age(self.now, entry.recorded_at))
+            .collect();
+        let age_width = ages.iter().map(String::len).max().unwrap_or(0);
+
+        let rows = entries_area
+            .rows()
+            .zip(
+                self.history
+                    .entries
+                    .iter()
+                    .enumerate()
+                    .skip(self.history.top),
+            )
+            .zip(ages);
+        for ((row_area, (index, entry)), age) in rows {
+            let selector = if index == self.history.selected {
+                Span::styled(
+                    &self.config.char_set.selector_middle,
+                    self.config.theme.selector,
+                )
+            } else {
+                Span::from(" ")
+            };
+
+            Line::from(vec![
+                selector,
+                Span::from("  "),
+                Span::from(format!("{age:>age_width$}")),
+                Span::from("  "),
+                Span::styled(&entry.title, self.config.theme.node_title),
+                Span::from("  "),
+                Span::styled(&entry.description, self.config.theme.node_target),
+            ])
+            .render(row_area, buf);
+
+            mouse_areas.push((
+                row_area,
+                smallvec![MouseEventKind::Down(MouseButton::Left)],
+                smallvec![Action::SelectHistoryEntry(index)],
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock;
+
+    fn record(kind: &str, payload_json: &str) -> EventRecord {
+        EventRecord {
+            id: 1,
+            recorded_at: 0,
+            session_id: None,
+            kind: String::from(kind),
+            object_id: 57,
+            payload_json: String::from(payload_json),
+            object_type: Some(String::from("node")),
+            object_serial: Some(570),
+            natural_key: Some(String::from("alsa_output.usb")),
+            title: Some(String::from("USB Headset")),
+        }
+    }
+
+    #[test]
+    fn describe_entries() {
+        let wirehose = mock::WirehoseHandle::default();
+        let view = view::View::new(&wirehose);
+
+        let volumes = record("NodeVolumes", r#"{"volumes":[0.125,0.125]}"#);
+        let entry = HistoryEntry::from(&volumes, &view, VolumeScale::Cubic);
+        assert_eq!(entry.title, "USB Headset");
+        assert_eq!(entry.description, "Volume set to 50%");
+        let entry = HistoryEntry::from(&volumes, &view, VolumeScale::Db);
+        assert_eq!(entry.description, "Volume set to -18.1dB");
+
+        // Removed devices are described from the event alone
+        let mut removed = record("Removed", "{}");
+        removed.object_type = Some(String::from("device"));
+        removed.title = Some(String::from("USB Audio"));
+        let entry = HistoryEntry::from(&removed, &view, VolumeScale::Cubic);
+        assert_eq!(entry.title, "USB Audio");
+        assert_eq!(entry.description, "Removed");
+        assert!(entry.is_device);
+
+        let entry = HistoryEntry::from(
+            &record(
+                "MetadataProperty",
+                r#"{"key":"default.audio.sink","value":"{\"name\":\"alsa_output.hdmi\"}"}"#,
+            ),
+            &view,
+            VolumeScale::Cubic,
+        );
+        assert_eq!(entry.title, "alsa_output.hdmi");
+        assert_eq!(entry.description, "Made default sink");
+        assert_eq!(
+            entry.filter.natural_key.as_deref(),
+            Some("alsa_output.hdmi")
+        );
+    }
+
+    #[test]
+    fn format_ages() {
+        assert_eq!(format_age(59_999, 0), "59s ago");
+        assert_eq!(format_age(60_000, 0), "1m ago");
+        assert_eq!(format_age(7_200_000, 0), "2h ago");
+        assert_eq!(format_age(0, 1000), "0s ago");
+    }
+
+    #[test]
+    fn selection_follows_entry() {
+        let wirehose = mock::WirehoseHandle::default();
+        let view = view::View::new(&wirehose);
+
+        let mut history = HistoryList::default();
+        let mut records: Vec<_> = (1..=3)
+            .map(|id| {
+                let mut record = record("NodeMute", r#"{"mute":true}"#);
+                record.id = id;
+                record
+            })
+            .collect();
+        history.set_records(&records, &view, VolumeScale::Cubic);
+        history.down();
+        assert_eq!(history.selected_entry().map(|entry| entry.id), Some(2));
+
+        // A newer entry arrives at the top
+        let mut newer = records[0].clone();
+        newer.id = 4;
+        records.insert(0, newer);
+        history.set_records(&records, &view, VolumeScale::Cubic);
+        assert_eq!(history.selected_entry().map(|entry| entry.id), Some(2));
+    }
+}
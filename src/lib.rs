@@ -6,12 +6,16 @@ pub mod device_widget;
 pub mod dropdown_widget;
 pub mod event;
 pub mod help;
+pub mod history;
 pub mod input;
 pub mod meter;
 pub mod node_widget;
 pub mod object_list;
 pub mod opt;
+pub mod patchbay;
+pub mod stream_restore;
 pub mod view;
+pub mod virtual_device;
 pub mod wirehose;
 
 #[cfg(feature = "trace")]
@@ -19,10 +23,25 @@ pub mod trace;
 
 #[cfg(test)]
 mod mock {
+    use std::cell::RefCell;
+
     use crate::wirehose::{CommandSender, ObjectId};
 
     #[derive(Default)]
-    pub struct WirehoseHandle {}
+    pub struct WirehoseHandle {
+        /// The node and volumes of each node_volumes call
+        pub volumes_set: RefCell<Vec<(ObjectId, Vec<f32>)>>,
+        /// The node and mute of each node_mute call
+        pub mutes_set: RefCell<Vec<(ObjectId, bool)>>,
+        /// The subject, key and value of each metadata_set_property call
+        pub metadata_set: RefCell<Vec<(u32, String, Option<String>)>>,
+        /// The output and input ports of each link_create call
+        pub links_created: RefCell<Vec<(ObjectId, ObjectId)>>,
+        /// The name of each node_create_virtual call
+        pub virtual_nodes_created: RefCell<Vec<String>>,
+        /// The node of each node_destroy call
+        pub nodes_destroyed: RefCell<Vec<ObjectId>>,
+    }
 
     impl CommandSender for WirehoseHandle {
         fn node_capture_start(
@@ -30,11 +49,16 @@ mod mock {
             _object_id: ObjectId,
             _object_serial: u64,
             _capture_sink: bool,
+            _measure_levels: bool,
         ) {
         }
         fn node_capture_stop(&self, _object_id: ObjectId) {}
-        fn node_mute(&self, _object_id: ObjectId, _mute: bool) {}
-        fn node_volumes(&self, _object_id: ObjectId, _volumes: Vec<f32>) {}
+        fn node_mute(&self, object_id: ObjectId, mute: bool) {
+            self.mutes_set.borrow_mut().push((object_id, mute));
+        }
+        fn node_volumes(&self, object_id: ObjectId, volumes: Vec<f32>) {
+            self.volumes_set.borrow_mut().push((object_id, volumes));
+        }
         fn device_mute(
             &self,
             _object_id: ObjectId,
@@ -67,11 +91,37 @@ mod mock {
         fn metadata_set_property(
             &self,
             _object_id: ObjectId,
-            _subject: u32,
-            _key: String,
+            subject: u32,
+            key: String,
             _type_: Option<String>,
-            _value: Option<String>,
+            value: Option<String>,
         ) {
+            self.metadata_set.borrow_mut().push((subject, key, value));
+        }
+        fn link_create(
+            &self,
+            output_port: ObjectId,
+            input_port: ObjectId,
+            _passive: bool,
+            _linger: bool,
+        ) {
+            self.links_created
+                .borrow_mut()
+                .push((output_port, input_port));
+        }
+        fn link_destroy(&self, _object_id: ObjectId) {}
+        fn node_create_virtual(
+            &self,
+            name: String,
+            _description: String,
+            _media_class: String,
+            _positions: Vec<String>,
+            _linger: bool,
+        ) {
+            self.virtual_nodes_created.borrow_mut().push(name);
+        }
+        fn node_destroy(&self, object_id: ObjectId) {
+            self.nodes_destroyed.borrow_mut().push(object_id);
         }
     }
 }
@@ -1,7 +1,7 @@
 use std::io::stdout;
 use std::sync::{mpsc, Arc};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
@@ -10,9 +10,10 @@ use crossterm::{
 
 use wiremix::app;
 use wiremix::config::Config;
+use wiremix::db::db_channel::SHUTDOWN_TIMEOUT;
 use wiremix::event::Event;
 use wiremix::input;
-use wiremix::opt::Opt;
+use wiremix::opt::{Command, Opt};
 use wiremix::wirehose::Session;
 
 fn main() -> Result<()> {
@@ -28,6 +29,26 @@ fn main() -> Result<()> {
 
     let config = Config::try_new(config_path, &opt)?;
 
+    // Headless database subcommands
+    if let Some(Command::Db(db_opt)) = &opt.command {
+        let database_url = db_opt
+            .database
+            .as_deref()
+            .or(config.database_url.as_deref())
+            .context(
+                "No database configured. Set database_url or use --database",
+            )?;
+        let scope = config.database_scope.clone().unwrap_or_else(|| {
+            wiremix::db::db::default_scope(config.remote.as_deref())
+        });
+        return wiremix::db::cli::run(
+            database_url,
+            &scope,
+            &db_opt.command,
+            &mut stdout().lock(),
+        );
+    }
+
     // Handler for events from PipeWire - just wrap them and put them on the
     // event channel.
     let event_handler = {
@@ -61,12 +82,27 @@ fn main() -> Result<()> {
     }
     let mut terminal = ratatui::init();
     terminal.clear()?;
-    let app_result =
-        app::App::new(&client, event_rx, config).run(&mut terminal);
+    let mut app =
+        app::App::new(&client, Arc::clone(&event_tx), event_rx, config);
+    let database = app.take_database();
+    let app_result = app.run(&mut terminal);
     ratatui::restore();
     if support_mouse {
         stdout().execute(DisableMouseCapture)?;
     }
 
+    // Make sure queued changes are written before exiting
+    if let Some(database) = database {
+        match database.shutdown(SHUTDOWN_TIMEOUT) {
+            Ok(report) if report.failed > 0 => eprintln!(
+                "Warning: {} of {} database writes failed",
+                report.failed,
+                report.written + report.failed
+            ),
+            Ok(_) => {}
+            Err(e) => eprintln!("Warning: Failed to save database: {e}"),
+        }
+    }
+
     app_result
 }
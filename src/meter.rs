@@ -1,36 +1,98 @@
-//! Peak level meter rendering.
+//! Peak level meter ballistics and rendering.
 
 use ratatui::{
     prelude::{Alignment, Buffer, Constraint, Direction, Layout, Rect, Widget},
     text::{Line, Span},
 };
 
-use crate::config::Config;
+use crate::config::{self, Config, MeterSource};
 
-fn render_peak(peak: f32, area: Rect) -> (usize, usize, usize) {
-    fn normalize(value: f32) -> f32 {
+/// Returns a peak processor implementing the configured meter ballistics.
+pub fn ballistics(meter: &config::Meter) -> impl Fn(f32, f32, u32, u32) -> f32 {
+    let (attack, release) = meter.time_constants();
+
+    move |current_peak, new_peak, rate, samples| {
+        let time_constant = if new_peak > current_peak {
+            attack
+        } else {
+            release
+        };
+        if time_constant <= 0.0 {
+            return new_peak;
+        }
+        let coef =
+            1.0 - (-(samples as f32) / (time_constant * rate as f32)).exp();
+
+        current_peak + (new_peak - current_peak) * coef
+    }
+}
+
+/// Number of characters lit for a level in dB.
+fn lit_chars(db: f32, total_chars: usize, meter: &config::Meter) -> usize {
+    let normalize = |value: f32| {
         let amplitude = 10.0_f32.powf(value / 60.0);
-        let min = 10.0_f32.powf(-60.0 / 60.0);
-        let max = 10.0_f32.powf(6.0 / 60.0);
+        let min = 10.0_f32.powf(meter.floor_db / 60.0);
+        let max = 10.0_f32.powf(meter.ceiling_db / 60.0);
 
         (amplitude - min) / (max - min)
+    };
+
+    let meter_value = normalize(db.clamp(meter.floor_db, meter.ceiling_db));
+
+    ((meter_value * total_chars as f32).round() as usize).min(total_chars)
+}
+
+fn to_db(peak: f32) -> f32 {
+    20.0 * (peak + 1e-10).log10()
+}
+
+/// The unit of levels measured by a meter source.
+fn unit(source: MeterSource) -> &'static str {
+    match source {
+        MeterSource::Peak | MeterSource::Rms => "dB",
+        MeterSource::TruePeak => "dBTP",
+        MeterSource::Momentary | MeterSource::ShortTerm => "LUFS",
     }
+}
 
-    // Convert to dB between -20 and +3
-    let db = 20.0 * (peak + 1e-10).log10();
-    let vu_value = db.clamp(-60.0, 6.0);
+/// Width of the numeric readout, which fits levels down to -99.9.
+pub fn readout_width(source: MeterSource) -> u16 {
+    5 + unit(source).len() as u16
+}
 
-    let meter = normalize(vu_value);
+/// Formats a level in dB or LUFS for the numeric readout.
+pub fn readout(level: Option<f32>, source: MeterSource) -> String {
+    let unit = unit(source);
+    match level {
+        Some(level) if level > -99.95 => format!("{level:.1}{unit}"),
+        _ => format!("-inf{unit}"),
+    }
+}
 
+/// The level of the loudest channel in dB.
+pub fn max_db(levels: &[f32]) -> Option<f32> {
+    levels
+        .iter()
+        .copied()
+        .reduce(f32::max)
+        .filter(|&level| level > 0.0)
+        .map(to_db)
+}
+
+fn render_peak(
+    peak: f32,
+    area: Rect,
+    meter: &config::Meter,
+) -> (usize, usize, usize) {
     let total_chars = area.width as usize;
-    let lit = ((meter * total_chars as f32).round() as usize).min(total_chars);
+    let lit = lit_chars(to_db(peak), total_chars, meter);
 
-    // Values above 0.0 will be colored differently
-    let zero_char = (normalize(0.0) * total_chars as f32).round() as usize;
+    // Values above the overload threshold will be colored differently
+    let overload_char = lit_chars(meter.overload_db, total_chars, meter);
 
     // Assign colors
-    let active_size = lit.min(zero_char);
-    let overload_size = lit.saturating_sub(zero_char);
+    let active_size = lit.min(overload_char);
+    let overload_size = lit.saturating_sub(overload_char);
     let inactive_size = total_chars
         .saturating_sub(active_size)
         .saturating_sub(overload_size);
@@ -38,10 +100,63 @@ fn render_peak(peak: f32, area: Rect) -> (usize, usize, usize) {
     (active_size, overload_size, inactive_size)
 }
 
+/// Draws a peak-hold marker over a meter. Left meters grow to the left.
+fn render_hold(
+    hold: f32,
+    area: Rect,
+    buf: &mut Buffer,
+    grows_left: bool,
+    config: &Config,
+) {
+    let lit = lit_chars(to_db(hold), area.width as usize, &config.meter) as u16;
+    if lit == 0 {
+        return;
+    }
+
+    let x = if grows_left {
+        area.right().saturating_sub(lit)
+    } else {
+        area.x.saturating_add(lit - 1)
+    };
+    Span::styled(&config.char_set.meter_hold, config.theme.meter_hold)
+        .render(Rect::new(x, area.y, 1, 1), buf);
+}
+
+/// Draws a meter growing to the right.
+fn render_right(
+    peak: f32,
+    hold: Option<f32>,
+    area: Rect,
+    buf: &mut Buffer,
+    config: &Config,
+) {
+    let (active_peak, overload_peak, inactive_peak) =
+        render_peak(peak, area, &config.meter);
+    Line::from(vec![
+        Span::styled(
+            config.char_set.meter_right_active.repeat(active_peak),
+            config.theme.meter_active,
+        ),
+        Span::styled(
+            config.char_set.meter_right_overload.repeat(overload_peak),
+            config.theme.meter_overload,
+        ),
+        Span::styled(
+            config.char_set.meter_right_inactive.repeat(inactive_peak),
+            config.theme.meter_inactive,
+        ),
+    ])
+    .render(area, buf);
+    if let Some(hold) = hold {
+        render_hold(hold, area, buf, false, config);
+    }
+}
+
 pub fn render_stereo(
     meter_area: Rect,
     buf: &mut Buffer,
     peaks: Option<(f32, f32)>,
+    holds: Option<(f32, f32)>,
     config: &Config,
 ) {
     let layout = Layout::default()
@@ -61,7 +176,7 @@ pub fn render_stereo(
 
     let area = meter_left;
     let (active_peak, overload_peak, inactive_peak) =
-        render_peak(left_peak, area);
+        render_peak(left_peak, area, &config.meter);
     Line::from(vec![
         Span::styled(
             config.char_set.meter_left_inactive.repeat(inactive_peak),
@@ -78,10 +193,13 @@ pub fn render_stereo(
     ])
     .alignment(Alignment::Right)
     .render(area, buf);
+    if let Some((left_hold, _)) = holds {
+        render_hold(left_hold, area, buf, true, config);
+    }
 
     let area = meter_right;
     let (active_peak, overload_peak, inactive_peak) =
-        render_peak(right_peak, area);
+        render_peak(right_peak, area, &config.meter);
     Line::from(vec![
         Span::styled(
             config.char_set.meter_right_active.repeat(active_peak),
@@ -97,6 +215,9 @@ pub fn render_stereo(
         ),
     ])
     .render(area, buf);
+    if let Some((_, right_hold)) = holds {
+        render_hold(right_hold, area, buf, false, config);
+    }
 
     let live_line = if peaks.is_some() {
         Line::from(Span::styled(
@@ -124,6 +245,7 @@ pub fn render_mono(
     meter_area: Rect,
     buf: &mut Buffer,
     peak: Option<f32>,
+    hold: Option<f32>,
     config: &Config,
 ) {
     let mono_peak = peak.unwrap_or_default();
@@ -139,24 +261,7 @@ pub fn render_mono(
     let meter_live = layout[0];
     let meter_mono = layout[1];
 
-    let area = meter_mono;
-    let (active_peak, overload_peak, inactive_peak) =
-        render_peak(mono_peak, area);
-    Line::from(vec![
-        Span::styled(
-            config.char_set.meter_right_active.repeat(active_peak),
-            config.theme.meter_active,
-        ),
-        Span::styled(
-            config.char_set.meter_right_overload.repeat(overload_peak),
-            config.theme.meter_overload,
-        ),
-        Span::styled(
-            config.char_set.meter_right_inactive.repeat(inactive_peak),
-            config.theme.meter_inactive,
-        ),
-    ])
-    .render(area, buf);
+    render_right(mono_peak, hold, meter_mono, buf, config);
 
     let live_line = if peak.is_some() {
         Line::from(Span::styled(
@@ -171,3 +276,141 @@ pub fn render_mono(
     };
     live_line.render(meter_live, buf);
 }
+
+/// The level of one channel in a multichannel meter.
+pub struct ChannelLevel {
+    /// Channel position name, such as "FL"
+    pub label: String,
+    pub peak: Option<f32>,
+    pub hold: Option<f32>,
+}
+
+/// Narrowest bar worth drawing beside a channel label
+const MIN_LABELED_BAR: u16 = 3;
+
+/// Draws a meter for each channel side by side. Labels are dropped when the
+/// bars get too narrow, and a single mono meter is drawn if there isn't room
+/// for a column per channel.
+pub fn render_channels(
+    meter_area: Rect,
+    buf: &mut Buffer,
+    channels: &[ChannelLevel],
+    config: &Config,
+) {
+    let count = channels.len() as u16;
+    let spacing = count.saturating_sub(1);
+    let channel_width = meter_area.width.saturating_sub(spacing) / count.max(1);
+    if channel_width == 0 {
+        let mean = |levels: Vec<f32>| {
+            (levels.len() == channels.len())
+                .then(|| levels.iter().sum::<f32>() / levels.len() as f32)
+        };
+        let peaks = channels.iter().filter_map(|channel| channel.peak);
+        let holds = channels.iter().filter_map(|channel| channel.hold);
+        return render_mono(
+            meter_area,
+            buf,
+            mean(peaks.collect()),
+            mean(holds.collect()),
+            config,
+        );
+    }
+
+    let label_width = channels
+        .iter()
+        .map(|channel| channel.label.chars().count() as u16)
+        .max()
+        .unwrap_or_default();
+    let show_labels = channel_width >= label_width + 1 + MIN_LABELED_BAR;
+
+    let areas = Layout::horizontal(vec![Constraint::Fill(1); channels.len()])
+        .spacing(1)
+        .split(meter_area);
+    for (channel, &area) in channels.iter().zip(areas.iter()) {
+        let area = if show_labels {
+            let [label_area, bar_area] = Layout::horizontal([
+                Constraint::Length(label_width),
+                Constraint::Fill(1),
+            ])
+            .spacing(1)
+            .areas(area);
+            let style = if channel.peak.is_some() {
+                config.theme.meter_center_active
+            } else {
+                config.theme.meter_center_inactive
+            };
+            Span::styled(&channel.label, style).render(label_area, buf);
+            bar_area
+        } else {
+            area
+        };
+        render_right(
+            channel.peak.unwrap_or_default(),
+            channel.hold,
+            area,
+            buf,
+            config,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::Parser;
+
+    use crate::opt::Opt;
+
+    fn config() -> Config {
+        Config::try_new(None, &Opt::parse_from(["wiremix"])).unwrap()
+    }
+
+    fn channels() -> Vec<ChannelLevel> {
+        ["FL", "FR", "LFE"]
+            .into_iter()
+            .zip([0.2, 0.4, 0.6])
+            .map(|(label, peak)| ChannelLevel {
+                label: String::from(label),
+                peak: Some(peak),
+                hold: None,
+            })
+            .collect()
+    }
+
+    fn render(width: u16, channels: &[ChannelLevel]) -> Buffer {
+        let area = Rect::new(0, 0, width, 1);
+        let mut buf = Buffer::empty(area);
+        render_channels(area, &mut buf, channels, &config());
+        buf
+    }
+
+    fn text(buf: &Buffer) -> String {
+        buf.content().iter().map(|cell| cell.symbol()).collect()
+    }
+
+    #[test]
+    fn channel_labels_shown_when_bars_fit() {
+        // Three 7-cell channels fit a 3-cell label, a space and a bar
+        let text = text(&render(23, &channels()));
+        assert!(text.starts_with("FL "));
+        assert!(text.contains("FR "));
+        assert!(text.contains("LFE "));
+    }
+
+    #[test]
+    fn channel_labels_hidden_when_bars_are_narrow() {
+        let text = text(&render(20, &channels()));
+        assert!(!text.contains("FL"));
+        assert!(!text.contains("LFE"));
+    }
+
+    #[test]
+    fn channels_fall_back_to_mono_when_they_do_not_fit() {
+        let area = Rect::new(0, 0, 4, 1);
+        let mut expected = Buffer::empty(area);
+        render_mono(area, &mut expected, Some(0.4), None, &config());
+
+        assert_eq!(render(4, &channels()), expected);
+    }
+}
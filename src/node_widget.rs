@@ -8,10 +8,10 @@ use ratatui::{
 };
 
 use crossterm::event::{MouseButton, MouseEventKind};
-use smallvec::smallvec;
+use smallvec::{smallvec, SmallVec};
 
 use crate::app::{Action, MouseArea};
-use crate::config::{Config, Peaks};
+use crate::config::{Config, MeterSource, Peaks, VolumeScale};
 use crate::device_kind::DeviceKind;
 use crate::meter;
 use crate::object_list::ObjectList;
@@ -40,6 +40,10 @@ pub struct NodeWidget<'a> {
     device_kind: Option<DeviceKind>,
     node: &'a view::Node,
     selected: bool,
+    /// Show a volume bar for each channel
+    channels_unlocked: bool,
+    /// Channel to highlight when the node is selected
+    selected_channel: usize,
 }
 
 impl<'a> NodeWidget<'a> {
@@ -54,6 +58,51 @@ impl<'a> NodeWidget<'a> {
             device_kind,
             node,
             selected,
+            channels_unlocked: false,
+            selected_channel: 0,
+        }
+    }
+
+    /// Shows a volume bar for each channel if channels are unlocked.
+    pub fn channels(
+        mut self,
+        channels_unlocked: bool,
+        selected_channel: usize,
+    ) -> Self {
+        self.channels_unlocked = channels_unlocked;
+        self.selected_channel = selected_channel;
+        self
+    }
+
+    fn render_volume(
+        &self,
+        area: Rect,
+        buf: &mut Buffer,
+        mouse_areas: &mut Vec<MouseArea>,
+    ) {
+        if !self.channels_unlocked {
+            VolumeWidget::new(self.config, self.node, None).render(
+                area,
+                buf,
+                mouse_areas,
+            );
+            return;
+        }
+
+        let channel_count = self.node.volumes.len();
+        let layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Fill(1); channel_count])
+            .spacing(1)
+            .split(area);
+        for (channel, channel_area) in layout.iter().enumerate() {
+            let selected = self.selected && channel == self.selected_channel;
+            VolumeWidget::new(
+                self.config,
+                self.node,
+                Some((channel, selected)),
+            )
+            .render(*channel_area, buf, mouse_areas);
         }
     }
 
@@ -170,8 +219,12 @@ impl StatefulWidget for NodeWidget<'_> {
             mouse_areas,
         );
 
+        // MIDI and video nodes have no volume to show
+        if self.node.volumes.is_empty() {
+            return;
+        }
+
         // Render volume bar and (if enabled) peak meter
-        let volume = VolumeWidget::new(self.config, self.node);
         if self.config.peaks == Peaks::Off {
             let layout = Layout::default()
                 .direction(Direction::Horizontal)
@@ -184,7 +237,7 @@ impl StatefulWidget for NodeWidget<'_> {
             // index 0 is _padding
             let volume_area = layout[1];
 
-            volume.render(volume_area, buf, mouse_areas);
+            self.render_volume(volume_area, buf, mouse_areas);
         } else {
             let layout = Layout::default()
                 .direction(Direction::Horizontal)
@@ -201,7 +254,7 @@ impl StatefulWidget for NodeWidget<'_> {
             // index 2 is _padding
             let meter_area = layout[3];
 
-            volume.render(volume_area, buf, mouse_areas);
+            self.render_volume(volume_area, buf, mouse_areas);
             MeterWidget::new(self.config, self.node).render(meter_area, buf);
         }
     }
@@ -371,11 +424,31 @@ impl StatefulWidget for HeaderWidget<'_> {
 struct VolumeWidget<'a> {
     config: &'a Config,
     node: &'a view::Node,
+    /// Show only this channel and whether it is selected
+    channel: Option<(usize, bool)>,
 }
 
 impl<'a> VolumeWidget<'a> {
-    fn new(config: &'a Config, node: &'a view::Node) -> Self {
-        Self { config, node }
+    fn new(
+        config: &'a Config,
+        node: &'a view::Node,
+        channel: Option<(usize, bool)>,
+    ) -> Self {
+        Self {
+            config,
+            node,
+            channel,
+        }
+    }
+
+    /// Actions to run before changing the volume from this widget.
+    fn select_actions(&self) -> SmallVec<[Action; 4]> {
+        let mut actions: SmallVec<[Action; 4]> =
+            smallvec![Action::SelectObject(self.node.object_id)];
+        if let Some((channel, _)) = self.channel {
+            actions.push(Action::SelectChannel(channel));
+        }
+        actions
     }
 }
 
@@ -385,32 +458,68 @@ impl StatefulWidget for VolumeWidget<'_> {
     fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
         let mouse_areas = state;
 
-        let max_volume = self.config.max_volume_percent / 100.0;
+        let max_volume = self
+            .config
+            .volume_scale
+            .from_percent(self.config.max_volume_percent);
+
+        let (volume, area) = match self.channel {
+            Some((channel, selected)) => {
+                let name = self
+                    .node
+                    .channel_names()
+                    .into_iter()
+                    .nth(channel)
+                    .unwrap_or_default();
+                let layout = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([
+                        Constraint::Length(name.len() as u16), // name_label
+                        Constraint::Min(0),                    // volume_area
+                    ])
+                    .spacing(1)
+                    .split(area);
+                let style = if selected {
+                    self.config.theme.selector
+                } else {
+                    self.config.theme.volume
+                };
+                Span::styled(name, style).render(layout[0], buf);
+
+                let volume = self.node.volumes.get(channel).copied();
+                (volume, layout[1])
+            }
+            // Show the loudest channel, which is what changes when all
+            // channels are adjusted together
+            None => (self.node.volumes.iter().copied().reduce(f32::max), area),
+        };
 
+        // dB labels like "-12.5dB" are wider than percentages
+        let label_width = match self.config.volume_scale {
+            VolumeScale::Db => 7,
+            _ => 5,
+        };
         let layout = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([
-                Constraint::Length(5), // volume_label
-                Constraint::Min(0),    // volume_bar
+                Constraint::Length(label_width), // volume_label
+                Constraint::Min(0),              // volume_bar
             ])
             .spacing(1)
             .split(area);
         let volume_label = layout[0];
         let volume_bar = layout[1];
 
-        let volumes = &self.node.volumes;
-        if !volumes.is_empty() {
-            let mean = volumes.iter().sum::<f32>() / volumes.len() as f32;
-            let volume = mean.cbrt();
-            let percent = (volume * 100.0).round() as u32;
-
+        if let Some(volume) = volume {
+            let scale = self.config.volume_scale;
             Line::from(Span::styled(
-                format!("{percent}%"),
+                scale.label(volume),
                 self.config.theme.volume,
             ))
             .alignment(Alignment::Right)
             .render(volume_label, buf);
 
+            let volume = scale.from_linear(volume);
             let count = ((volume.clamp(0.0, max_volume) / max_volume)
                 * volume_bar.width as f32)
                 .round() as usize;
@@ -459,16 +568,15 @@ impl StatefulWidget for VolumeWidget<'_> {
                 volume
             };
 
+            let mut actions = self.select_actions();
+            actions.push(Action::SetAbsoluteVolume(sticky_volume));
             mouse_areas.push((
                 volume_area,
                 smallvec![
                     MouseEventKind::Down(MouseButton::Left),
                     MouseEventKind::Drag(MouseButton::Left),
                 ],
-                smallvec![
-                    Action::SelectObject(self.node.object_id),
-                    Action::SetAbsoluteVolume(sticky_volume),
-                ],
+                actions,
             ));
         }
     }
@@ -485,22 +593,139 @@ impl<'a> MeterWidget<'a> {
     }
 }
 
+impl MeterWidget<'_> {
+    /// Levels of each channel from the configured meter source. Loudness is
+    /// a single level for all channels.
+    fn levels(&self) -> Option<Vec<f32>> {
+        let levels = self.node.levels.as_ref();
+        let amplitude = |lufs: Option<f32>| {
+            vec![lufs.map_or(0.0, |lufs| 10.0_f32.powf(lufs / 20.0))]
+        };
+        match self.config.meter.source {
+            MeterSource::Peak => self.node.peaks.clone(),
+            MeterSource::Rms => levels.map(|levels| levels.rms.clone()),
+            MeterSource::TruePeak => {
+                levels.map(|levels| levels.true_peaks.clone())
+            }
+            MeterSource::Momentary => {
+                levels.map(|levels| amplitude(levels.momentary))
+            }
+            MeterSource::ShortTerm => {
+                levels.map(|levels| amplitude(levels.short_term))
+            }
+        }
+    }
+
+    /// Levels for a meter per channel, labeled and ordered by channel
+    /// position.
+    fn channel_levels(
+        &self,
+        levels: Option<&[f32]>,
+        holds: Option<&[f32]>,
+    ) -> Vec<meter::ChannelLevel> {
+        let mut labels = self.node.channel_names();
+        let count = levels.map_or(labels.len(), <[f32]>::len);
+        if labels.len() != count {
+            labels = (1..=count).map(|i| i.to_string()).collect();
+        }
+
+        let mut order: Vec<usize> = (0..count).collect();
+        if let Some(positions) = self
+            .node
+            .positions
+            .as_deref()
+            .filter(|positions| positions.len() == count)
+        {
+            order.sort_by_key(|&i| positions[i]);
+        }
+
+        order
+            .into_iter()
+            .map(|i| meter::ChannelLevel {
+                label: labels[i].clone(),
+                peak: levels.and_then(|levels| levels.get(i).copied()),
+                hold: holds.and_then(|holds| holds.get(i).copied()),
+            })
+            .collect()
+    }
+
+    fn render_readout(
+        &self,
+        levels: Option<&[f32]>,
+        area: Rect,
+        buf: &mut Buffer,
+    ) {
+        let source = self.config.meter.source;
+        let node_levels = self.node.levels.as_ref();
+        let level = match source {
+            MeterSource::Momentary => node_levels.and_then(|l| l.momentary),
+            MeterSource::ShortTerm => node_levels.and_then(|l| l.short_term),
+            _ => levels.and_then(meter::max_db),
+        };
+        Line::from(Span::styled(
+            meter::readout(level, source),
+            self.config.theme.meter_readout,
+        ))
+        .alignment(Alignment::Right)
+        .render(area, buf);
+    }
+}
+
 impl Widget for MeterWidget<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        match self.node.peaks.as_deref() {
+        let source = self.config.meter.source;
+        let levels = self.levels();
+
+        let area = if self.config.meter.readout {
+            let [meter_area, readout_area] = Layout::horizontal([
+                Constraint::Fill(1),
+                Constraint::Length(meter::readout_width(source)),
+            ])
+            .spacing(1)
+            .areas(area);
+            self.render_readout(levels.as_deref(), readout_area, buf);
+            meter_area
+        } else {
+            area
+        };
+
+        // Peak hold only applies to sample peaks
+        let holds = self
+            .node
+            .peak_holds
+            .as_deref()
+            .filter(|_| source == MeterSource::Peak);
+        let loudness =
+            matches!(source, MeterSource::Momentary | MeterSource::ShortTerm);
+        let channels = self.node.volumes.len();
+        match levels.as_deref() {
+            Some(levels)
+                if levels.len() > 2 && self.config.peaks == Peaks::Auto =>
+            {
+                meter::render_channels(
+                    area,
+                    buf,
+                    &self.channel_levels(Some(levels), holds),
+                    self.config,
+                )
+            }
             Some([left, right]) if self.config.peaks != Peaks::Mono => {
                 meter::render_stereo(
                     area,
                     buf,
                     Some((*left, *right)),
+                    match holds {
+                        Some([left, right]) => Some((*left, *right)),
+                        _ => None,
+                    },
                     self.config,
                 )
             }
-            Some(peaks @ [..]) => meter::render_mono(
+            Some(levels @ [..]) => meter::render_mono(
                 area,
                 buf,
-                (!peaks.is_empty())
-                    .then_some(peaks.iter().sum::<f32>() / peaks.len() as f32),
+                mean(levels),
+                holds.and_then(mean),
                 self.config,
             ),
             _ => match self
@@ -509,11 +734,110 @@ impl Widget for MeterWidget<'_> {
                 .as_ref()
                 .map(|positions| positions.len())
             {
-                Some(2) if self.config.peaks != Peaks::Mono => {
-                    meter::render_stereo(area, buf, None, self.config)
+                Some(2) if self.config.peaks != Peaks::Mono && !loudness => {
+                    meter::render_stereo(area, buf, None, None, self.config)
                 }
-                _ => meter::render_mono(area, buf, None, self.config),
+                Some(n)
+                    if n == channels
+                        && n > 2
+                        && self.config.peaks == Peaks::Auto
+                        && !loudness =>
+                {
+                    meter::render_channels(
+                        area,
+                        buf,
+                        &self.channel_levels(None, None),
+                        self.config,
+                    )
+                }
+                _ => meter::render_mono(area, buf, None, None, self.config),
             },
         }
     }
 }
+
+fn mean(values: &[f32]) -> Option<f32> {
+    (!values.is_empty())
+        .then_some(values.iter().sum::<f32>() / values.len() as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::Parser;
+
+    use crate::opt::Opt;
+    use crate::wirehose::ObjectId;
+
+    fn node(positions: Option<Vec<u32>>) -> view::Node {
+        view::Node {
+            object_id: ObjectId::from_raw_id(0),
+            object_serial: 0,
+            name: String::new(),
+            title: String::new(),
+            title_source_sink: None,
+            media_class: String::new(),
+            routes: None,
+            target_title: String::new(),
+            target: None,
+            volumes: vec![1.0; 3],
+            mute: false,
+            peaks: None,
+            peak_holds: None,
+            levels: None,
+            positions,
+            device_info: None,
+            is_default_sink: false,
+            is_default_source: false,
+            is_virtual: false,
+        }
+    }
+
+    fn channel_levels(
+        node: &view::Node,
+        levels: &[f32],
+    ) -> Vec<(String, Option<f32>)> {
+        let config =
+            Config::try_new(None, &Opt::parse_from(["wiremix"])).unwrap();
+        MeterWidget::new(&config, node)
+            .channel_levels(Some(levels), None)
+            .into_iter()
+            .map(|channel| (channel.label, channel.peak))
+            .collect()
+    }
+
+    #[test]
+    fn channel_levels_ordered_by_position() {
+        // FR, FL, FC
+        let node = node(Some(vec![4, 3, 5]));
+
+        assert_eq!(
+            channel_levels(&node, &[0.2, 0.1, 0.3]),
+            vec![
+                (String::from("FL"), Some(0.1)),
+                (String::from("FR"), Some(0.2)),
+                (String::from("FC"), Some(0.3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn channel_levels_numbered_without_matching_positions() {
+        let expected = vec![
+            (String::from("1"), Some(0.2)),
+            (String::from("2"), Some(0.1)),
+            (String::from("3"), Some(0.3)),
+        ];
+
+        let mismatched = node(Some(vec![4, 3]));
+        assert_eq!(channel_levels(&mismatched, &[0.2, 0.1, 0.3]), expected);
+
+        // Levels for a different number of channels than the volumes
+        let matched = node(Some(vec![4, 3, 5]));
+        assert_eq!(
+            channel_levels(&matched, &[0.2, 0.1, 0.3, 0.4])[3],
+            (String::from("4"), Some(0.4))
+        );
+    }
+}
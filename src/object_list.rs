@@ -10,7 +10,7 @@ use crossterm::event::{MouseButton, MouseEventKind};
 use smallvec::smallvec;
 
 use crate::app::{Action, MouseArea};
-use crate::config::Config;
+use crate::config::{Config, VolumeScale};
 use crate::device_kind::DeviceKind;
 use crate::device_widget::DeviceWidget;
 use crate::dropdown_widget::DropdownWidget;
@@ -36,6 +36,10 @@ pub struct ObjectList {
     pub dropdown_state: ListState,
     /// Targets
     pub targets: Vec<(view::Target, String)>,
+    /// Whether channel volumes are changed individually
+    pub channels_unlocked: bool,
+    /// Index of the channel changed when channels are unlocked
+    pub selected_channel: usize,
 }
 
 impl ObjectList {
@@ -49,6 +53,11 @@ impl ObjectList {
         }
     }
 
+    /// Whether the object is in this list.
+    pub fn contains(&self, view: &view::View, object_id: ObjectId) -> bool {
+        view.position(self.list_kind, object_id).is_some()
+    }
+
     pub fn down(&mut self, view: &view::View) {
         if self.dropdown_state.selected().is_some() {
             self.dropdown_state.select_next();
@@ -136,6 +145,7 @@ impl ObjectList {
         view: &view::View,
         volume: f32,
         max: Option<f32>,
+        scale: VolumeScale,
     ) -> bool {
         if matches!(self.list_kind, ListKind::Device) {
             return false;
@@ -144,7 +154,9 @@ impl ObjectList {
             return view.volume(
                 node_id,
                 VolumeAdjustment::Absolute(volume),
+                self.channel(),
                 max,
+                scale,
             );
         }
         false
@@ -155,6 +167,7 @@ impl ObjectList {
         view: &view::View,
         volume: f32,
         max: Option<f32>,
+        scale: VolumeScale,
     ) -> bool {
         if matches!(self.list_kind, ListKind::Device) {
             return false;
@@ -163,12 +176,74 @@ impl ObjectList {
             return view.volume(
                 node_id,
                 VolumeAdjustment::Relative(volume),
+                self.channel(),
                 max,
+                scale,
             );
         }
         false
     }
 
+    pub fn set_relative_balance(
+        &mut self,
+        view: &view::View,
+        delta: f32,
+        scale: VolumeScale,
+    ) {
+        if matches!(self.list_kind, ListKind::Device) {
+            return;
+        }
+        if let Some(node_id) = self.selected {
+            view.balance(node_id, delta, scale);
+        }
+    }
+
+    pub fn toggle_channel_lock(&mut self) {
+        self.channels_unlocked = !self.channels_unlocked;
+    }
+
+    /// Selects the channel at the provided index, if the selected node has
+    /// it.
+    pub fn select_channel(&mut self, view: &view::View, channel: usize) {
+        if channel < self.channel_count(view) {
+            self.selected_channel = channel;
+        }
+    }
+
+    pub fn next_channel(&mut self, view: &view::View) {
+        self.select_channel(view, self.selected_channel.saturating_add(1));
+    }
+
+    pub fn previous_channel(&mut self, view: &view::View) {
+        self.select_channel(view, self.selected_channel.saturating_sub(1));
+    }
+
+    fn channel_count(&self, view: &view::View) -> usize {
+        self.selected
+            .and_then(|node_id| view.nodes.get(&node_id))
+            .map(|node| node.volumes.len())
+            .unwrap_or_default()
+    }
+
+    /// The channel to change volumes of, or None for all channels.
+    fn channel(&self) -> Option<usize> {
+        self.channels_unlocked.then_some(self.selected_channel)
+    }
+
+    /// The kind of device listed, if this lists sinks or sources.
+    pub fn device_kind(&self) -> Option<DeviceKind> {
+        self.device_kind
+    }
+
+    pub fn remove_virtual_device(&mut self, view: &view::View) {
+        if matches!(self.list_kind, ListKind::Device) {
+            return;
+        }
+        if let (Some(node_id), Some(_)) = (self.selected, self.device_kind) {
+            view.remove_virtual_device(node_id);
+        }
+    }
+
     pub fn set_default(&mut self, view: &view::View) {
         if matches!(self.list_kind, ListKind::Device) {
             return;
@@ -186,6 +261,9 @@ impl ObjectList {
     }
 
     fn select(&mut self, object_id: Option<ObjectId>) {
+        if self.selected != object_id {
+            self.selected_channel = 0;
+        }
         self.selected = object_id;
         // Close the dropdown in case it is open for the previously-selected
         // object. This can happen when the object is removed from PipeWire
@@ -303,6 +381,10 @@ impl ObjectListWidget<'_, '_> {
                 object,
                 selected,
             )
+            .channels(
+                self.object_list.channels_unlocked,
+                self.object_list.selected_channel,
+            )
             .render(object_area, buf, mouse_areas);
         }
 
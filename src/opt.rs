@@ -1,11 +1,13 @@
 //! Parse command-line arguments.
 
 use std::path::PathBuf;
+use std::time::Duration;
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 use crate::app::TabKind;
 use crate::config;
+use crate::db::cli::parse_age;
 
 // VERGEN_GIT_DESCRIBE is emitted by build.rs.
 const VERSION: &str = match option_env!("VERGEN_GIT_DESCRIBE") {
@@ -106,6 +108,79 @@ pub struct Opt {
     #[cfg(debug_assertions)]
     #[clap(short, long, help = "Dump events without showing interface")]
     pub dump_events: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    #[clap(about = "Inspect and maintain the database")]
+    Db(DbOpt),
+}
+
+#[derive(Args)]
+pub struct DbOpt {
+    #[clap(
+        short,
+        long,
+        value_name = "FILE",
+        help = "Database file [default: database_url from the config file]"
+    )]
+    pub database: Option<String>,
+
+    #[command(subcommand)]
+    pub command: DbCommand,
+}
+
+#[derive(Subcommand)]
+pub enum DbCommand {
+    #[clap(about = "Show journaled events for an object ID or natural key")]
+    History {
+        #[clap(help = "Object ID, node.name, device.name, or client key")]
+        object: String,
+
+        #[clap(
+            short = 'n',
+            long,
+            default_value_t = 50,
+            help = "Maximum number of events to show"
+        )]
+        limit: u32,
+    },
+
+    #[clap(about = "Export the event journal or level history")]
+    Export {
+        #[clap(long, value_enum, default_value_t = ExportFormat::Jsonl)]
+        format: ExportFormat,
+
+        #[clap(long, value_enum, default_value_t = ExportTable::Events)]
+        table: ExportTable,
+    },
+
+    #[clap(about = "Remove history older than an age such as 30d or 12h")]
+    Prune {
+        #[clap(long, value_name = "AGE", value_parser = parse_age)]
+        older_than: Duration,
+    },
+
+    #[clap(about = "Copy the write-ahead log into the database file")]
+    Checkpoint,
+
+    #[clap(about = "Show the schema version and table definitions")]
+    Schema,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ExportFormat {
+    Jsonl,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ExportTable {
+    Events,
+    Levels,
 }
 
 impl Opt {
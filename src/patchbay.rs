@@ -0,0 +1,636 @@
+//! A Ratatui widget drawing the PipeWire graph as a text patchbay.
+//!
+//! Output ports are listed on the left and input ports on the right, each
+//! grouped under the node they belong to. Picking an output port and then an
+//! input port links them, or unlinks them if they are already linked.
+//!
+//! The columns scroll independently, so links aren't drawn between them.
+//! Instead, each port has a marker showing whether it's linked and lists the
+//! ports it's linked to after its title. The ports linked to the selected port
+//! are highlighted, and the detail line at the bottom names them in full.
+
+use ratatui::{
+    prelude::{Alignment, Buffer, Constraint, Direction, Layout, Rect},
+    style::Style,
+    text::{Line, Span},
+    widgets::{StatefulWidget, Widget},
+};
+
+use crossterm::event::{MouseButton, MouseEventKind};
+use smallvec::smallvec;
+
+use crate::app::{Action, MouseArea};
+use crate::config::Config;
+use crate::view;
+use crate::wirehose::{ObjectId, PortDirection};
+
+/// A row in one of the patchbay's columns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Row {
+    Node(ObjectId),
+    Port(ObjectId),
+}
+
+/// The rows listing the ports of the provided direction, grouped by node.
+fn rows(view: &view::View, direction: PortDirection) -> Vec<Row> {
+    let mut rows = Vec::new();
+    for node_id in &view.nodes_all {
+        let mut ports: Vec<_> = view
+            .ports
+            .values()
+            .filter(|port| {
+                port.node_id == *node_id && port.direction == direction
+            })
+            .map(|port| port.object_id)
+            .collect();
+        if ports.is_empty() {
+            continue;
+        }
+        ports.sort();
+
+        rows.push(Row::Node(*node_id));
+        rows.extend(ports.into_iter().map(Row::Port));
+    }
+    rows
+}
+
+fn ports(view: &view::View, direction: PortDirection) -> Vec<ObjectId> {
+    rows(view, direction)
+        .into_iter()
+        .filter_map(|row| match row {
+            Row::Port(port_id) => Some(port_id),
+            Row::Node(_) => None,
+        })
+        .collect()
+}
+
+/// The viewport and selection of one column.
+#[derive(Default, Debug)]
+struct Column {
+    /// Index of the first row in viewport
+    top: usize,
+    /// The currently selected port
+    selected: Option<ObjectId>,
+}
+
+/// Patchbay stores the viewport and selection of each column along with the
+/// output port picked for linking.
+pub struct Patchbay {
+    /// The column being navigated
+    pub focus: PortDirection,
+    outputs: Column,
+    inputs: Column,
+    /// The output port to link or unlink from the selected input port
+    pub picked: Option<ObjectId>,
+}
+
+impl Default for Patchbay {
+    fn default() -> Self {
+        Self {
+            focus: PortDirection::Output,
+            outputs: Default::default(),
+            inputs: Default::default(),
+            picked: None,
+        }
+    }
+}
+
+impl Patchbay {
+    fn column(&self, direction: PortDirection) -> &Column {
+        match direction {
+            PortDirection::Output => &self.outputs,
+            PortDirection::Input => &self.inputs,
+        }
+    }
+
+    fn column_mut(&mut self, direction: PortDirection) -> &mut Column {
+        match direction {
+            PortDirection::Output => &mut self.outputs,
+            PortDirection::Input => &mut self.inputs,
+        }
+    }
+
+    /// The selected port in the focused column.
+    pub fn selected_port(&self) -> Option<ObjectId> {
+        self.column(self.focus).selected
+    }
+
+    pub fn down(&mut self, view: &view::View) {
+        self.step(view, 1);
+    }
+
+    pub fn up(&mut self, view: &view::View) {
+        self.step(view, -1);
+    }
+
+    fn step(&mut self, view: &view::View, offset: isize) {
+        let ports = ports(view, self.focus);
+        let column = self.column_mut(self.focus);
+        let index = column
+            .selected
+            .and_then(|selected| ports.iter().position(|&p| p == selected))
+            .map(|index| index.saturating_add_signed(offset))
+            .unwrap_or(0)
+            .min(ports.len().saturating_sub(1));
+        if let Some(&port_id) = ports.get(index) {
+            column.selected = Some(port_id);
+        }
+    }
+
+    /// Move focus to the provided column.
+    pub fn focus(&mut self, direction: PortDirection) {
+        self.focus = direction;
+    }
+
+    /// Picks the selected output port, or links the picked port to the
+    /// selected input port.
+    pub fn activate(&mut self, view: &view::View) {
+        match self.focus {
+            PortDirection::Output => {
+                self.picked = self.outputs.selected;
+                if self.picked.is_some() {
+                    self.focus = PortDirection::Input;
+                }
+            }
+            PortDirection::Input => match (self.picked, self.inputs.selected) {
+                // The pick is kept so one output can be linked to several
+                // inputs in a row
+                (Some(output), Some(input)) => view.toggle_link(output, input),
+                _ => self.focus = PortDirection::Output,
+            },
+        }
+    }
+
+    /// Forget the picked port.
+    pub fn cancel(&mut self) {
+        self.picked = None;
+        self.focus = PortDirection::Output;
+    }
+
+    /// Select a port by clicking on it. Clicking an output port picks it, and
+    /// clicking an input port links it to the picked port.
+    pub fn click(&mut self, port_id: ObjectId, view: &view::View) {
+        let Some(port) = view.ports.get(&port_id) else {
+            return;
+        };
+        self.focus = port.direction;
+        self.column_mut(port.direction).selected = Some(port_id);
+        if port.direction == PortDirection::Output || self.picked.is_some() {
+            self.activate(view);
+        }
+    }
+
+    /// Reconciles the viewports and selections.
+    pub fn update(&mut self, area: Rect, view: &view::View) {
+        let (_, list_area, _, _) = self.areas(&area);
+        let rows_visible = list_area.height as usize;
+
+        if self
+            .picked
+            .is_some_and(|picked| !view.ports.contains_key(&picked))
+        {
+            self.picked = None;
+        }
+
+        for direction in [PortDirection::Output, PortDirection::Input] {
+            let rows = rows(view, direction);
+            let column = self.column_mut(direction);
+
+            let selected = column.selected.and_then(|selected| {
+                rows.iter().position(|&row| row == Row::Port(selected))
+            });
+            let selected = selected.or_else(|| {
+                rows.iter().position(|row| matches!(row, Row::Port(_)))
+            });
+            column.selected = selected.and_then(|index| match rows[index] {
+                Row::Port(port_id) => Some(port_id),
+                Row::Node(_) => None,
+            });
+            let selected = selected.unwrap_or(0);
+
+            if column.top >= rows.len() {
+                column.top = rows.len().saturating_sub(rows_visible);
+            }
+
+            if selected >= column.top.saturating_add(rows_visible) {
+                column.top =
+                    selected.saturating_sub(rows_visible.saturating_sub(1));
+            } else if selected < column.top {
+                // Keep the node the port belongs to in view
+                column.top = selected.saturating_sub(1);
+            }
+        }
+    }
+
+    fn areas(&self, area: &Rect) -> (Rect, Rect, Rect, Rect) {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1), // header_area
+                Constraint::Min(0),    // list_area
+                Constraint::Length(1), // footer_area
+                Constraint::Length(1), // detail_area
+            ])
+            .split(*area);
+
+        (layout[0], layout[1], layout[2], layout[3])
+    }
+}
+
+/// Points from a port of the provided direction to its peers.
+fn arrow(direction: PortDirection) -> &'static str {
+    match direction {
+        PortDirection::Output => " → ",
+        PortDirection::Input => " ← ",
+    }
+}
+
+pub struct PatchbayWidget<'a> {
+    pub patchbay: &'a Patchbay,
+    pub view: &'a view::View<'a>,
+    pub config: &'a Config,
+}
+
+impl PatchbayWidget<'_> {
+    /// Renders one column of the patchbay.
+    fn render_column(
+        &self,
+        direction: PortDirection,
+        areas: (Rect, Rect, Rect),
+        buf: &mut Buffer,
+        mouse_areas: &mut Vec<MouseArea>,
+    ) {
+        let (header_area, list_area, footer_area) = areas;
+        let column = self.patchbay.column(direction);
+        let rows = rows(self.view, direction);
+        let rows_visible = list_area.height as usize;
+        let is_focused = self.patchbay.focus == direction;
+
+        let focused_port = self.patchbay.selected_port();
+        let peers = focused_port
+            .map(|port_id| self.view.port_peers(port_id))
+            .unwrap_or_default();
+
+        if column.top > 0 {
+            Line::from(Span::styled(
+                &self.config.char_set.list_more,
+                self.config.theme.list_more,
+            ))
+            .alignment(Alignment::Center)
+            .render(header_area, buf);
+        }
+
+        if column.top.saturating_add(rows_visible) < rows.len() {
+            Line::from(Span::styled(
+                &self.config.char_set.list_more,
+                self.config.theme.list_more,
+            ))
+            .alignment(Alignment::Center)
+            .render(footer_area, buf);
+        }
+
+        for (row_area, row) in
+            list_area.rows().zip(rows.iter().skip(column.top))
+        {
+            match *row {
+                Row::Node(node_id) => {
+                    let Some(node) = self.view.nodes.get(&node_id) else {
+                        continue;
+                    };
+                    Line::from(vec![
+                        Span::from("   "),
+                        Span::styled(&node.title, self.config.theme.node_title),
+                    ])
+                    .render(row_area, buf);
+                }
+                Row::Port(port_id) => {
+                    let Some(port) = self.view.ports.get(&port_id) else {
+                        continue;
+                    };
+
+                    let selector =
+                        if is_focused && column.selected == Some(port_id) {
+                            Span::styled(
+                                &self.config.char_set.selector_middle,
+                                self.config.theme.selector,
+                            )
+                        } else {
+                            Span::from(" ")
+                        };
+
+                    let port_peers = self.peer_titles(port_id);
+                    let marker = if port_peers.is_empty() {
+                        Span::styled(
+                            &self.config.char_set.port_unlinked,
+                            self.config.theme.port_unlinked,
+                        )
+                    } else {
+                        Span::styled(
+                            &self.config.char_set.port_linked,
+                            self.config.theme.port_linked,
+                        )
+                    };
+
+                    let style = if self.patchbay.picked == Some(port_id) {
+                        self.config.theme.port_picked
+                    } else if peers.contains(&port_id) {
+                        self.config.theme.port_linked
+                    } else {
+                        Style::default()
+                    };
+
+                    let mut spans = vec![
+                        selector,
+                        Span::from("    "),
+                        marker,
+                        Span::from(" "),
+                        Span::styled(&port.title, style),
+                    ];
+                    if !port_peers.is_empty() {
+                        spans.push(Span::from(arrow(direction)));
+                        spans.push(Span::styled(
+                            port_peers.join(", "),
+                            self.config.theme.node_target,
+                        ));
+                    }
+                    Line::from(spans).render(row_area, buf);
+
+                    mouse_areas.push((
+                        row_area,
+                        smallvec![MouseEventKind::Down(MouseButton::Left)],
+                        smallvec![Action::SelectPort(port_id)],
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Describes what the focused port is linked to.
+    fn detail(&self) -> Line<'_> {
+        let Some(port) = self
+            .patchbay
+            .selected_port()
+            .and_then(|port_id| self.view.ports.get(&port_id))
+        else {
+            return Line::from("   No ports");
+        };
+
+        let peers = self.peer_titles(port.object_id);
+
+        let mut spans = vec![
+            Span::from("   "),
+            Span::styled(
+                self.port_title(port.object_id).unwrap_or_default(),
+                self.config.theme.node_title,
+            ),
+        ];
+        if peers.is_empty() {
+            spans.push(Span::from(": Not linked"));
+        } else {
+            spans.push(Span::from(arrow(port.direction)));
+            spans.push(Span::styled(
+                peers.join(", "),
+                self.config.theme.node_target,
+            ));
+        }
+        Line::from(spans)
+    }
+
+    /// The titles of the ports linked to the provided port.
+    fn peer_titles(&self, port_id: ObjectId) -> Vec<String> {
+        self.view
+            .port_peers(port_id)
+            .iter()
+            .filter_map(|peer_id| self.port_title(*peer_id))
+            .collect()
+    }
+
+    /// A port's title prefixed by its node's title.
+    fn port_title(&self, port_id: ObjectId) -> Option<String> {
+        let port = self.view.ports.get(&port_id)?;
+        let node = self.view.nodes.get(&port.node_id)?;
+        Some(format!("{}:{}", node.title, port.title))
+    }
+}
+
+impl StatefulWidget for &mut PatchbayWidget<'_> {
+    type State = Vec<MouseArea>;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let mouse_areas = state;
+
+        let (header_area, list_area, footer_area, detail_area) =
+            self.patchbay.areas(&area);
+
+        mouse_areas.extend([
+            (
+                header_area,
+                smallvec![MouseEventKind::Down(MouseButton::Left)],
+                smallvec![Action::MoveUp],
+            ),
+            (
+                footer_area,
+                smallvec![MouseEventKind::Down(MouseButton::Left)],
+                smallvec![Action::MoveDown],
+            ),
+            (
+                list_area,
+                smallvec![MouseEventKind::ScrollUp],
+                smallvec![Action::MoveUp],
+            ),
+            (
+                list_area,
+                smallvec![MouseEventKind::ScrollDown],
+                smallvec![Action::MoveDown],
+            ),
+        ]);
+
+        if self.view.ports.is_empty() {
+            Line::from("   No ports").render(list_area, buf);
+            return;
+        }
+
+        let split = |area: Rect| {
+            let layout = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Fill(1), Constraint::Fill(1)])
+                .split(area);
+            (layout[0], layout[1])
+        };
+        let (output_header, input_header) = split(header_area);
+        let (output_list, input_list) = split(list_area);
+        let (output_footer, input_footer) = split(footer_area);
+
+        self.render_column(
+            PortDirection::Output,
+            (output_header, output_list, output_footer),
+            buf,
+            mouse_areas,
+        );
+        self.render_column(
+            PortDirection::Input,
+            (input_header, input_list, input_footer),
+            buf,
+            mouse_areas,
+        );
+
+        self.detail().render(detail_area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::Parser;
+
+    use crate::mock;
+    use crate::opt::Opt;
+
+    fn node(object_id: u32, title: &str) -> view::Node {
+        view::Node {
+            object_id: ObjectId::from_raw_id(object_id),
+            object_serial: object_id.into(),
+            name: String::new(),
+            title: String::from(title),
+            title_source_sink: None,
+            media_class: String::new(),
+            routes: None,
+            target_title: String::new(),
+            target: None,
+            volumes: Vec::new(),
+            mute: false,
+            peaks: None,
+            peak_holds: None,
+            levels: None,
+            positions: None,
+            device_info: None,
+            is_default_sink: false,
+            is_default_source: false,
+            is_virtual: false,
+        }
+    }
+
+    fn port(
+        object_id: u32,
+        node_id: u32,
+        direction: PortDirection,
+    ) -> view::Port {
+        view::Port {
+            object_id: ObjectId::from_raw_id(object_id),
+            node_id: ObjectId::from_raw_id(node_id),
+            direction,
+            title: format!("port_{object_id}"),
+        }
+    }
+
+    fn view(wirehose: &mock::WirehoseHandle) -> view::View<'_> {
+        let mut view = view::View::new(wirehose);
+        view.nodes_all =
+            vec![ObjectId::from_raw_id(1), ObjectId::from_raw_id(2)];
+        for port in [
+            port(12, 2, PortDirection::Output),
+            port(11, 1, PortDirection::Output),
+            port(13, 2, PortDirection::Input),
+            port(10, 1, PortDirection::Output),
+        ] {
+            view.ports.insert(port.object_id, port);
+        }
+        view
+    }
+
+    #[test]
+    fn rows_grouped_by_node() {
+        let wirehose = mock::WirehoseHandle::default();
+        let view = view(&wirehose);
+        let id = ObjectId::from_raw_id;
+
+        assert_eq!(
+            rows(&view, PortDirection::Output),
+            vec![
+                Row::Node(id(1)),
+                Row::Port(id(10)),
+                Row::Port(id(11)),
+                Row::Node(id(2)),
+                Row::Port(id(12)),
+            ]
+        );
+        assert_eq!(
+            rows(&view, PortDirection::Input),
+            vec![Row::Node(id(2)), Row::Port(id(13))]
+        );
+    }
+
+    #[test]
+    fn pick_then_link() {
+        let wirehose = mock::WirehoseHandle::default();
+        let view = view(&wirehose);
+        let id = ObjectId::from_raw_id;
+
+        let mut patchbay = Patchbay::default();
+        patchbay.update(Rect::new(0, 0, 80, 10), &view);
+        assert_eq!(patchbay.selected_port(), Some(id(10)));
+
+        // Moving skips over node headers
+        patchbay.down(&view);
+        patchbay.down(&view);
+        assert_eq!(patchbay.selected_port(), Some(id(12)));
+        patchbay.down(&view);
+        assert_eq!(patchbay.selected_port(), Some(id(12)));
+
+        patchbay.activate(&view);
+        assert_eq!(patchbay.picked, Some(id(12)));
+        assert_eq!(patchbay.focus, PortDirection::Input);
+        assert_eq!(patchbay.selected_port(), Some(id(13)));
+
+        // The pick survives linking
+        patchbay.activate(&view);
+        assert_eq!(patchbay.picked, Some(id(12)));
+        assert_eq!(*wirehose.links_created.borrow(), [(id(12), id(13))]);
+
+        patchbay.cancel();
+        assert_eq!(patchbay.picked, None);
+        assert_eq!(patchbay.focus, PortDirection::Output);
+    }
+
+    #[test]
+    fn peers_listed_after_ports() {
+        let wirehose = mock::WirehoseHandle::default();
+        let mut view = view(&wirehose);
+        let id = ObjectId::from_raw_id;
+        for node in [node(1, "Speakers"), node(2, "Mic")] {
+            view.nodes.insert(node.object_id, node);
+        }
+        for (object_id, output_port_id) in [(20, 10), (21, 12)] {
+            view.port_links.push(view::PortLink {
+                object_id: id(object_id),
+                output_port_id: id(output_port_id),
+                input_port_id: id(13),
+            });
+        }
+        let config =
+            Config::try_new(None, &Opt::parse_from(["wiremix"])).unwrap();
+        let patchbay = Patchbay::default();
+        let mut widget = PatchbayWidget {
+            patchbay: &patchbay,
+            view: &view,
+            config: &config,
+        };
+
+        let area = Rect::new(0, 0, 100, 8);
+        let mut buf = Buffer::empty(area);
+        widget.render(area, &mut buf, &mut Vec::new());
+        // Each column takes half the width
+        let row = |x: std::ops::Range<u16>, y| {
+            x.map(|x| buf[(x, y)].symbol())
+                .collect::<String>()
+                .trim_end()
+                .to_string()
+        };
+
+        assert!(row(0..50, 2).ends_with("port_10 → Mic:port_13"));
+        assert!(row(0..50, 3).ends_with("port_11"));
+        assert!(row(50..100, 2)
+            .ends_with("port_13 ← Speakers:port_10, Mic:port_12"));
+    }
+}
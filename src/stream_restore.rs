@@ -0,0 +1,433 @@
+//! Restore stream volumes, mutes, and targets saved in the database.
+//!
+//! Settings are saved whenever a tracked stream changes and are applied to
+//! streams that appear after wiremix is ready. They are keyed by
+//! [`StreamRestore::match_key()`](`config::StreamRestore::match_key()`) rather
+//! than by object ID, so they carry over to new instances of the same
+//! application.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::config;
+use crate::db::db::StreamSettings;
+use crate::db::db_channel::{DatabaseHandle, DbMessage};
+use crate::wirehose::{
+    media_class, state::State, CommandSender, ObjectId, StateEvent,
+};
+
+/// A change to the state which may affect a stream's saved settings.
+#[derive(Debug, Clone, Copy)]
+pub enum Change {
+    Node(ObjectId),
+    /// A client or device, which may complete a new stream's match key
+    Owner,
+    Target(ObjectId),
+    Removed(ObjectId),
+}
+
+impl Change {
+    pub fn from_event(event: &StateEvent) -> Option<Self> {
+        match event {
+            StateEvent::NodeProperties { object_id, .. }
+            | StateEvent::NodeVolumes { object_id, .. }
+            | StateEvent::NodeMute { object_id, .. } => {
+                Some(Change::Node(*object_id))
+            }
+            StateEvent::ClientProperties { .. }
+            | StateEvent::DeviceProperties { .. } => Some(Change::Owner),
+            StateEvent::MetadataProperty {
+                subject,
+                key: Some(key),
+                ..
+            } if key == "target.node" || key == "target.object" => {
+                Some(Change::Target(ObjectId::from_raw_id(*subject)))
+            }
+            StateEvent::Removed { object_id } => {
+                Some(Change::Removed(*object_id))
+            }
+            _ => None,
+        }
+    }
+}
+
+pub struct StreamRestore {
+    config: config::StreamRestore,
+    database: DatabaseHandle,
+    /// Saved settings by match key
+    saved: HashMap<String, StreamSettings>,
+    /// Streams whose changes are saved. These are streams which existed when
+    /// wiremix became ready and streams which have since been restored.
+    tracked: HashSet<ObjectId>,
+    /// New streams waiting for their client or device before they can be
+    /// restored
+    pending: HashSet<ObjectId>,
+    is_ready: bool,
+}
+
+impl StreamRestore {
+    pub fn new(
+        config: config::StreamRestore,
+        database: DatabaseHandle,
+        saved: HashMap<String, StreamSettings>,
+    ) -> Self {
+        Self {
+            config,
+            database,
+            saved,
+            tracked: Default::default(),
+            pending: Default::default(),
+            is_ready: false,
+        }
+    }
+
+    /// Start restoring new streams. Streams which already exist are tracked
+    /// but not restored.
+    pub fn ready(&mut self, state: &State) {
+        self.tracked.extend(
+            state
+                .nodes
+                .values()
+                .filter(|node| is_stream(state, node.object_id))
+                .map(|node| node.object_id),
+        );
+        self.is_ready = true;
+    }
+
+    /// Handle a change which has already been applied to the state.
+    pub fn update(
+        &mut self,
+        state: &State,
+        wirehose: &dyn CommandSender,
+        change: Change,
+    ) {
+        match change {
+            Change::Node(object_id) if self.tracked.contains(&object_id) => {
+                self.save(state, object_id);
+            }
+            Change::Node(object_id) if self.is_ready => {
+                self.restore(state, wirehose, object_id);
+            }
+            Change::Owner => {
+                for object_id in std::mem::take(&mut self.pending) {
+                    self.restore(state, wirehose, object_id);
+                }
+            }
+            Change::Target(object_id) if self.tracked.contains(&object_id) => {
+                self.save(state, object_id);
+            }
+            Change::Removed(object_id) => {
+                self.tracked.remove(&object_id);
+                self.pending.remove(&object_id);
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply saved settings to a new stream once enough is known about it.
+    fn restore(
+        &mut self,
+        state: &State,
+        wirehose: &dyn CommandSender,
+        object_id: ObjectId,
+    ) {
+        if !is_stream(state, object_id) {
+            return;
+        }
+        let Some(node) = state.nodes.get(&object_id) else {
+            return;
+        };
+        // Wait for the channel count
+        let Some(volumes) = &node.volumes else {
+            return;
+        };
+        let Some(key) = self.config.match_key(state, node) else {
+            self.pending.insert(object_id);
+            return;
+        };
+
+        self.pending.remove(&object_id);
+        self.tracked.insert(object_id);
+
+        let Some(settings) = self.saved.get(&key) else {
+            return;
+        };
+        log::info!("Restoring settings for {key}");
+
+        if let Some(saved) =
+            settings.volumes.as_ref().filter(|_| self.config.volume)
+        {
+            if let Some(restored) = adapt_volumes(saved, volumes.len()) {
+                wirehose.node_volumes(object_id, restored);
+            }
+        }
+
+        if let Some(mute) = settings.mute.filter(|_| self.config.mute) {
+            wirehose.node_mute(object_id, mute);
+        }
+
+        if let Some(target) =
+            settings.target.as_ref().filter(|_| self.config.target)
+        {
+            set_target(state, wirehose, object_id, target);
+        }
+    }
+
+    /// Save a tracked stream's current settings.
+    fn save(&mut self, state: &State, object_id: ObjectId) {
+        let Some(node) = state.nodes.get(&object_id) else {
+            return;
+        };
+        let Some(key) = self.config.match_key(state, node) else {
+            return;
+        };
+
+        let settings = StreamSettings {
+            volumes: node.volumes.clone(),
+            mute: node.mute,
+            target: target_name(state, object_id),
+        };
+        if self.saved.get(&key) == Some(&settings) {
+            return;
+        }
+
+        self.saved.insert(key.clone(), settings.clone());
+        let _ = self
+            .database
+            .send(DbMessage::SaveStreamSettings { key, settings });
+    }
+}
+
+fn is_stream(state: &State, object_id: ObjectId) -> bool {
+    state
+        .nodes
+        .get(&object_id)
+        .and_then(|node| node.props.media_class())
+        .is_some_and(|media_class| {
+            media_class::is_sink_input(media_class)
+                || media_class::is_source_output(media_class)
+        })
+}
+
+/// Fit saved volumes to a stream's channel count. If the counts differ, every
+/// channel gets the average of the saved volumes.
+fn adapt_volumes(saved: &[f32], channels: usize) -> Option<Vec<f32>> {
+    if saved.is_empty() || channels == 0 {
+        return None;
+    }
+    if saved.len() == channels {
+        return Some(saved.to_vec());
+    }
+
+    let average = saved.iter().sum::<f32>() / saved.len() as f32;
+    Some(vec![average; channels])
+}
+
+/// Returns the node.name of a stream's explicitly-set target.
+fn target_name(state: &State, object_id: ObjectId) -> Option<String> {
+    let properties = state
+        .get_metadata_by_name("default")?
+        .properties
+        .get(&object_id.into())?;
+
+    let target = ["target.node", "target.object"].iter().find_map(|key| {
+        let target: i64 = serde_json::from_str(properties.get(*key)?).ok()?;
+        (target != -1).then_some(target)
+    })?;
+
+    // target.object may be an object serial rather than an ID
+    state
+        .nodes
+        .values()
+        .find(|node| {
+            i64::from(u32::from(node.object_id)) == target
+                || node.props.object_serial().map(|s| *s as i64) == Some(target)
+        })?
+        .props
+        .node_name()
+        .cloned()
+}
+
+/// Point a stream at the node with the given node.name, if it exists.
+fn set_target(
+    state: &State,
+    wirehose: &dyn CommandSender,
+    object_id: ObjectId,
+    target: &str,
+) {
+    let Some(metadata) = state.get_metadata_by_name("default") else {
+        return;
+    };
+    let Some(target_id) = state
+        .nodes
+        .values()
+        .find(|node| {
+            node.object_id != object_id
+                && node.props.node_name().map(String::as_str) == Some(target)
+        })
+        .map(|node| node.object_id)
+    else {
+        return;
+    };
+
+    wirehose.metadata_set_property(
+        metadata.object_id,
+        object_id.into(),
+        String::from("target.object"),
+        None,
+        None,
+    );
+    wirehose.metadata_set_property(
+        metadata.object_id,
+        object_id.into(),
+        String::from("target.node"),
+        Some(String::from("Spa:Id")),
+        Some(u32::from(target_id).to_string()),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use crate::db::db_channel::DatabaseThread;
+    use crate::mock;
+    use crate::wirehose::PropertyStore;
+
+    /// Apply an event to the state, then to the provided StreamRestore.
+    fn apply(
+        state: &mut State,
+        stream_restore: &mut StreamRestore,
+        wirehose: &mock::WirehoseHandle,
+        event: StateEvent,
+    ) {
+        let change = Change::from_event(&event);
+        state.update(wirehose, event);
+        if let Some(change) = change {
+            stream_restore.update(state, wirehose, change);
+        }
+    }
+
+    fn client(object_id: ObjectId) -> StateEvent {
+        let mut props = PropertyStore::default();
+        props.set_application_name(String::from("Firefox"));
+        StateEvent::ClientProperties { object_id, props }
+    }
+
+    fn stream(object_id: ObjectId, client_id: ObjectId) -> StateEvent {
+        let mut props = PropertyStore::default();
+        props.set_node_name(format!("firefox_{}", u32::from(object_id)));
+        props.set_media_class(String::from("Stream/Output/Audio"));
+        props.set_client_id(client_id);
+        StateEvent::NodeProperties { object_id, props }
+    }
+
+    #[test]
+    fn saved_settings_restored_to_new_stream() {
+        let db_path = std::env::temp_dir().join("test_stream_restore.db");
+        let _ = std::fs::remove_file(&db_path);
+        // The thread isn't run, so saved settings stay queued
+        let (_db_thread, db_handle) = DatabaseThread::new(
+            db_path.to_str().unwrap(),
+            None,
+            "test/default",
+            Duration::from_millis(1000),
+            10,
+            Default::default(),
+        )
+        .unwrap();
+
+        let wirehose = mock::WirehoseHandle::default();
+        let mut state = State::default();
+        let config = config::StreamRestore {
+            enabled: true,
+            ..Default::default()
+        };
+        let mut stream_restore =
+            StreamRestore::new(config, db_handle, Default::default());
+        let id = ObjectId::from_raw_id;
+
+        let mut sink_props = PropertyStore::default();
+        sink_props.set_node_name(String::from("headphones"));
+        sink_props.set_media_class(String::from("Audio/Sink"));
+        for event in [
+            StateEvent::MetadataMetadataName {
+                object_id: id(1),
+                metadata_name: String::from("default"),
+            },
+            StateEvent::NodeProperties {
+                object_id: id(30),
+                props: sink_props,
+            },
+            client(id(40)),
+            stream(id(50), id(40)),
+        ] {
+            apply(&mut state, &mut stream_restore, &wirehose, event);
+        }
+        stream_restore.ready(&state);
+
+        // Changes to an existing stream are saved
+        for event in [
+            StateEvent::NodeVolumes {
+                object_id: id(50),
+                volumes: vec![0.5, 0.25],
+            },
+            StateEvent::NodeMute {
+                object_id: id(50),
+                mute: true,
+            },
+            StateEvent::MetadataProperty {
+                object_id: id(1),
+                subject: 50,
+                key: Some(String::from("target.node")),
+                value: Some(String::from("30")),
+            },
+            StateEvent::Removed { object_id: id(50) },
+        ] {
+            apply(&mut state, &mut stream_restore, &wirehose, event);
+        }
+        assert!(wirehose.volumes_set.borrow().is_empty());
+
+        // A new instance isn't restored until its client is known, so it's
+        // matched by application.name rather than node.name
+        for event in [
+            stream(id(51), id(41)),
+            StateEvent::NodeVolumes {
+                object_id: id(51),
+                volumes: vec![1.0, 1.0],
+            },
+        ] {
+            apply(&mut state, &mut stream_restore, &wirehose, event);
+        }
+        assert!(wirehose.volumes_set.borrow().is_empty());
+
+        apply(&mut state, &mut stream_restore, &wirehose, client(id(41)));
+        assert_eq!(*wirehose.volumes_set.borrow(), [(id(51), vec![0.5, 0.25])]);
+        assert_eq!(*wirehose.mutes_set.borrow(), [(id(51), true)]);
+        assert!(wirehose.metadata_set.borrow().contains(&(
+            51,
+            String::from("target.node"),
+            Some(String::from("30"))
+        )));
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[test]
+    fn adapt_volumes_same_channels() {
+        assert_eq!(adapt_volumes(&[0.5, 0.25], 2), Some(vec![0.5, 0.25]));
+    }
+
+    #[test]
+    fn adapt_volumes_different_channels() {
+        assert_eq!(adapt_volumes(&[0.5, 0.25], 1), Some(vec![0.375]));
+        assert_eq!(adapt_volumes(&[0.5], 3), Some(vec![0.5, 0.5, 0.5]));
+    }
+
+    #[test]
+    fn adapt_volumes_empty() {
+        assert_eq!(adapt_volumes(&[], 2), None);
+        assert_eq!(adapt_volumes(&[0.5], 0), None);
+    }
+}
@@ -5,9 +5,13 @@ use std::collections::HashMap;
 
 use serde_json::json;
 
-use crate::config;
+use crate::config::{self, VolumeScale};
 use crate::device_kind::DeviceKind;
-use crate::wirehose::{media_class, state, CommandSender, ObjectId};
+use crate::wirehose::{
+    audio_channel::{self, Side},
+    levels::Levels,
+    media_class, state, CommandSender, ObjectId, PortDirection,
+};
 
 /// A view for transforming [`State`](`state::State`) into a better format for
 /// rendering.
@@ -35,6 +39,8 @@ pub struct View<'a> {
     pub nodes_recording: Vec<ObjectId>,
     pub nodes_output: Vec<ObjectId>,
     pub nodes_input: Vec<ObjectId>,
+    pub nodes_midi: Vec<ObjectId>,
+    pub nodes_video: Vec<ObjectId>,
 
     pub devices_all: Vec<ObjectId>,
 
@@ -45,6 +51,9 @@ pub struct View<'a> {
     pub default_source: Option<Target>,
 
     pub metadata_id: Option<ObjectId>,
+
+    pub ports: HashMap<ObjectId, Port>,
+    pub port_links: Vec<PortLink>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
@@ -73,6 +82,9 @@ pub struct Node {
     pub mute: bool,
 
     pub peaks: Option<Vec<f32>>,
+    /// Levels of the peak-hold markers, if enabled
+    pub peak_holds: Option<Vec<f32>>,
+    pub levels: Option<Levels>,
     pub positions: Option<Vec<u32>>,
 
     /// If this is a device/endpoint node, store the (device_id, route_index,
@@ -82,12 +94,18 @@ pub struct Node {
 
     pub is_default_sink: bool,
     pub is_default_source: bool,
+
+    /// Whether this is a virtual sink or source created by wiremix, which
+    /// can be removed
+    pub is_virtual: bool,
 }
 
 #[derive(Debug)]
 pub struct Device {
     pub object_id: ObjectId,
     pub object_serial: u64,
+    /// device.name, which stays the same across PipeWire restarts
+    pub name: Option<String>,
     pub title: String,
 
     pub profiles: Vec<(Target, String)>,
@@ -96,6 +114,22 @@ pub struct Device {
     pub target: Option<Target>,
 }
 
+#[derive(Debug)]
+pub struct Port {
+    pub object_id: ObjectId,
+    pub node_id: ObjectId,
+    pub direction: PortDirection,
+    pub title: String,
+}
+
+/// A link between two ports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PortLink {
+    pub object_id: ObjectId,
+    pub output_port_id: ObjectId,
+    pub input_port_id: ObjectId,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum VolumeAdjustment {
     Relative(f32),
@@ -108,6 +142,8 @@ pub enum NodeKind {
     Recording,
     Output,
     Input,
+    Midi,
+    Video,
     #[default]
     All,
 }
@@ -199,33 +235,39 @@ impl Node {
         let media_class = node.props.media_class()?.clone();
         let title = names.resolve(state, node)?;
 
+        // MIDI and video nodes have no volume and are described by what they
+        // are linked to.
+        let is_midi_or_video = media_class::is_midi(&media_class)
+            || media_class::is_video(&media_class);
+
         // Nodes can represent either streams or devices.
-        let (volumes, mute, device_info) =
-            if let Some(device_id) = node.props.device_id() {
-                // Nodes for devices should get their volume and mute status
-                // from the associated device's active route which is also used
-                // for changing the volume and mute status.
-                let device = state.devices.get(device_id)?;
-                let card_device = *node.props.card_profile_device()?;
-                if let Some(route) = active_route(device, card_device) {
-                    let route_index = route.index;
-                    (
-                        route.volumes.clone(),
-                        route.mute,
-                        Some((*device_id, route_index, card_device)),
-                    )
-                } else {
-                    (node.volumes.as_ref()?.clone(), node.mute?, None)
-                }
+        let (volumes, mute, device_info) = if is_midi_or_video {
+            (Vec::new(), false, None)
+        } else if let Some(device_id) = node.props.device_id() {
+            // Nodes for devices should get their volume and mute status
+            // from the associated device's active route which is also used
+            // for changing the volume and mute status.
+            let device = state.devices.get(device_id)?;
+            let card_device = *node.props.card_profile_device()?;
+            if let Some(route) = active_route(device, card_device) {
+                let route_index = route.index;
+                (
+                    route.volumes.clone(),
+                    route.mute,
+                    Some((*device_id, route_index, card_device)),
+                )
             } else {
-                // We can interact with a stream node's volume and mute status
-                // directly.
                 (node.volumes.as_ref()?.clone(), node.mute?, None)
-            };
+            }
+        } else {
+            // We can interact with a stream node's volume and mute status
+            // directly.
+            (node.volumes.as_ref()?.clone(), node.mute?, None)
+        };
 
-        let (routes, target, target_title) = if let Some(device_id) =
-            node.props.device_id()
-        {
+        let (routes, target, target_title) = if is_midi_or_video {
+            (None, None, linked_title(state, names, object_id))
+        } else if let Some(device_id) = node.props.device_id() {
             // Targets for device nodes are routes for the associated device.
             let device = state.devices.get(device_id)?;
             let card_device = *node.props.card_profile_device()?;
@@ -314,14 +356,49 @@ impl Node {
             volumes,
             mute,
             peaks: node.peaks.clone(),
+            peak_holds: hold_levels(node),
+            levels: node.levels.clone(),
             positions: node.positions.clone(),
             device_info,
             is_default_sink: default_sink_name.as_ref()
                 == node.props.node_name(),
             is_default_source: default_source_name.as_ref()
                 == node.props.node_name(),
+            is_virtual: node.props.wiremix_virtual() == Some(&true),
         })
     }
+
+    /// The channel positions matching each volume, if they are known.
+    fn channel_positions(&self) -> Option<&[u32]> {
+        self.positions
+            .as_deref()
+            .filter(|positions| positions.len() == self.volumes.len())
+    }
+
+    /// Short labels for each channel, such as "FL", falling back to channel
+    /// numbers.
+    pub fn channel_names(&self) -> Vec<String> {
+        match self.channel_positions() {
+            Some(positions) => positions
+                .iter()
+                .map(|&position| audio_channel::name(position))
+                .collect(),
+            None => (1..=self.volumes.len()).map(|i| i.to_string()).collect(),
+        }
+    }
+
+    /// Which side each channel is on. Two channels without known positions
+    /// are assumed to be stereo.
+    fn channel_sides(&self) -> Vec<Side> {
+        match self.channel_positions() {
+            Some(positions) => positions
+                .iter()
+                .map(|&position| audio_channel::side(position))
+                .collect(),
+            None if self.volumes.len() == 2 => vec![Side::Left, Side::Right],
+            None => vec![Side::Center; self.volumes.len()],
+        }
+    }
 }
 
 impl Device {
@@ -366,6 +443,7 @@ impl Device {
         Some(Device {
             object_id,
             object_serial,
+            name: device.props.device_name().cloned(),
             title,
             profiles,
             target_title,
@@ -374,6 +452,59 @@ impl Device {
     }
 }
 
+impl Port {
+    /// Only ports belonging to a node in the view are included.
+    fn from(
+        port: &state::Port,
+        nodes: &HashMap<ObjectId, Node>,
+    ) -> Option<Port> {
+        let node_id = port.node_id()?;
+        nodes.get(&node_id)?;
+
+        let title = port
+            .props
+            .port_name()
+            .or(port.channel())
+            .cloned()
+            .unwrap_or_else(|| format!("Port {}", u32::from(port.object_id)));
+
+        Some(Port {
+            object_id: port.object_id,
+            node_id,
+            direction: port.direction,
+            title,
+        })
+    }
+}
+
+/// Describes the nodes linked to a node, in either direction.
+fn linked_title(
+    state: &state::State,
+    names: &config::Names,
+    node_id: ObjectId,
+) -> String {
+    let peers: Vec<_> = state
+        .outputs(node_id)
+        .into_iter()
+        .chain(state.inputs(node_id))
+        .unique()
+        .filter_map(|peer_id| names.resolve(state, state.nodes.get(&peer_id)?))
+        .sorted()
+        .collect();
+
+    if peers.is_empty() {
+        String::from("Not linked")
+    } else {
+        peers.join(", ")
+    }
+}
+
+fn hold_levels(node: &state::Node) -> Option<Vec<f32>> {
+    node.peak_holds
+        .as_ref()
+        .map(|holds| holds.iter().map(|hold| hold.peak).collect())
+}
+
 fn default_for(state: &state::State, which: &str) -> Option<String> {
     let metadata = state.get_metadata_by_name("default")?;
     let json = metadata.properties.get(&0)?.get(which)?;
@@ -407,6 +538,121 @@ fn has_target(state: &state::State, node_id: ObjectId) -> bool {
     }
 }
 
+/// Applies an adjustment to linear volumes. Adjusting all channels scales
+/// them so that the loudest channel gets the new volume and the others keep
+/// their ratio to it.
+fn adjust_volumes(
+    volumes: &[f32],
+    adjustment: VolumeAdjustment,
+    channel: Option<usize>,
+    scale: VolumeScale,
+) -> Vec<f32> {
+    let adjust = |level: f32| match adjustment {
+        VolumeAdjustment::Relative(delta) => (level + delta).max(0.0),
+        VolumeAdjustment::Absolute(volume) => volume.max(0.0),
+    };
+
+    // Work with slider positions like the UI does
+    let mut levels: Vec<f32> = volumes
+        .iter()
+        .map(|&volume| scale.from_linear(volume))
+        .collect();
+    match channel {
+        Some(channel) => {
+            if let Some(level) = levels.get_mut(channel) {
+                *level = adjust(*level);
+            }
+        }
+        None => {
+            let loudest = levels.iter().copied().fold(0.0, f32::max);
+            let target = adjust(loudest);
+            if loudest > 0.0 {
+                levels
+                    .iter_mut()
+                    .for_each(|level| *level *= target / loudest);
+            } else {
+                levels.fill(target);
+            }
+        }
+    }
+
+    levels.iter().map(|&level| scale.to_linear(level)).collect()
+}
+
+/// Average slider position of the channels on one side.
+fn side_level(
+    volumes: &[f32],
+    sides: &[Side],
+    side: Side,
+    scale: VolumeScale,
+) -> Option<f32> {
+    let levels: Vec<f32> = volumes
+        .iter()
+        .zip(sides)
+        .filter(|&(_, &channel_side)| channel_side == side)
+        .map(|(&volume, _)| scale.from_linear(volume))
+        .collect();
+    (!levels.is_empty())
+        .then(|| levels.iter().sum::<f32>() / levels.len() as f32)
+}
+
+/// Balance from -1.0 (only left) to 1.0 (only right). Returns None if there
+/// aren't both left and right channels.
+fn balance(volumes: &[f32], sides: &[Side], scale: VolumeScale) -> Option<f32> {
+    let left = side_level(volumes, sides, Side::Left, scale)?;
+    let right = side_level(volumes, sides, Side::Right, scale)?;
+
+    Some(if left == right {
+        0.0
+    } else if left > right {
+        right / left - 1.0
+    } else {
+        1.0 - left / right
+    })
+}
+
+/// Sets the balance of linear volumes. The louder side is left as it is and
+/// center channels are untouched.
+fn set_balance(
+    volumes: &[f32],
+    sides: &[Side],
+    balance: f32,
+    scale: VolumeScale,
+) -> Vec<f32> {
+    let left =
+        side_level(volumes, sides, Side::Left, scale).unwrap_or_default();
+    let right =
+        side_level(volumes, sides, Side::Right, scale).unwrap_or_default();
+    let loudest = left.max(right);
+
+    let (new_left, new_right) = if balance < 0.0 {
+        (loudest, loudest * (1.0 + balance))
+    } else {
+        (loudest * (1.0 - balance), loudest)
+    };
+    let rescale = |level: f32, old: f32, new: f32| {
+        if old > 0.0 {
+            level * new / old
+        } else {
+            new
+        }
+    };
+
+    volumes
+        .iter()
+        .zip(sides)
+        .map(|(&volume, side)| {
+            let level = scale.from_linear(volume);
+            let level = match side {
+                Side::Left => rescale(level, left, new_left),
+                Side::Right => rescale(level, right, new_right),
+                Side::Center => level,
+            };
+            scale.to_linear(level)
+        })
+        .collect()
+}
+
 impl<'a> View<'a> {
     pub fn new(wirehose: &'a dyn CommandSender) -> View<'a> {
         Self {
@@ -418,12 +664,16 @@ impl<'a> View<'a> {
             nodes_recording: Default::default(),
             nodes_output: Default::default(),
             nodes_input: Default::default(),
+            nodes_midi: Default::default(),
+            nodes_video: Default::default(),
             devices_all: Default::default(),
             sinks: Default::default(),
             sources: Default::default(),
             default_sink: Default::default(),
             default_source: Default::default(),
             metadata_id: Default::default(),
+            ports: Default::default(),
+            port_links: Default::default(),
         }
     }
 
@@ -527,6 +777,8 @@ impl<'a> View<'a> {
         let mut nodes_recording = Vec::new();
         let mut nodes_output = Vec::new();
         let mut nodes_input = Vec::new();
+        let mut nodes_midi = Vec::new();
+        let mut nodes_video = Vec::new();
         for (id, node) in
             nodes.iter().sorted_by_key(|(_, node)| node.object_serial)
         {
@@ -543,12 +795,20 @@ impl<'a> View<'a> {
             if media_class::is_source(&node.media_class) {
                 nodes_input.push(*id);
             }
+            if media_class::is_midi(&node.media_class) {
+                nodes_midi.push(*id);
+            }
+            if media_class::is_video(&node.media_class) {
+                nodes_video.push(*id);
+            }
         }
         let nodes_all = nodes_all;
         let nodes_playback = nodes_playback;
         let nodes_recording = nodes_recording;
         let nodes_output = nodes_output;
         let nodes_input = nodes_input;
+        let nodes_midi = nodes_midi;
+        let nodes_video = nodes_video;
 
         let devices_all = devices
             .iter()
@@ -556,6 +816,28 @@ impl<'a> View<'a> {
             .map(|(&id, _)| id)
             .collect();
 
+        let ports: HashMap<ObjectId, Port> = state
+            .ports
+            .values()
+            .filter_map(|port| Port::from(port, &nodes))
+            .map(|port| (port.object_id, port))
+            .collect();
+
+        let port_links: Vec<PortLink> = state
+            .links
+            .iter()
+            .filter(|(_, link)| {
+                ports.contains_key(&link.output_port_id)
+                    && ports.contains_key(&link.input_port_id)
+            })
+            .map(|(&object_id, link)| PortLink {
+                object_id,
+                output_port_id: link.output_port_id,
+                input_port_id: link.input_port_id,
+            })
+            .sorted_by_key(|link| link.object_id)
+            .collect();
+
         Self {
             wirehose,
             nodes,
@@ -565,12 +847,16 @@ impl<'a> View<'a> {
             nodes_recording,
             nodes_output,
             nodes_input,
+            nodes_midi,
+            nodes_video,
             devices_all,
             sinks,
             sources,
             default_sink,
             default_source,
             metadata_id: state.metadatas_by_name.get("default").copied(),
+            ports,
+            port_links,
         }
     }
 
@@ -587,6 +873,8 @@ impl<'a> View<'a> {
                     }
                     _ => node.peaks = None,
                 }
+                node.peak_holds = hold_levels(state_node);
+                node.levels.clone_from(&state_node.levels);
             }
         }
     }
@@ -615,6 +903,55 @@ impl<'a> View<'a> {
         );
     }
 
+    /// Creates a virtual sink or source with a channel for each of the
+    /// provided positions. Its node.name is made from the description, with a
+    /// number added if another node already has that name.
+    pub fn create_virtual_device(
+        &self,
+        device_kind: DeviceKind,
+        description: &str,
+        positions: &[&str],
+        linger: bool,
+    ) {
+        let media_class = match device_kind {
+            DeviceKind::Sink => "Audio/Sink",
+            DeviceKind::Source => "Audio/Source",
+        };
+        let slug: String = description
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+
+        // node.name identifies the node, so it has to be unique
+        let base = format!("wiremix_virtual.{slug}");
+        let mut name = base.clone();
+        let mut suffix = 1;
+        while self.nodes.values().any(|node| node.name == name) {
+            suffix += 1;
+            name = format!("{base}_{suffix}");
+        }
+
+        self.wirehose.node_create_virtual(
+            name,
+            String::from(description),
+            String::from(media_class),
+            positions
+                .iter()
+                .map(|&position| String::from(position))
+                .collect(),
+            linger,
+        );
+    }
+
+    /// Removes a virtual sink or source created by wiremix. Other nodes are
+    /// left alone.
+    pub fn remove_virtual_device(&self, node_id: ObjectId) {
+        if self.nodes.get(&node_id).is_some_and(|node| node.is_virtual) {
+            self.wirehose.node_destroy(node_id);
+        }
+    }
+
     /// Sets the provided node's target to the provided target.
     pub fn set_target(&self, node_id: ObjectId, target: Target) {
         let Some(metadata_id) = self.metadata_id else {
@@ -667,12 +1004,55 @@ impl<'a> View<'a> {
         }
     }
 
+    /// Returns the ports linked to the provided port.
+    pub fn port_peers(&self, port_id: ObjectId) -> Vec<ObjectId> {
+        self.port_links
+            .iter()
+            .filter_map(|link| {
+                if link.output_port_id == port_id {
+                    Some(link.input_port_id)
+                } else if link.input_port_id == port_id {
+                    Some(link.output_port_id)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Links the provided ports, or unlinks them if they are already linked.
+    pub fn toggle_link(
+        &self,
+        output_port_id: ObjectId,
+        input_port_id: ObjectId,
+    ) {
+        let existing = self.port_links.iter().find(|link| {
+            link.output_port_id == output_port_id
+                && link.input_port_id == input_port_id
+        });
+
+        match existing {
+            Some(link) => self.wirehose.link_destroy(link.object_id),
+            None => self.wirehose.link_create(
+                output_port_id,
+                input_port_id,
+                false,
+                true,
+            ),
+        }
+    }
+
     /// Mutes the provided node.
     pub fn mute(&self, node_id: ObjectId) {
         let Some(node) = self.nodes.get(&node_id) else {
             return;
         };
 
+        // MIDI and video nodes can't be muted
+        if node.volumes.is_empty() {
+            return;
+        }
+
         let mute = !node.mute;
 
         if let Some((device_id, route_index, route_device)) = node.device_info {
@@ -687,43 +1067,72 @@ impl<'a> View<'a> {
         }
     }
 
-    /// Changes the volume of the provided node. If max volume is provided,
-    /// won't change volume if result would be greater than max. Returns true
-    /// if volume was changed, otherwise false.
+    /// Changes the volume of the provided node. If a channel is provided,
+    /// only that channel is changed, otherwise all channels are changed while
+    /// keeping the balance between them. If max volume is provided, as a
+    /// percentage on the cubic scale, won't change volume if result would be
+    /// greater than max. This limits the gain the same way whatever the
+    /// scale. Returns true if volume was changed, otherwise false.
     pub fn volume(
         &self,
         node_id: ObjectId,
         adjustment: VolumeAdjustment,
+        channel: Option<usize>,
         max: Option<f32>,
+        scale: VolumeScale,
     ) -> bool {
         let Some(node) = self.nodes.get(&node_id) else {
             return false;
         };
 
-        let mut volumes = node.volumes.clone();
-        if volumes.is_empty() {
+        if node.volumes.is_empty() {
             return false;
         }
-        match adjustment {
-            VolumeAdjustment::Relative(delta) => {
-                let avg = volumes.iter().sum::<f32>() / volumes.len() as f32;
-                volumes.fill((avg.cbrt() + delta).max(0.0).powi(3));
-            }
-            VolumeAdjustment::Absolute(volume) => {
-                volumes.fill(volume.max(0.0).powi(3));
-            }
-        }
-        let volumes = volumes;
+        let volumes = adjust_volumes(&node.volumes, adjustment, channel, scale);
 
         if let Some(max) = max {
-            if volumes
-                .iter()
-                .any(|volume| (volume.cbrt() * 100.0).round() > max)
-            {
+            if volumes.iter().any(|&volume| {
+                (VolumeScale::Cubic.from_linear(volume) * 100.0).round() > max
+            }) {
                 return false;
             }
         }
 
+        self.set_volumes(node, volumes);
+
+        true
+    }
+
+    /// Shifts the balance of the provided node towards the right channels, or
+    /// the left channels if delta is negative. Returns true if the balance
+    /// was changed, otherwise false.
+    pub fn balance(
+        &self,
+        node_id: ObjectId,
+        delta: f32,
+        scale: VolumeScale,
+    ) -> bool {
+        let Some(node) = self.nodes.get(&node_id) else {
+            return false;
+        };
+
+        let sides = node.channel_sides();
+        let Some(current) = balance(&node.volumes, &sides, scale) else {
+            return false;
+        };
+        let volumes = set_balance(
+            &node.volumes,
+            &sides,
+            (current + delta).clamp(-1.0, 1.0),
+            scale,
+        );
+
+        self.set_volumes(node, volumes);
+
+        true
+    }
+
+    fn set_volumes(&self, node: &Node, volumes: Vec<f32>) {
         if let Some((device_id, route_index, route_device)) = node.device_info {
             self.wirehose.device_volumes(
                 device_id,
@@ -732,10 +1141,8 @@ impl<'a> View<'a> {
                 volumes,
             );
         } else {
-            self.wirehose.node_volumes(node_id, volumes);
+            self.wirehose.node_volumes(node.object_id, volumes);
         }
-
-        true
     }
 
     fn object_ids(&self, node_kind: ListKind) -> &[ObjectId] {
@@ -744,6 +1151,8 @@ impl<'a> View<'a> {
             ListKind::Node(NodeKind::Recording) => &self.nodes_recording,
             ListKind::Node(NodeKind::Output) => &self.nodes_output,
             ListKind::Node(NodeKind::Input) => &self.nodes_input,
+            ListKind::Node(NodeKind::Midi) => &self.nodes_midi,
+            ListKind::Node(NodeKind::Video) => &self.nodes_video,
             ListKind::Node(NodeKind::All) => &self.nodes_all,
             ListKind::Device => &self.devices_all,
         }
@@ -887,3 +1296,86 @@ impl<'a> View<'a> {
         Some((targets, selected_position))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBIC: VolumeScale = VolumeScale::Cubic;
+
+    fn levels(volumes: &[f32]) -> Vec<f32> {
+        volumes
+            .iter()
+            .map(|volume| (volume.cbrt() * 100.0).round() / 100.0)
+            .collect()
+    }
+
+    fn cubed(levels: &[f32]) -> Vec<f32> {
+        levels.iter().map(|level| level.powi(3)).collect()
+    }
+
+    #[test]
+    fn adjust_keeps_ratios() {
+        let volumes = cubed(&[0.8, 0.4]);
+
+        let adjusted = adjust_volumes(
+            &volumes,
+            VolumeAdjustment::Relative(0.2),
+            None,
+            CUBIC,
+        );
+        assert_eq!(levels(&adjusted), [1.0, 0.5]);
+
+        let adjusted = adjust_volumes(
+            &volumes,
+            VolumeAdjustment::Absolute(0.4),
+            None,
+            CUBIC,
+        );
+        assert_eq!(levels(&adjusted), [0.4, 0.2]);
+
+        let adjusted = adjust_volumes(
+            &volumes,
+            VolumeAdjustment::Relative(-0.1),
+            Some(1),
+            CUBIC,
+        );
+        assert_eq!(levels(&adjusted), [0.8, 0.3]);
+
+        let adjusted = adjust_volumes(
+            &[0.8, 0.4],
+            VolumeAdjustment::Relative(0.2),
+            None,
+            VolumeScale::Linear,
+        );
+        assert_eq!(adjusted, [1.0, 0.5]);
+
+        let silent = cubed(&[0.0, 0.0]);
+        let adjusted = adjust_volumes(
+            &silent,
+            VolumeAdjustment::Relative(0.1),
+            None,
+            CUBIC,
+        );
+        assert_eq!(levels(&adjusted), [0.1, 0.1]);
+    }
+
+    #[test]
+    fn balance_left_and_right() {
+        let sides = [Side::Left, Side::Right, Side::Center, Side::Left];
+        let volumes = cubed(&[0.8, 0.8, 0.5, 0.8]);
+        assert_eq!(balance(&volumes, &sides, CUBIC), Some(0.0));
+
+        let shifted = set_balance(&volumes, &sides, 0.25, CUBIC);
+        assert_eq!(levels(&shifted), [0.6, 0.8, 0.5, 0.6]);
+        assert_eq!(
+            balance(&shifted, &sides, CUBIC).map(|b| (b * 100.0).round()),
+            Some(25.0)
+        );
+
+        let shifted = set_balance(&shifted, &sides, -0.5, CUBIC);
+        assert_eq!(levels(&shifted), [0.8, 0.4, 0.5, 0.8]);
+
+        assert_eq!(balance(&volumes, &[Side::Center; 4], CUBIC), None);
+    }
+}
@@ -0,0 +1,254 @@
+//! A form for creating virtual sinks and sources.
+//!
+//! While the form is open it takes key presses directly rather than through
+//! the keybindings so that a name can be typed.
+
+use ratatui::{
+    prelude::{Buffer, Constraint, Layout, Rect},
+    text::{Line, Span},
+    widgets::{Block, Borders, Padding, StatefulWidget, Widget},
+};
+
+use crossterm::event::{
+    KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEventKind,
+};
+use smallvec::smallvec;
+
+use crate::app::{Action, MouseArea};
+use crate::config::Config;
+use crate::device_kind::DeviceKind;
+
+/// Channel layouts which can be chosen, with their channel positions
+pub const LAYOUTS: &[(&str, &[&str])] = &[
+    ("Mono", &["MONO"]),
+    ("Stereo", &["FL", "FR"]),
+    ("2.1", &["FL", "FR", "LFE"]),
+    ("Quadraphonic", &["FL", "FR", "RL", "RR"]),
+    ("5.1", &["FL", "FR", "FC", "LFE", "RL", "RR"]),
+    ("7.1", &["FL", "FR", "FC", "LFE", "RL", "RR", "SL", "SR"]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Name,
+    Layout,
+    Linger,
+}
+
+/// What to do after the form handles a key press.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FormResult {
+    Continue,
+    Cancel,
+    Submit,
+}
+
+pub struct VirtualDeviceForm {
+    /// Whether to create a sink or a source
+    pub device_kind: DeviceKind,
+    pub name: String,
+    /// Index into [`LAYOUTS`]
+    layout: usize,
+    /// Keep the device after wiremix exits
+    pub linger: bool,
+    field: Field,
+}
+
+impl VirtualDeviceForm {
+    pub fn new(device_kind: DeviceKind) -> Self {
+        Self {
+            device_kind,
+            name: String::new(),
+            layout: 1,
+            linger: false,
+            field: Field::Name,
+        }
+    }
+
+    /// The channel positions of the chosen layout.
+    pub fn positions(&self) -> &'static [&'static str] {
+        LAYOUTS[self.layout].1
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> FormResult {
+        match (key.code, self.field) {
+            (KeyCode::Esc, _) => return FormResult::Cancel,
+            (KeyCode::Enter, _) if !self.name.trim().is_empty() => {
+                return FormResult::Submit;
+            }
+            (KeyCode::Tab | KeyCode::Down, _) => {
+                self.field = match self.field {
+                    Field::Name => Field::Layout,
+                    Field::Layout | Field::Linger => Field::Linger,
+                };
+            }
+            (KeyCode::BackTab | KeyCode::Up, _) => {
+                self.field = match self.field {
+                    Field::Name | Field::Layout => Field::Name,
+                    Field::Linger => Field::Layout,
+                };
+            }
+            (KeyCode::Backspace, Field::Name) => {
+                self.name.pop();
+            }
+            (KeyCode::Char(c), Field::Name)
+                if !key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                self.name.push(c);
+            }
+            (KeyCode::Left, Field::Layout) => {
+                self.layout = self.layout.saturating_sub(1);
+            }
+            (KeyCode::Right, Field::Layout) => {
+                self.layout = (self.layout + 1).min(LAYOUTS.len() - 1);
+            }
+            (
+                KeyCode::Left | KeyCode::Right | KeyCode::Char(' '),
+                Field::Linger,
+            ) => {
+                self.linger = !self.linger;
+            }
+            _ => {}
+        }
+        FormResult::Continue
+    }
+}
+
+pub struct VirtualDeviceWidget<'a> {
+    pub form: &'a VirtualDeviceForm,
+    pub config: &'a Config,
+}
+
+impl VirtualDeviceWidget<'_> {
+    pub fn width() -> u16 {
+        48
+    }
+
+    pub fn height() -> u16 {
+        // 5 rows plus 2 borders
+        7
+    }
+
+    fn field_line<'a>(
+        &self,
+        field: Field,
+        label: &'a str,
+        value: String,
+    ) -> Line<'a> {
+        let (selector, style) = if self.form.field == field {
+            (
+                Span::from(self.config.char_set.dropdown_selector.clone()),
+                self.config.theme.dropdown_selected,
+            )
+        } else {
+            (Span::from(" "), self.config.theme.dropdown_item)
+        };
+        Line::from(vec![
+            selector,
+            Span::from(format!(" {label:<16}")),
+            Span::styled(value, style),
+        ])
+    }
+}
+
+impl StatefulWidget for VirtualDeviceWidget<'_> {
+    type State = Vec<MouseArea>;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let mouse_areas = state;
+
+        // App initialized mouse_areas so clicking anywhere closes this widget.
+        // Make it safe to click within the widget.
+        mouse_areas.push((
+            area,
+            smallvec![MouseEventKind::Down(MouseButton::Left)],
+            smallvec![Action::Nothing],
+        ));
+
+        let title = match self.form.device_kind {
+            DeviceKind::Sink => " New virtual sink ",
+            DeviceKind::Source => " New virtual source ",
+        };
+        let borders = Block::default()
+            .borders(Borders::ALL)
+            .border_style(self.config.theme.dropdown_border)
+            .border_type(self.config.char_set.dropdown_border)
+            .title(title)
+            .padding(Padding::horizontal(1));
+
+        let inner_area = borders.inner(area);
+        borders.render(area, buf);
+
+        let [name_area, layout_area, linger_area, _, help_area] =
+            Layout::vertical([Constraint::Length(1); 5]).areas(inner_area);
+
+        let (layout_name, positions) = LAYOUTS[self.form.layout];
+        let lines = [
+            (
+                name_area,
+                self.field_line(
+                    Field::Name,
+                    "Name",
+                    format!("{}_", self.form.name),
+                ),
+            ),
+            (
+                layout_area,
+                self.field_line(
+                    Field::Layout,
+                    "Channels",
+                    format!("{layout_name} ({})", positions.join(", ")),
+                ),
+            ),
+            (
+                linger_area,
+                self.field_line(
+                    Field::Linger,
+                    "Keep after exit",
+                    String::from(if self.form.linger { "Yes" } else { "No" }),
+                ),
+            ),
+            (help_area, Line::from("  Enter: create  Esc: cancel")),
+        ];
+        for (line_area, line) in lines {
+            line.render(line_area, buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn fill_in_form() {
+        let mut form = VirtualDeviceForm::new(DeviceKind::Sink);
+
+        // A name is required
+        assert_eq!(form.handle_key(key(KeyCode::Enter)), FormResult::Continue);
+
+        for c in "Call busx".chars() {
+            form.handle_key(key(KeyCode::Char(c)));
+        }
+        form.handle_key(key(KeyCode::Backspace));
+        assert_eq!(form.name, "Call bus");
+
+        form.handle_key(key(KeyCode::Down));
+        form.handle_key(key(KeyCode::Left));
+        assert_eq!(form.positions(), &["MONO"]);
+
+        // Letters don't go into the name when it isn't focused
+        form.handle_key(key(KeyCode::Down));
+        form.handle_key(key(KeyCode::Char(' ')));
+        form.handle_key(key(KeyCode::Char('x')));
+        assert!(form.linger);
+        assert_eq!(form.name, "Call bus");
+
+        assert_eq!(form.handle_key(key(KeyCode::Enter)), FormResult::Submit);
+        assert_eq!(form.handle_key(key(KeyCode::Esc)), FormResult::Cancel);
+    }
+}
@@ -1,4 +1,5 @@
 //! Event-based wrapper around pipewire-rs.
+pub mod audio_channel;
 mod client;
 mod command;
 mod deserialize;
@@ -6,11 +7,13 @@ mod device;
 mod event;
 mod event_sender;
 mod execute;
+pub mod levels;
 mod link;
 pub mod media_class;
 mod metadata;
 mod node;
 mod object_id;
+mod port;
 mod property_store;
 mod proxy_registry;
 mod session;
@@ -20,7 +23,7 @@ mod stream_registry;
 mod sync_registry;
 
 pub use command::{Command, CommandSender};
-pub use event::{Event, StateEvent};
+pub use event::{Event, PortDirection, StateEvent, StateEventKind};
 pub use event_sender::EventHandler;
 pub use object_id::ObjectId;
 pub use property_store::PropertyStore;
@@ -0,0 +1,81 @@
+//! Audio channel position names and classification.
+//!
+//! Positions are the values of `enum spa_audio_channel`.
+
+const NAMES: &[&str] = &[
+    "UNKNOWN", "NA", "MONO", "FL", "FR", "FC", "LFE", "SL", "SR", "FLC", "FRC",
+    "RC", "RL", "RR", "TC", "TFL", "TFC", "TFR", "TRL", "TRC", "TRR", "RLC",
+    "RRC", "FLW", "FRW", "LFE2", "FLH", "FCH", "FRH", "TFLC", "TFRC", "TSL",
+    "TSR", "LLFE", "RLFE", "BC", "BLC", "BRC",
+];
+
+/// The first auxiliary channel position, AUX0.
+const START_AUX: u32 = 0x1000;
+/// The last auxiliary channel position.
+const LAST_AUX: u32 = 0x1fff;
+
+/// The short name of a channel position, such as "FL".
+pub fn name(position: u32) -> String {
+    match NAMES.get(position as usize) {
+        Some(name) => String::from(*name),
+        None if (START_AUX..=LAST_AUX).contains(&position) => {
+            format!("AUX{}", position - START_AUX)
+        }
+        None => format!("#{position}"),
+    }
+}
+
+/// Which side of the listener a channel is on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+    Center,
+}
+
+pub fn side(position: u32) -> Side {
+    match name(position).as_str() {
+        "FL" | "SL" | "FLC" | "RL" | "TFL" | "TRL" | "RLC" | "FLW" | "FLH"
+        | "TFLC" | "TSL" | "LLFE" | "BLC" => Side::Left,
+        "FR" | "SR" | "FRC" | "RR" | "TFR" | "TRR" | "RRC" | "FRW" | "FRH"
+        | "TFRC" | "TSR" | "RLFE" | "BRC" => Side::Right,
+        _ => Side::Center,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_sides() {
+        assert_eq!(name(3), "FL");
+        assert_eq!(name(13), "RR");
+        assert_eq!(name(0x1002), "AUX2");
+
+        let sides: Vec<_> = ["FL", "FR", "FC", "LFE", "RL", "RR", "SL", "SR"]
+            .iter()
+            .map(|&expected| {
+                let position =
+                    NAMES.iter().position(|&name| name == expected).unwrap();
+                side(position as u32)
+            })
+            .collect();
+        assert_eq!(
+            sides,
+            [
+                Side::Left,
+                Side::Right,
+                Side::Center,
+                Side::Center,
+                Side::Left,
+                Side::Right,
+                Side::Left,
+                Side::Right,
+            ]
+        );
+        assert_eq!(side(10), Side::Right); // FRC
+        assert_eq!(side(11), Side::Center); // RC
+        assert_eq!(side(26), Side::Left); // FLH
+    }
+}
@@ -10,9 +10,24 @@ pub enum Command {
     DeviceVolumes(ObjectId, i32, i32, Vec<f32>),
     DeviceSetRoute(ObjectId, i32, i32),
     DeviceSetProfile(ObjectId, i32),
-    NodeCaptureStart(ObjectId, u64, bool),
+    NodeCaptureStart(ObjectId, u64, bool, bool),
     NodeCaptureStop(ObjectId),
     MetadataSetProperty(ObjectId, u32, String, Option<String>, Option<String>),
+    LinkCreate {
+        output_port: ObjectId,
+        input_port: ObjectId,
+        passive: bool,
+        linger: bool,
+    },
+    LinkDestroy(ObjectId),
+    NodeCreateVirtual {
+        name: String,
+        description: String,
+        media_class: String,
+        positions: Vec<String>,
+        linger: bool,
+    },
+    NodeDestroy(ObjectId),
 }
 
 /// Trait for sending commands to control PipeWire. The trait exists to
@@ -23,6 +38,7 @@ pub trait CommandSender {
         obj_id: ObjectId,
         object_serial: u64,
         capture_sink: bool,
+        measure_levels: bool,
     );
     fn node_capture_stop(&self, obj_id: ObjectId);
     fn node_mute(&self, obj_id: ObjectId, mute: bool);
@@ -56,4 +72,21 @@ pub trait CommandSender {
         type_: Option<String>,
         value: Option<String>,
     );
+    fn link_create(
+        &self,
+        output_port: ObjectId,
+        input_port: ObjectId,
+        passive: bool,
+        linger: bool,
+    );
+    fn link_destroy(&self, obj_id: ObjectId);
+    fn node_create_virtual(
+        &self,
+        name: String,
+        description: String,
+        media_class: String,
+        positions: Vec<String>,
+        linger: bool,
+    );
+    fn node_destroy(&self, obj_id: ObjectId);
 }
@@ -1,6 +1,7 @@
 use pipewire::link::LinkInfoRef;
+use serde::{Deserialize, Serialize};
 
-use crate::wirehose::{ObjectId, PropertyStore};
+use crate::wirehose::{levels::Levels, ObjectId, PropertyStore};
 
 /// Events emitted by the PipeWire monitoring thread.
 #[derive(Debug)]
@@ -9,12 +10,32 @@ pub enum Event {
     State(StateEvent),
     /// An error occurred during monitoring
     Error(String),
+    /// A command couldn't be carried out, for example because the server
+    /// rejected an object wiremix asked it to create. Monitoring continues.
+    CommandFailed(String),
     /// The [StateEvent]s representing the PipeWire state at the time of
     /// connection have been sent. wirehose is listening for changes now.
     Ready,
 }
 
-#[derive(Debug)]
+/// Which way audio flows through a port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PortDirection {
+    Input,
+    Output,
+}
+
+impl PortDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PortDirection::Input => "Input",
+            PortDirection::Output => "Output",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind")]
 /// PipeWire state change events.
 pub enum StateEvent {
     DeviceEnumRoute {
@@ -72,6 +93,10 @@ pub enum StateEvent {
         peaks: Vec<f32>,
         samples: u32,
     },
+    NodeLevels {
+        object_id: ObjectId,
+        levels: Levels,
+    },
     NodePositions {
         object_id: ObjectId,
         positions: Vec<u32>,
@@ -93,10 +118,18 @@ pub enum StateEvent {
         mute: bool,
     },
 
+    PortProperties {
+        object_id: ObjectId,
+        direction: PortDirection,
+        props: PropertyStore,
+    },
+
     Link {
         object_id: ObjectId,
         output_id: ObjectId,
         input_id: ObjectId,
+        output_port_id: ObjectId,
+        input_port_id: ObjectId,
     },
 
     StreamStopped {
@@ -108,12 +141,127 @@ pub enum StateEvent {
     },
 }
 
+/// The kind of a [`StateEvent`], without its data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum StateEventKind {
+    DeviceEnumRoute,
+    DeviceEnumProfile,
+    DeviceProfile,
+    DeviceProperties,
+    DeviceRoute,
+    MetadataMetadataName,
+    MetadataProperty,
+    ClientProperties,
+    NodePeaks,
+    NodeLevels,
+    NodePositions,
+    NodeProperties,
+    NodeRate,
+    NodeVolumes,
+    NodeMute,
+    PortProperties,
+    Link,
+    StreamStopped,
+    Removed,
+}
+
+impl StateEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StateEventKind::DeviceEnumRoute => "DeviceEnumRoute",
+            StateEventKind::DeviceEnumProfile => "DeviceEnumProfile",
+            StateEventKind::DeviceProfile => "DeviceProfile",
+            StateEventKind::DeviceProperties => "DeviceProperties",
+            StateEventKind::DeviceRoute => "DeviceRoute",
+            StateEventKind::MetadataMetadataName => "MetadataMetadataName",
+            StateEventKind::MetadataProperty => "MetadataProperty",
+            StateEventKind::ClientProperties => "ClientProperties",
+            StateEventKind::NodePeaks => "NodePeaks",
+            StateEventKind::NodeLevels => "NodeLevels",
+            StateEventKind::NodePositions => "NodePositions",
+            StateEventKind::NodeProperties => "NodeProperties",
+            StateEventKind::NodeRate => "NodeRate",
+            StateEventKind::NodeVolumes => "NodeVolumes",
+            StateEventKind::NodeMute => "NodeMute",
+            StateEventKind::PortProperties => "PortProperties",
+            StateEventKind::Link => "Link",
+            StateEventKind::StreamStopped => "StreamStopped",
+            StateEventKind::Removed => "Removed",
+        }
+    }
+}
+
+impl StateEvent {
+    pub fn kind(&self) -> StateEventKind {
+        match self {
+            StateEvent::DeviceEnumRoute { .. } => {
+                StateEventKind::DeviceEnumRoute
+            }
+            StateEvent::DeviceEnumProfile { .. } => {
+                StateEventKind::DeviceEnumProfile
+            }
+            StateEvent::DeviceProfile { .. } => StateEventKind::DeviceProfile,
+            StateEvent::DeviceProperties { .. } => {
+                StateEventKind::DeviceProperties
+            }
+            StateEvent::DeviceRoute { .. } => StateEventKind::DeviceRoute,
+            StateEvent::MetadataMetadataName { .. } => {
+                StateEventKind::MetadataMetadataName
+            }
+            StateEvent::MetadataProperty { .. } => {
+                StateEventKind::MetadataProperty
+            }
+            StateEvent::ClientProperties { .. } => {
+                StateEventKind::ClientProperties
+            }
+            StateEvent::NodePeaks { .. } => StateEventKind::NodePeaks,
+            StateEvent::NodeLevels { .. } => StateEventKind::NodeLevels,
+            StateEvent::NodePositions { .. } => StateEventKind::NodePositions,
+            StateEvent::NodeProperties { .. } => StateEventKind::NodeProperties,
+            StateEvent::NodeRate { .. } => StateEventKind::NodeRate,
+            StateEvent::NodeVolumes { .. } => StateEventKind::NodeVolumes,
+            StateEvent::NodeMute { .. } => StateEventKind::NodeMute,
+            StateEvent::PortProperties { .. } => StateEventKind::PortProperties,
+            StateEvent::Link { .. } => StateEventKind::Link,
+            StateEvent::StreamStopped { .. } => StateEventKind::StreamStopped,
+            StateEvent::Removed { .. } => StateEventKind::Removed,
+        }
+    }
+
+    /// The ID of the object the event pertains to.
+    pub fn object_id(&self) -> ObjectId {
+        match self {
+            StateEvent::DeviceEnumRoute { object_id, .. }
+            | StateEvent::DeviceEnumProfile { object_id, .. }
+            | StateEvent::DeviceProfile { object_id, .. }
+            | StateEvent::DeviceProperties { object_id, .. }
+            | StateEvent::DeviceRoute { object_id, .. }
+            | StateEvent::MetadataMetadataName { object_id, .. }
+            | StateEvent::MetadataProperty { object_id, .. }
+            | StateEvent::ClientProperties { object_id, .. }
+            | StateEvent::NodePeaks { object_id, .. }
+            | StateEvent::NodeLevels { object_id, .. }
+            | StateEvent::NodePositions { object_id, .. }
+            | StateEvent::NodeProperties { object_id, .. }
+            | StateEvent::NodeRate { object_id, .. }
+            | StateEvent::NodeVolumes { object_id, .. }
+            | StateEvent::NodeMute { object_id, .. }
+            | StateEvent::PortProperties { object_id, .. }
+            | StateEvent::Link { object_id, .. }
+            | StateEvent::StreamStopped { object_id }
+            | StateEvent::Removed { object_id } => *object_id,
+        }
+    }
+}
+
 impl From<&LinkInfoRef> for StateEvent {
     fn from(link_info: &LinkInfoRef) -> Self {
         StateEvent::Link {
             object_id: ObjectId::from_raw_id(link_info.id()),
             output_id: ObjectId::from_raw_id(link_info.output_node_id()),
             input_id: ObjectId::from_raw_id(link_info.input_node_id()),
+            output_port_id: ObjectId::from_raw_id(link_info.output_port_id()),
+            input_port_id: ObjectId::from_raw_id(link_info.input_port_id()),
         }
     }
 }
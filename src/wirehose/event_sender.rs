@@ -52,6 +52,15 @@ impl EventSender {
         }
     }
 
+    pub fn send_command_failed(&self, message: String) {
+        let event = Event::CommandFailed(message);
+        if !self.handler.borrow_mut().handle_event(event) {
+            if let Some(main_loop) = self.main_loop_weak.upgrade() {
+                main_loop.quit();
+            }
+        }
+    }
+
     pub fn send_error(&self, error: String) {
         if !self.handler.borrow_mut().handle_event(Event::Error(error)) {
             if let Some(main_loop) = self.main_loop_weak.upgrade() {
@@ -1,11 +1,15 @@
-use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
 
 use crate::wirehose::event_sender::EventSender;
 use crate::wirehose::proxy_registry::ProxyRegistry;
 use crate::wirehose::stream_registry::StreamRegistry;
-use crate::wirehose::{command::Command, stream};
+use crate::wirehose::{command::Command, stream, ObjectId};
 
-use pipewire::{core::Core, device::Device, node::Node};
+use pipewire::{
+    core::Core, device::Device, link::Link, node::Node, properties::properties,
+    proxy::ProxyT, registry::Registry,
+};
 
 use libspa::param::ParamType;
 use libspa::pod::{
@@ -15,11 +19,16 @@ use libspa::pod::{
 
 pub fn execute_command(
     core: &Core,
+    registry: &Registry,
     sender: Rc<EventSender>,
     streams: &mut StreamRegistry<stream::StreamData>,
-    proxies: &ProxyRegistry,
+    proxies: &Rc<RefCell<ProxyRegistry>>,
     command: Command,
 ) {
+    // Created objects remove themselves from the registry if they fail
+    let proxies_weak = Rc::downgrade(proxies);
+    let proxies = &mut proxies.borrow_mut();
+
     match command {
         Command::NodeMute(obj_id, mute) => {
             if let Some(node) = proxies.nodes.get(&obj_id) {
@@ -51,13 +60,19 @@ pub fn execute_command(
                 device_set_profile(device, profile_index);
             }
         }
-        Command::NodeCaptureStart(obj_id, object_serial, capture_sink) => {
+        Command::NodeCaptureStart(
+            obj_id,
+            object_serial,
+            capture_sink,
+            measure_levels,
+        ) => {
             let result = stream::capture_node(
                 core,
                 &sender,
                 obj_id,
                 &object_serial.to_string(),
                 capture_sink,
+                measure_levels,
             );
             if let Some((stream, listener)) = result {
                 streams.add_stream(obj_id, stream, listener);
@@ -76,9 +91,156 @@ pub fn execute_command(
                 );
             }
         }
+        Command::LinkCreate {
+            output_port,
+            input_port,
+            passive,
+            linger,
+        } => {
+            link_create(
+                core,
+                sender,
+                proxies,
+                &proxies_weak,
+                output_port,
+                input_port,
+                passive,
+                linger,
+            );
+        }
+        Command::LinkDestroy(obj_id) | Command::NodeDestroy(obj_id) => {
+            let _ = registry.destroy_global(obj_id.into());
+        }
+        Command::NodeCreateVirtual {
+            name,
+            description,
+            media_class,
+            positions,
+            linger,
+        } => {
+            node_create_virtual(
+                core,
+                sender,
+                proxies,
+                &proxies_weak,
+                &name,
+                &description,
+                &media_class,
+                &positions,
+                linger,
+            );
+        }
+    }
+}
+
+fn link_create(
+    core: &Core,
+    sender: Rc<EventSender>,
+    proxies: &mut ProxyRegistry,
+    proxies_weak: &Weak<RefCell<ProxyRegistry>>,
+    output_port: ObjectId,
+    input_port: ObjectId,
+    passive: bool,
+    linger: bool,
+) {
+    let (output_port, input_port) =
+        (u32::from(output_port), u32::from(input_port));
+    // link-factory finds the nodes from the ports.
+    let props = properties! {
+        *pipewire::keys::LINK_OUTPUT_PORT => output_port.to_string(),
+        *pipewire::keys::LINK_INPUT_PORT => input_port.to_string(),
+        *pipewire::keys::LINK_PASSIVE => passive.to_string(),
+        *pipewire::keys::OBJECT_LINGER => linger.to_string(),
+    };
+    let report = move |error: &str| {
+        sender.send_command_failed(format!(
+            "Couldn't link port {output_port} to port {input_port}: {error}"
+        ));
+    };
+    match core.create_object::<Link>("link-factory", &props) {
+        Ok(link) => keep_created(proxies, proxies_weak, Rc::new(link), report),
+        Err(error) => report(&error.to_string()),
+    }
+}
+
+fn node_create_virtual(
+    core: &Core,
+    sender: Rc<EventSender>,
+    proxies: &mut ProxyRegistry,
+    proxies_weak: &Weak<RefCell<ProxyRegistry>>,
+    name: &str,
+    description: &str,
+    media_class: &str,
+    positions: &[String],
+    linger: bool,
+) {
+    let props = properties! {
+        *pipewire::keys::FACTORY_NAME => "support.null-audio-sink",
+        *pipewire::keys::NODE_NAME => name,
+        *pipewire::keys::NODE_DESCRIPTION => description,
+        *pipewire::keys::MEDIA_CLASS => media_class,
+        *pipewire::keys::AUDIO_CHANNELS => positions.len().to_string(),
+        // There's no constant for this one
+        "audio.position" => positions.join(","),
+        // Make volume changes apply to the monitor ports as well
+        "monitor.channel-volumes" => "true",
+        *pipewire::keys::OBJECT_LINGER => linger.to_string(),
+        // Marks the node as one wiremix may remove
+        "wiremix.virtual" => "true",
+    };
+    let description = String::from(description);
+    let report = move |error: &str| {
+        sender.send_command_failed(format!(
+            "Couldn't create virtual device {description}: {error}"
+        ));
+    };
+    match core.create_object::<Node>("adapter", &props) {
+        Ok(node) => keep_created(proxies, proxies_weak, Rc::new(node), report),
+        Err(error) => report(&error.to_string()),
     }
 }
 
+/// Objects created by wiremix are destroyed along with their proxies unless
+/// they linger, so keep the proxy until the object is removed. Its ID is only
+/// known once bound. If the server rejects the object, it's never bound, so
+/// the proxy is also dropped on an error, after passing the error's message to
+/// `on_error`.
+fn keep_created<P: ProxyT + 'static>(
+    proxies: &mut ProxyRegistry,
+    proxies_weak: &Weak<RefCell<ProxyRegistry>>,
+    proxy: Rc<P>,
+    on_error: impl Fn(&str) + 'static,
+) {
+    let proxy_id = proxy.upcast_ref().id();
+    let global_id = Rc::new(Cell::new(None));
+    let forget = {
+        let proxies_weak = Weak::clone(proxies_weak);
+        move || {
+            if let Some(proxies) = proxies_weak.upgrade() {
+                proxies.borrow_mut().remove_created(proxy_id);
+            }
+        }
+    };
+    let listener = proxy
+        .upcast_ref()
+        .add_listener_local()
+        .bound({
+            let global_id = Rc::clone(&global_id);
+            move |id| global_id.set(Some(ObjectId::from_raw_id(id)))
+        })
+        .error({
+            let forget = forget.clone();
+            move |_seq, _res, message| {
+                on_error(message);
+                forget();
+            }
+        })
+        .removed(forget)
+        .register();
+
+    proxies.add_created(proxy_id, proxy, Box::new(listener), global_id);
+}
+
 fn node_set_mute(node: &Node, mute: bool) {
     node_set_properties(
         node,
@@ -0,0 +1,396 @@
+//! Level measurement of captured audio: RMS, true peak, and EBU R128
+//! momentary and short-term loudness.
+//!
+//! Audio is measured in 100 ms blocks. Loudness follows ITU-R BS.1770, with
+//! K-weighting filters and 4x oversampling for true peaks.
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+use serde::Serialize;
+
+use crate::wirehose::audio_channel;
+
+/// Blocks per second
+const BLOCK_RATE: u32 = 10;
+/// Blocks in the RMS window of 300 ms
+const RMS_BLOCKS: usize = 3;
+/// Blocks in the momentary loudness and true peak window of 400 ms
+const MOMENTARY_BLOCKS: usize = 4;
+/// Blocks in the short-term loudness window of 3 s
+const SHORT_TERM_BLOCKS: usize = 30;
+/// Loudness below this is treated as silence, in LUFS
+const ABSOLUTE_GATE: f32 = -70.0;
+
+/// Oversampling factor for true peaks
+const OVERSAMPLING: usize = 4;
+/// Interpolation filter taps for each oversampled phase
+const TAPS_PER_PHASE: usize = 12;
+
+/// Levels measured over recent audio.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Levels {
+    /// RMS amplitude of each channel over the last 300 ms
+    pub rms: Vec<f32>,
+    /// Oversampled peak amplitude of each channel over the last 400 ms
+    pub true_peaks: Vec<f32>,
+    /// Loudness over the last 400 ms in LUFS, or None if silent
+    pub momentary: Option<f32>,
+    /// Loudness over the last 3 s in LUFS, or None if silent
+    pub short_term: Option<f32>,
+}
+
+/// How much a channel at a position contributes to loudness.
+pub fn channel_weight(position: u32) -> f32 {
+    match audio_channel::name(position).as_str() {
+        "LFE" | "LFE2" | "LLFE" | "RLFE" => 0.0,
+        // Surround channels
+        "SL" | "SR" | "RL" | "RR" => 1.41,
+        _ => 1.0,
+    }
+}
+
+/// A second-order IIR filter in transposed direct form II.
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self { b, a, z: [0.0; 2] }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// The two stages of the K-weighting filter: a high shelf modelling the head
+/// followed by a high-pass filter.
+fn k_weighting(rate: u32) -> [Biquad; 2] {
+    let rate = f64::from(rate);
+
+    let f0 = 1681.974450955533;
+    let gain_db = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = (PI * f0 / rate).tan();
+    let vh = 10.0_f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    [shelf, high_pass]
+}
+
+/// Coefficients of a Hann-windowed sinc filter for interpolating between
+/// samples.
+fn interpolation_filter() -> [f32; OVERSAMPLING * TAPS_PER_PHASE] {
+    let len = OVERSAMPLING * TAPS_PER_PHASE;
+    let center = (len - 1) as f64 / 2.0;
+
+    let mut taps = [0.0; OVERSAMPLING * TAPS_PER_PHASE];
+    for (n, tap) in taps.iter_mut().enumerate() {
+        let t = (n as f64 - center) / OVERSAMPLING as f64;
+        let sinc = if t == 0.0 {
+            1.0
+        } else {
+            (PI * t).sin() / (PI * t)
+        };
+        let window =
+            0.5 - 0.5 * (2.0 * PI * (n as f64 + 0.5) / len as f64).cos();
+        *tap = (sinc * window) as f32;
+    }
+    taps
+}
+
+#[derive(Debug, Clone)]
+struct Channel {
+    weight: f32,
+    filters: [Biquad; 2],
+    /// Recent samples for interpolation, newest first
+    history: [f32; TAPS_PER_PHASE],
+    /// Sum of squared samples in the current block
+    sum: f64,
+    /// Sum of squared K-weighted samples in the current block
+    weighted_sum: f64,
+    /// Highest true peak in the current block
+    true_peak: f32,
+}
+
+/// Measurements of one 100 ms block.
+#[derive(Debug, Clone)]
+struct Block {
+    /// Mean square of each channel
+    powers: Vec<f64>,
+    /// Channel-weighted sum of K-weighted mean squares
+    weighted_power: f64,
+    true_peaks: Vec<f32>,
+}
+
+/// Measures levels of interleaved audio from a single stream.
+#[derive(Debug, Clone)]
+pub struct LevelMeter {
+    channels: Vec<Channel>,
+    interpolation: [f32; OVERSAMPLING * TAPS_PER_PHASE],
+    block_frames: usize,
+    /// Frames in the current block
+    frames: usize,
+    /// Recent complete blocks, newest last
+    blocks: VecDeque<Block>,
+}
+
+impl LevelMeter {
+    /// Creates a meter for audio at the provided rate, with a loudness weight
+    /// for each channel.
+    pub fn new(rate: u32, weights: Vec<f32>) -> Self {
+        let filters = k_weighting(rate);
+        let channels = weights
+            .into_iter()
+            .map(|weight| Channel {
+                weight,
+                filters,
+                history: [0.0; TAPS_PER_PHASE],
+                sum: 0.0,
+                weighted_sum: 0.0,
+                true_peak: 0.0,
+            })
+            .collect();
+
+        Self {
+            channels,
+            interpolation: interpolation_filter(),
+            block_frames: (rate / BLOCK_RATE).max(1) as usize,
+            frames: 0,
+            blocks: VecDeque::with_capacity(SHORT_TERM_BLOCKS),
+        }
+    }
+
+    /// Measures interleaved samples. Returns the latest levels if a block was
+    /// completed.
+    pub fn process(&mut self, samples: &[f32]) -> Option<Levels> {
+        if self.channels.is_empty() {
+            return None;
+        }
+
+        let mut completed = false;
+        for frame in samples.chunks_exact(self.channels.len()) {
+            for (channel, &sample) in self.channels.iter_mut().zip(frame) {
+                let x = f64::from(sample);
+                channel.sum += x * x;
+
+                let weighted =
+                    channel.filters.iter_mut().fold(x, |x, f| f.process(x));
+                channel.weighted_sum += weighted * weighted;
+
+                channel.history.copy_within(..TAPS_PER_PHASE - 1, 1);
+                channel.history[0] = sample;
+                let true_peak = (0..OVERSAMPLING)
+                    .map(|phase| {
+                        channel
+                            .history
+                            .iter()
+                            .enumerate()
+                            .map(|(j, x)| {
+                                x * self.interpolation[phase + OVERSAMPLING * j]
+                            })
+                            .sum::<f32>()
+                            .abs()
+                    })
+                    .fold(sample.abs(), f32::max);
+                channel.true_peak = channel.true_peak.max(true_peak);
+            }
+
+            self.frames += 1;
+            if self.frames == self.block_frames {
+                self.finish_block();
+                completed = true;
+            }
+        }
+
+        completed.then(|| self.levels())
+    }
+
+    fn finish_block(&mut self) {
+        let frames = self.frames as f64;
+        let block = Block {
+            powers: self
+                .channels
+                .iter()
+                .map(|channel| channel.sum / frames)
+                .collect(),
+            weighted_power: self
+                .channels
+                .iter()
+                .map(|channel| {
+                    f64::from(channel.weight) * channel.weighted_sum / frames
+                })
+                .sum(),
+            true_peaks: self
+                .channels
+                .iter()
+                .map(|channel| channel.true_peak)
+                .collect(),
+        };
+
+        for channel in &mut self.channels {
+            channel.sum = 0.0;
+            channel.weighted_sum = 0.0;
+            channel.true_peak = 0.0;
+        }
+        self.frames = 0;
+
+        if self.blocks.len() == SHORT_TERM_BLOCKS {
+            self.blocks.pop_front();
+        }
+        self.blocks.push_back(block);
+    }
+
+    /// The most recent blocks, if there are enough of them.
+    fn recent(&self, count: usize) -> Option<impl Iterator<Item = &Block>> {
+        let skip = self.blocks.len().checked_sub(count)?;
+        Some(self.blocks.iter().skip(skip))
+    }
+
+    fn loudness(&self, count: usize) -> Option<f32> {
+        let power = self
+            .recent(count)?
+            .map(|block| block.weighted_power)
+            .sum::<f64>()
+            / count as f64;
+        let loudness = (-0.691 + 10.0 * power.log10()) as f32;
+        (loudness > ABSOLUTE_GATE).then_some(loudness)
+    }
+
+    fn levels(&self) -> Levels {
+        let channels = 0..self.channels.len();
+        let rms_blocks = RMS_BLOCKS.min(self.blocks.len());
+        let rms = channels
+            .clone()
+            .map(|c| {
+                let power = self
+                    .blocks
+                    .iter()
+                    .rev()
+                    .take(rms_blocks)
+                    .map(|block| block.powers[c])
+                    .sum::<f64>()
+                    / rms_blocks as f64;
+                power.sqrt() as f32
+            })
+            .collect();
+        let true_peaks = channels
+            .map(|c| {
+                self.blocks
+                    .iter()
+                    .rev()
+                    .take(MOMENTARY_BLOCKS)
+                    .map(|block| block.true_peaks[c])
+                    .fold(0.0, f32::max)
+            })
+            .collect();
+
+        Levels {
+            rms,
+            true_peaks,
+            momentary: self.loudness(MOMENTARY_BLOCKS),
+            short_term: self.loudness(SHORT_TERM_BLOCKS),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(rate: u32, frequency: f32, phase: f32, seconds: f32) -> Vec<f32> {
+        let count = (rate as f32 * seconds) as usize;
+        (0..count)
+            .map(|n| {
+                let t = n as f64 / f64::from(rate);
+                let angle = 2.0 * PI * f64::from(frequency) * t;
+                (angle + f64::from(phase)).sin() as f32
+            })
+            .collect()
+    }
+
+    fn last_levels(meter: &mut LevelMeter, samples: &[f32]) -> Levels {
+        samples
+            .chunks(1024)
+            .filter_map(|chunk| meter.process(chunk))
+            .last()
+            .unwrap()
+    }
+
+    #[test]
+    fn full_scale_sine() {
+        let mut meter = LevelMeter::new(48000, vec![1.0]);
+        let levels = last_levels(&mut meter, &sine(48000, 997.0, 0.0, 3.0));
+
+        // A full-scale 997 Hz sine measures -3.01 LUFS
+        let momentary = levels.momentary.unwrap();
+        assert!((momentary + 3.01).abs() < 0.05, "{momentary}");
+        let short_term = levels.short_term.unwrap();
+        assert!((short_term + 3.01).abs() < 0.05, "{short_term}");
+
+        assert!(
+            (levels.rms[0] - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.001
+        );
+        assert!((levels.true_peaks[0] - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn true_peak_between_samples() {
+        // At a quarter of the sample rate with a 45 degree phase, every
+        // sample misses the peak
+        let samples = sine(48000, 12000.0, std::f32::consts::FRAC_PI_4, 0.5);
+        assert!(samples.iter().all(|sample| sample.abs() < 0.71));
+
+        let mut meter = LevelMeter::new(48000, vec![1.0]);
+        let levels = last_levels(&mut meter, &samples);
+        assert!(levels.true_peaks[0] > 0.95, "{:?}", levels.true_peaks);
+
+        // Not enough audio for short-term loudness yet
+        assert_eq!(levels.short_term, None);
+    }
+
+    #[test]
+    fn silence_and_weights() {
+        let mut meter = LevelMeter::new(48000, vec![1.0, 0.0]);
+        let silence = vec![0.0; 48000];
+        assert_eq!(last_levels(&mut meter, &silence).momentary, None);
+
+        // Only the LFE channel has audio, which doesn't count as loudness
+        let lfe: Vec<f32> = sine(48000, 50.0, 0.0, 1.0)
+            .into_iter()
+            .flat_map(|sample| [0.0, sample])
+            .collect();
+        let levels = last_levels(&mut meter, &lfe);
+        assert_eq!(levels.momentary, None);
+        assert!(levels.rms[1] > 0.7);
+
+        assert_eq!(channel_weight(6), 0.0); // LFE
+        assert_eq!(channel_weight(12), 1.41); // RL
+    }
+}
@@ -40,6 +40,6 @@ pub fn monitor_link(
 }
 
 fn link_info_props(sender: &EventSender, link_info: &LinkInfoRef) {
-    // Ignore props and get the nodes directly from the link info.
+    // Ignore props and get the nodes and ports directly from the link info.
     sender.send(StateEvent::from(link_info));
 }
@@ -15,3 +15,29 @@ pub fn is_sink_input(s: &str) -> bool {
 pub fn is_source_output(s: &str) -> bool {
     s == "Stream/Input/Audio"
 }
+
+pub fn is_midi(s: &str) -> bool {
+    matches!(
+        s,
+        "Midi/Bridge"
+            | "Midi/Source"
+            | "Midi/Sink"
+            | "Stream/Output/Midi"
+            | "Stream/Input/Midi"
+    )
+}
+
+pub fn is_video(s: &str) -> bool {
+    matches!(
+        s,
+        "Video/Source"
+            | "Video/Sink"
+            | "Stream/Output/Video"
+            | "Stream/Input/Video"
+    )
+}
+
+/// Whether this is a MIDI or video device rather than a stream.
+pub fn is_midi_or_video_device(s: &str) -> bool {
+    (is_midi(s) || is_video(s)) && !s.starts_with("Stream/")
+}
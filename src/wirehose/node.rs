@@ -14,7 +14,7 @@ use libspa::{
 
 use crate::wirehose::event_sender::EventSender;
 use crate::wirehose::{
-    deserialize::deserialize, ObjectId, PropertyStore, StateEvent,
+    deserialize::deserialize, media_class, ObjectId, PropertyStore, StateEvent,
 };
 
 pub fn monitor_node(
@@ -31,6 +31,8 @@ pub fn monitor_node(
         "Audio/Source" => (),
         "Stream/Output/Audio" => (),
         "Stream/Input/Audio" => (),
+        _ if media_class::is_midi(media_class)
+            || media_class::is_video(media_class) => {}
         _ => return None,
     }
 
@@ -2,9 +2,12 @@
 
 use libspa::utils::dict::DictRef;
 use pipewire::registry::GlobalObject;
+use serde::Serialize;
 
 /// A PipeWire object ID.
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd)]
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd, Serialize,
+)]
 pub struct ObjectId(u32);
 
 impl From<&GlobalObject<&DictRef>> for ObjectId {
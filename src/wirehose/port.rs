@@ -0,0 +1,65 @@
+use std::rc::Rc;
+
+use pipewire::{
+    port::{Port, PortChangeMask, PortInfoRef},
+    proxy::Listener,
+    registry::{GlobalObject, Registry},
+};
+
+use libspa::utils::{dict::DictRef, Direction};
+
+use crate::wirehose::event_sender::EventSender;
+use crate::wirehose::{ObjectId, PortDirection, PropertyStore, StateEvent};
+
+pub fn monitor_port(
+    registry: &Registry,
+    object: &GlobalObject<&DictRef>,
+    sender: &Rc<EventSender>,
+) -> Option<(Rc<Port>, Box<dyn Listener>)> {
+    let object_id = ObjectId::from(object);
+
+    let port: Port = registry.bind(object).ok()?;
+    let port = Rc::new(port);
+
+    let listener = port
+        .add_listener_local()
+        .info({
+            let sender_weak = Rc::downgrade(sender);
+            move |info| {
+                let Some(sender) = sender_weak.upgrade() else {
+                    return;
+                };
+                for change in info.change_mask().iter() {
+                    if change == PortChangeMask::PROPS {
+                        port_info_props(&sender, object_id, info);
+                    }
+                }
+            }
+        })
+        .register();
+
+    Some((port, Box::new(listener)))
+}
+
+fn port_info_props(
+    sender: &EventSender,
+    object_id: ObjectId,
+    port_info: &PortInfoRef,
+) {
+    let Some(props) = port_info.props() else {
+        return;
+    };
+
+    let direction = match port_info.direction() {
+        Direction::Input => PortDirection::Input,
+        Direction::Output => PortDirection::Output,
+        _ => return,
+    };
+
+    let property_store = PropertyStore::from(props);
+    sender.send(StateEvent::PortProperties {
+        object_id,
+        direction,
+        props: property_store,
+    });
+}
@@ -1,9 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
 
 use libspa::utils::dict::DictRef;
 
 use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::wirehose::ObjectId;
 
@@ -28,6 +29,9 @@ struct PropertyEntry {
 /// Provides typed accessors for supported standard PipeWire properties.
 /// [PropertyStore::raw] can be used to access any property (including
 /// unsupported ones) as an unparsed string.
+///
+/// Serializes to a flat object of raw string values, exactly as PipeWire
+/// reported them, so that deserializing re-parses them the same way.
 #[derive(Default, Debug, Clone)]
 pub struct PropertyStore {
     properties: HashMap<String, PropertyEntry>,
@@ -194,8 +198,43 @@ macro_rules! define_properties {
 
 impl From<&DictRef> for PropertyStore {
     fn from(dict: &DictRef) -> Self {
+        PropertyStore::from_raw(dict.iter())
+    }
+}
+
+impl Serialize for PropertyStore {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        // Sort by key so that the output is stable.
+        let raw: BTreeMap<&str, &str> = self
+            .properties
+            .iter()
+            .map(|(key, entry)| (key.as_str(), entry.raw.as_str()))
+            .collect();
+        raw.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PropertyStore {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let raw = HashMap::<String, String>::deserialize(deserializer)?;
+        Ok(PropertyStore::from_raw(
+            raw.iter()
+                .map(|(key, value)| (key.as_str(), value.as_str())),
+        ))
+    }
+}
+
+impl PropertyStore {
+    /// Build a store from raw key/value pairs, parsing the supported
+    /// properties. Values which fail to parse are kept as strings.
+    fn from_raw<'a>(items: impl Iterator<Item = (&'a str, &'a str)>) -> Self {
         let mut properties = HashMap::default();
-        for (key, value) in dict.iter() {
+        for (key, value) in items {
             let entry =
                 parse_dict_item(key, value).unwrap_or_else(|_| PropertyEntry {
                     raw: value.to_string(),
@@ -205,9 +244,7 @@ impl From<&DictRef> for PropertyStore {
         }
         PropertyStore { properties }
     }
-}
 
-impl PropertyStore {
     /// Get the raw string value for a property.
     pub fn raw(&self, key: &str) -> Option<&str> {
         self.properties.get(key).map(|e| e.raw.as_str())
@@ -405,4 +442,47 @@ define_properties! {
     audio_format: String = "audio.format",
     audio_allowed_rates: String = "audio.allowed-rates",
     target_object: String = "target.object",
+    wiremix_virtual: bool = "wiremix.virtual",
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_round_trip() {
+        let mut props = PropertyStore::default();
+        props.set_node_name(String::from("alsa_output.usb"));
+        props.set_object_serial(57);
+        props.set_node_virtual(true);
+        props.properties.insert(
+            String::from("custom.key"),
+            PropertyEntry {
+                raw: String::from("custom value"),
+                parsed: PropertyValue::String,
+            },
+        );
+
+        let json = serde_json::to_string(&props).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["node.name"], "alsa_output.usb");
+        assert_eq!(value["object.serial"], "57");
+        assert_eq!(value["node.virtual"], "true");
+        assert_eq!(value["custom.key"], "custom value");
+
+        let props: PropertyStore = serde_json::from_str(&json).unwrap();
+        assert_eq!(props.node_name(), Some(&String::from("alsa_output.usb")));
+        assert_eq!(props.object_serial(), Some(&57));
+        assert_eq!(props.node_virtual(), Some(&true));
+        assert_eq!(props.raw("custom.key"), Some("custom value"));
+    }
+
+    #[test]
+    fn json_unparseable_value_kept_raw() {
+        let props: PropertyStore =
+            serde_json::from_str(r#"{"object.serial": "not a number"}"#)
+                .unwrap();
+        assert_eq!(props.object_serial(), None);
+        assert_eq!(props.raw("object.serial"), Some("not a number"));
+    }
 }
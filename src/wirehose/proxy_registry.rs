@@ -1,3 +1,4 @@
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
@@ -11,11 +12,21 @@ use pipewire::{
     link::Link,
     metadata::Metadata,
     node::Node,
+    port::Port,
     proxy::{Listener, ProxyListener, ProxyT},
 };
 
 use crate::wirehose::ObjectId;
 
+/// A link or node created by wiremix
+struct CreatedObject {
+    // Declared first so that it's dropped while the proxy is still alive
+    listener: Box<dyn Listener>,
+    proxy: Rc<dyn ProxyT>,
+    /// The object's global ID, once bound
+    global_id: Rc<Cell<Option<ObjectId>>>,
+}
+
 /// Storage for keeping proxies and their listeners alive
 pub struct ProxyRegistry {
     /// Storage for keeping devices alive
@@ -28,12 +39,20 @@ pub struct ProxyRegistry {
     pub metadatas: HashMap<ObjectId, Rc<Metadata>>,
     /// Storage for keeping links alive
     links: HashMap<ObjectId, Rc<Link>>,
+    /// Storage for keeping ports alive
+    ports: HashMap<ObjectId, Rc<Port>>,
+    /// Links and nodes created by wiremix, with their listeners and their IDs
+    /// once bound, by proxy ID. These are separate from the objects bound
+    /// from the registry because destroying them destroys the object.
+    created: HashMap<u32, CreatedObject>,
     /// Storage for keeping listeners alive
     listeners: HashMap<ObjectId, Vec<Box<dyn Listener>>>,
-    /// Devices, clients, nodes, links, and metadata pending deletion
+    /// Devices, clients, nodes, links, ports, and metadata pending deletion
     garbage_proxies_t: Vec<Rc<dyn ProxyT>>,
     /// Listeners pending deletion
     garbage_listeners: Vec<Box<dyn Listener>>,
+    /// Created objects pending deletion
+    garbage_created: Vec<CreatedObject>,
     /// EventFd for signalling to [`wirehose`](`crate::wirehose`) that objects
     /// are pending deletion and that [`Self::collect_garbage()`] needs to be
     /// called
@@ -56,10 +75,13 @@ impl ProxyRegistry {
             clients: HashMap::new(),
             nodes: HashMap::new(),
             links: HashMap::new(),
+            ports: HashMap::new(),
+            created: HashMap::new(),
             metadatas: HashMap::new(),
             listeners: HashMap::new(),
             garbage_proxies_t: Vec::new(),
             garbage_listeners: Vec::new(),
+            garbage_created: Vec::new(),
             gc_fd,
         })
     }
@@ -69,6 +91,7 @@ impl ProxyRegistry {
     pub fn collect_garbage(&mut self) {
         self.garbage_listeners.clear();
         self.garbage_proxies_t.clear();
+        self.garbage_created.clear();
         let _ = self.gc_fd.read();
     }
 
@@ -148,6 +171,54 @@ impl ProxyRegistry {
         v.push(listener);
     }
 
+    /// Register a port and its listener, evicting any with the same ID.
+    pub fn add_port(
+        &mut self,
+        obj_id: ObjectId,
+        port: Rc<Port>,
+        listener: Box<dyn Listener>,
+    ) {
+        if let Some(old) = self.ports.insert(obj_id, port) {
+            self.garbage_proxies_t.push(old);
+            if let Some(listeners) = self.listeners.get_mut(&obj_id) {
+                self.garbage_listeners.append(listeners);
+            }
+            let _ = self.gc_fd.arm();
+        }
+
+        let v = self.listeners.entry(obj_id).or_default();
+        v.push(listener);
+    }
+
+    /// Keep an object created by wiremix alive until an object with its ID is
+    /// removed or [`Self::remove_created()`] is called.
+    pub fn add_created(
+        &mut self,
+        proxy_id: u32,
+        proxy: Rc<dyn ProxyT>,
+        listener: Box<dyn Listener>,
+        global_id: Rc<Cell<Option<ObjectId>>>,
+    ) {
+        self.created.insert(
+            proxy_id,
+            CreatedObject {
+                listener,
+                proxy,
+                global_id,
+            },
+        );
+    }
+
+    /// Stop keeping a created object alive, for example because the server
+    /// failed to create it, deferring deletion until
+    /// [`Self::collect_garbage()`] is called.
+    pub fn remove_created(&mut self, proxy_id: u32) {
+        if let Some(created) = self.created.remove(&proxy_id) {
+            self.garbage_created.push(created);
+            let _ = self.gc_fd.arm();
+        }
+    }
+
     /// Register metadata and its listener, evicting any with the same ID.
     pub fn add_metadata(
         &mut self,
@@ -202,9 +273,22 @@ impl ProxyRegistry {
             self.garbage_proxies_t.push(old);
             let _ = self.gc_fd.arm();
         }
+        if let Some(old) = self.ports.remove(&obj_id) {
+            self.garbage_proxies_t.push(old);
+            let _ = self.gc_fd.arm();
+        }
         if let Some(old) = self.metadatas.remove(&obj_id) {
             self.garbage_proxies_t.push(old);
             let _ = self.gc_fd.arm();
         }
+        let removed: Vec<_> = self
+            .created
+            .iter()
+            .filter(|(_, created)| created.global_id.get() == Some(obj_id))
+            .map(|(&proxy_id, _)| proxy_id)
+            .collect();
+        for proxy_id in removed {
+            self.remove_created(proxy_id);
+        }
     }
 }
@@ -18,7 +18,7 @@ use pipewire::{
 
 use crate::wirehose::{
     client, command::Command, device, event_sender::EventSender, execute, link,
-    metadata, node, proxy_registry::ProxyRegistry,
+    metadata, node, port, proxy_registry::ProxyRegistry,
     stream_registry::StreamRegistry, sync_registry::SyncRegistry,
     CommandSender, EventHandler, ObjectId, StateEvent,
 };
@@ -105,17 +105,20 @@ impl Drop for Session {
 /// Commands are sent asynchronously and are executed on the PipeWire monitoring thread.
 impl CommandSender for Session {
     /// Start capturing peak levels for a node. Set `capture_sink` to capture
-    /// from a source or a sink.
+    /// from a source or a sink, and `measure_levels` to also measure RMS,
+    /// true peak, and loudness.
     fn node_capture_start(
         &self,
         object_id: ObjectId,
         object_serial: u64,
         capture_sink: bool,
+        measure_levels: bool,
     ) {
         let _ = self.tx.send(Command::NodeCaptureStart(
             object_id,
             object_serial,
             capture_sink,
+            measure_levels,
         ));
     }
 
@@ -201,6 +204,54 @@ impl CommandSender for Session {
             object_id, subject, key, type_, value,
         ));
     }
+
+    /// Link an output port to an input port. Set `passive` so that the link
+    /// doesn't keep the nodes running, and `linger` to keep the link after
+    /// wiremix exits.
+    fn link_create(
+        &self,
+        output_port: ObjectId,
+        input_port: ObjectId,
+        passive: bool,
+        linger: bool,
+    ) {
+        let _ = self.tx.send(Command::LinkCreate {
+            output_port,
+            input_port,
+            passive,
+            linger,
+        });
+    }
+
+    /// Destroy a link.
+    fn link_destroy(&self, object_id: ObjectId) {
+        let _ = self.tx.send(Command::LinkDestroy(object_id));
+    }
+
+    /// Create a virtual sink or source backed by a null-audio-sink. It has a
+    /// channel for each of `positions`, such as "FL" and "FR". Set `linger`
+    /// to keep the node after wiremix exits.
+    fn node_create_virtual(
+        &self,
+        name: String,
+        description: String,
+        media_class: String,
+        positions: Vec<String>,
+        linger: bool,
+    ) {
+        let _ = self.tx.send(Command::NodeCreateVirtual {
+            name,
+            description,
+            media_class,
+            positions,
+            linger,
+        });
+    }
+
+    /// Destroy a node.
+    fn node_destroy(&self, object_id: ObjectId) {
+        let _ = self.tx.send(Command::NodeDestroy(object_id));
+    }
 }
 
 /// Monitors PipeWire.
@@ -393,6 +444,21 @@ fn monitor_pipewire(
                             None => None,
                         }
                     }
+                    ObjectType::Port => {
+                        let result =
+                            port::monitor_port(&registry, object, &sender);
+                        match result {
+                            Some((port, listener)) => {
+                                proxies.borrow_mut().add_port(
+                                    object_id,
+                                    Rc::clone(&port),
+                                    listener,
+                                );
+                                Some(port as Rc<dyn ProxyT>)
+                            }
+                            None => None,
+                        }
+                    }
                     ObjectType::Metadata => {
                         let result = metadata::monitor_metadata(
                             &registry, object, &sender,
@@ -448,12 +514,16 @@ fn monitor_pipewire(
     let proxies = Rc::clone(&proxies);
     let _receiver = rx.attach(main_loop.loop_(), {
         let core_weak = Rc::downgrade(&core);
+        let registry_weak = Rc::downgrade(&registry);
         let sender_weak = Rc::downgrade(&sender);
         let streams_weak = Rc::downgrade(&streams);
         move |command| {
             let Some(core) = core_weak.upgrade() else {
                 return;
             };
+            let Some(registry) = registry_weak.upgrade() else {
+                return;
+            };
             let Some(sender) = sender_weak.upgrade() else {
                 return;
             };
@@ -462,9 +532,10 @@ fn monitor_pipewire(
             };
             execute::execute_command(
                 &core,
+                &registry,
                 sender,
                 &mut streams.borrow_mut(),
-                &Rc::clone(&proxies).borrow(),
+                &proxies,
                 command,
             );
         }
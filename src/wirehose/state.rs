@@ -1,11 +1,15 @@
 //! Representation of PipeWire state.
 
 use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime};
 
-use crate::{db::db_channel::{DatabaseHandle, DbMessage}, wirehose::{
-    command::Command, media_class, CommandSender, ObjectId, PropertyStore,
-    StateEvent,
-}};
+use crate::db::db::ObjectIdentity;
+use crate::db::db_channel::{DatabaseHandle, DbMessage};
+use crate::db::Database;
+use crate::wirehose::{
+    command::Command, levels::Levels, media_class, CommandSender, ObjectId,
+    PortDirection, PropertyStore, StateEvent, StateEventKind,
+};
 
 #[derive(Debug, Clone)]
 pub struct Profile {
@@ -58,10 +62,21 @@ pub struct Node {
     pub volumes: Option<Vec<f32>>,
     pub mute: Option<bool>,
     pub peaks: Option<Vec<f32>>,
+    pub peak_holds: Option<Vec<PeakHold>>,
+    /// RMS, true peak and loudness measured by the capture stream
+    pub levels: Option<Levels>,
     pub rate: Option<u32>,
     pub positions: Option<Vec<u32>>,
 }
 
+/// The highest recent peak of a channel, for showing a peak-hold marker.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct PeakHold {
+    pub peak: f32,
+    /// Number of samples since the peak was reached
+    pub age: u64,
+}
+
 /// Trait for processing peaks in order to implement effects like ballistics.
 pub trait PeakProcessor {
     fn process_peak(
@@ -90,12 +105,14 @@ where
 
 impl Node {
     /// Update peaks with an optional peak processor for ballistics or other
-    /// effects.
+    /// effects. If peak_hold is provided, the highest processed peaks are
+    /// held for that long before falling back to the current peaks.
     pub fn update_peaks(
         &mut self,
         peaks: &Vec<f32>,
         samples: u32,
         peak_processor: Option<&dyn PeakProcessor>,
+        peak_hold: Option<Duration>,
     ) {
         let Some(rate) = self.rate else {
             return;
@@ -125,6 +142,49 @@ impl Node {
                 }
             }
         }
+
+        let Some(peak_hold) = peak_hold else {
+            self.peak_holds = None;
+            return;
+        };
+        let hold_samples = (peak_hold.as_secs_f64() * f64::from(rate)) as u64;
+
+        let holds = self.peak_holds.get_or_insert_with(Default::default);
+        if holds.len() != peaks_ref.len() {
+            holds.clear();
+        }
+        holds.resize(peaks_ref.len(), PeakHold::default());
+
+        for (hold, &peak) in holds.iter_mut().zip(peaks_ref.iter()) {
+            hold.age = hold.age.saturating_add(u64::from(samples));
+            if peak >= hold.peak || hold.age > hold_samples {
+                *hold = PeakHold { peak, age: 0 };
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Port {
+    pub object_id: ObjectId,
+    pub direction: PortDirection,
+    pub props: PropertyStore,
+}
+
+impl Port {
+    /// The node this port belongs to.
+    pub fn node_id(&self) -> Option<ObjectId> {
+        self.props.node_id().copied()
+    }
+
+    /// The channel position name, such as "FL", if this is an audio port.
+    pub fn channel(&self) -> Option<&String> {
+        self.props.audio_channel()
+    }
+
+    /// Whether this is a monitor port, which outputs what a sink plays.
+    pub fn is_monitor(&self) -> bool {
+        self.props.port_monitor().copied().unwrap_or_default()
     }
 }
 
@@ -132,6 +192,8 @@ impl Node {
 pub struct Link {
     pub output_id: ObjectId,
     pub input_id: ObjectId,
+    pub output_port_id: ObjectId,
+    pub input_port_id: ObjectId,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -156,14 +218,49 @@ pub struct State {
     pub nodes: HashMap<ObjectId, Node>,
     pub devices: HashMap<ObjectId, Device>,
     pub links: HashMap<ObjectId, Link>,
+    pub ports: HashMap<ObjectId, Port>,
     pub metadatas: HashMap<ObjectId, Metadata>,
     pub metadatas_by_name: HashMap<String, ObjectId>,
     peak_processor: Option<Box<dyn PeakProcessor>>,
+    peak_hold: Option<Duration>,
     capturing: Option<HashSet<ObjectId>>,
     database: Option<DatabaseHandle>,
+    journal: HashSet<StateEventKind>,
+    persist_peaks: bool,
+    level_history: bool,
+    measure_levels: bool,
 }
 
 impl State {
+    /// Build a state from the last-known objects saved in a database.
+    pub async fn from_database(database: &Database) -> anyhow::Result<Self> {
+        let mut state = Self::default();
+
+        for client in database.load_clients().await? {
+            state.clients.insert(client.object_id, client);
+        }
+        for node in database.load_nodes().await? {
+            state.nodes.insert(node.object_id, node);
+        }
+        for device in database.load_devices().await? {
+            state.devices.insert(device.object_id, device);
+        }
+        state.links.extend(database.load_links().await?);
+        for port in database.load_ports().await? {
+            state.ports.insert(port.object_id, port);
+        }
+        for metadata in database.load_metadatas().await? {
+            if let Some(metadata_name) = &metadata.metadata_name {
+                state
+                    .metadatas_by_name
+                    .insert(metadata_name.clone(), metadata.object_id);
+            }
+            state.metadatas.insert(metadata.object_id, metadata);
+        }
+
+        Ok(state)
+    }
+
     /// Provide a peak processor for setting peak levels.
     pub fn with_peak_processor(
         mut self,
@@ -173,19 +270,113 @@ impl State {
         self
     }
 
+    /// Hold the highest peaks for a while, for peak-hold markers.
+    pub fn with_peak_hold(mut self, peak_hold: Option<Duration>) -> Self {
+        self.peak_hold = peak_hold;
+        self
+    }
+
     /// Enable stream capturing.
     pub fn with_capture(mut self, enable: bool) -> Self {
         self.capturing = enable.then_some(Default::default());
         self
     }
 
+    /// Measure RMS, true peak, and loudness levels while capturing, which
+    /// costs more than measuring sample peaks alone.
+    pub fn with_measured_levels(mut self, enable: bool) -> Self {
+        self.measure_levels = enable;
+        self
+    }
+
     /// Set database for persistence.
     pub fn with_database(mut self, database: DatabaseHandle) -> Self {
         self.database = Some(database);
         self
     }
 
+    /// Include peak levels when persisting nodes. Peaks change on every
+    /// captured buffer, so this is off by default.
+    pub fn with_persisted_peaks(mut self, enable: bool) -> Self {
+        self.persist_peaks = enable;
+        self
+    }
+
+    /// Record downsampled peak levels in the database's level history.
+    pub fn with_level_history(mut self, enable: bool) -> Self {
+        self.level_history = enable;
+        self
+    }
+
+    /// Set which kinds of events are appended to the database's event
+    /// journal.
+    pub fn with_journal(mut self, kinds: HashSet<StateEventKind>) -> Self {
+        self.journal = kinds;
+        self
+    }
+
+    /// Append an event to the database's event journal if its kind is
+    /// enabled.
+    fn journal_event(&self, event: &StateEvent) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        let kind = event.kind();
+        if !self.journal.contains(&kind) {
+            return;
+        }
+
+        match serde_json::to_string(event) {
+            Ok(payload_json) => {
+                let _ = db.send(DbMessage::AppendEvent {
+                    recorded_at: SystemTime::now(),
+                    kind,
+                    object_id: event.object_id(),
+                    identity: self.event_identity(event),
+                    payload_json,
+                });
+            }
+            Err(e) => log::error!("Failed to serialize {kind:?} event: {e}"),
+        }
+    }
+
+    /// Identifies the object an event pertains to for the journal. Property
+    /// events carry the object's latest properties. Other events use those of
+    /// the object as it is before the event is applied, so removals are
+    /// still identified.
+    fn event_identity(&self, event: &StateEvent) -> ObjectIdentity {
+        let object_id = event.object_id();
+        match event {
+            StateEvent::NodeProperties { props, .. } => {
+                ObjectIdentity::node(props)
+            }
+            StateEvent::DeviceProperties { props, .. } => {
+                ObjectIdentity::device(props)
+            }
+            StateEvent::ClientProperties { props, .. } => {
+                ObjectIdentity::client(props)
+            }
+            _ => {
+                if let Some(node) = self.nodes.get(&object_id) {
+                    ObjectIdentity::node(&node.props)
+                } else if let Some(device) = self.devices.get(&object_id) {
+                    ObjectIdentity::device(&device.props)
+                } else if let Some(client) = self.clients.get(&object_id) {
+                    ObjectIdentity::client(&client.props)
+                } else {
+                    ObjectIdentity::default()
+                }
+            }
+        }
+    }
 
+    /// Called once the initial objects have been enumerated. Database rows
+    /// for objects which no longer exist are marked absent.
+    pub fn ready(&self) {
+        if let Some(db) = &self.database {
+            let _ = db.send(DbMessage::MarkAbsent);
+        }
+    }
 
     /// Helper methods to persist state changes
     fn persist_client(&self, object_id: ObjectId) {
@@ -199,7 +390,13 @@ impl State {
     fn persist_node(&self, object_id: ObjectId) {
         if let Some(db) = &self.database {
             if let Some(node) = self.nodes.get(&object_id) {
-                let _ = db.send(DbMessage::UpsertNode(node.clone()));
+                let mut node = node.clone();
+                if !self.persist_peaks {
+                    node.peaks = None;
+                }
+                node.peak_holds = None;
+                node.levels = None;
+                let _ = db.send(DbMessage::UpsertNode(node));
             }
         }
     }
@@ -220,6 +417,14 @@ impl State {
         }
     }
 
+    fn persist_port(&self, object_id: ObjectId) {
+        if let Some(db) = &self.database {
+            if let Some(port) = self.ports.get(&object_id) {
+                let _ = db.send(DbMessage::UpsertPort(port.clone()));
+            }
+        }
+    }
+
     fn persist_metadata(&self, object_id: ObjectId) {
         if let Some(db) = &self.database {
             if let Some(metadata) = self.metadatas.get(&object_id) {
@@ -233,6 +438,8 @@ impl State {
     pub fn update(&mut self, wirehose: &dyn CommandSender, event: StateEvent) {
         let mut commands = Vec::<Command>::new();
 
+        self.journal_event(&event);
+
         match event {
             StateEvent::ClientProperties { object_id, props } => {
                 self.client_entry(object_id).props = props;
@@ -331,8 +538,34 @@ impl State {
                         ..Default::default()
                     });
                 let peak_processor = self.peak_processor.as_deref();
-                node.update_peaks(&peaks, samples, peak_processor);
-                self.persist_node(object_id);
+                node.update_peaks(
+                    &peaks,
+                    samples,
+                    peak_processor,
+                    self.peak_hold,
+                );
+                if let Some(db) =
+                    self.database.as_ref().filter(|_| self.level_history)
+                {
+                    let _ = db.send(DbMessage::RecordLevels {
+                        recorded_at: SystemTime::now(),
+                        object_id,
+                        natural_key: crate::db::db::node_key(&node.props),
+                        peaks,
+                    });
+                }
+                if self.persist_peaks {
+                    self.persist_node(object_id);
+                }
+            }
+            StateEvent::NodeLevels { object_id, levels } => {
+                self.nodes
+                    .entry(object_id)
+                    .or_insert_with(|| Node {
+                        object_id,
+                        ..Default::default()
+                    })
+                    .levels = Some(levels);
             }
             StateEvent::NodeRate { object_id, rate } => {
                 self.node_entry(object_id).rate = Some(rate);
@@ -358,10 +591,27 @@ impl State {
                 self.node_entry(object_id).volumes = Some(volumes);
                 self.persist_node(object_id);
             }
+            StateEvent::PortProperties {
+                object_id,
+                direction,
+                props,
+            } => {
+                self.ports.insert(
+                    object_id,
+                    Port {
+                        object_id,
+                        direction,
+                        props,
+                    },
+                );
+                self.persist_port(object_id);
+            }
             StateEvent::Link {
                 object_id,
                 output_id,
                 input_id,
+                output_port_id,
+                input_port_id,
             } => {
                 if !self.inputs(input_id).contains(&output_id) {
                     if let Some(node) = self.nodes.get(&input_id) {
@@ -374,6 +624,8 @@ impl State {
                     Link {
                         output_id,
                         input_id,
+                        output_port_id,
+                        input_port_id,
                     },
                 );
                 self.persist_link(object_id);
@@ -422,9 +674,11 @@ impl State {
             }
             StateEvent::StreamStopped { object_id } => {
                 // It's likely that the node doesn't exist anymore.
-                self.nodes
-                    .entry(object_id)
-                    .and_modify(|node| node.peaks = None);
+                self.nodes.entry(object_id).and_modify(|node| {
+                    node.peaks = None;
+                    node.peak_holds = None;
+                    node.levels = None;
+                });
                 self.persist_node(object_id);
             }
             StateEvent::Removed { object_id } => {
@@ -441,6 +695,7 @@ impl State {
 
                 self.devices.remove(&object_id);
                 self.clients.remove(&object_id);
+                self.ports.remove(&object_id);
                 if let Some(node) = self.nodes.remove(&object_id) {
                     commands.extend(self.on_removed(&node));
                 }
@@ -465,12 +720,14 @@ impl State {
                         obj_id,
                         object_serial,
                         capture_sink,
+                        measure_levels,
                     ) => {
                         capturing.insert(obj_id);
                         wirehose.node_capture_start(
                             obj_id,
                             object_serial,
                             capture_sink,
+                            measure_levels,
                         );
                     }
                     Command::NodeCaptureStop(obj_id) => {
@@ -537,6 +794,23 @@ impl State {
             .collect()
     }
 
+    /// Returns a node's ports in the given direction, ordered by port ID.
+    pub fn node_ports(
+        &self,
+        node_id: ObjectId,
+        direction: PortDirection,
+    ) -> Vec<&Port> {
+        let mut ports: Vec<_> = self
+            .ports
+            .values()
+            .filter(|port| {
+                port.direction == direction && port.node_id() == Some(node_id)
+            })
+            .collect();
+        ports.sort_by_key(|port| port.object_id);
+        ports
+    }
+
     /// Call when a node's capture eligibility might have changed.
     fn on_node(&self, node: &Node) -> Option<Command> {
         self.capturing.as_ref()?;
@@ -618,6 +892,7 @@ impl State {
             node.object_id,
             *object_serial,
             capture_sink,
+            self.measure_levels,
         ))
     }
 
@@ -676,6 +951,51 @@ mod tests {
         assert!(state.get_metadata_by_name(&metadata_name).is_none());
     }
 
+    fn port_event(
+        object_id: u32,
+        node_id: u32,
+        direction: PortDirection,
+        channel: &str,
+    ) -> StateEvent {
+        let mut props = PropertyStore::default();
+        props.set_node_id(ObjectId::from_raw_id(node_id));
+        props.set_audio_channel(String::from(channel));
+        StateEvent::PortProperties {
+            object_id: ObjectId::from_raw_id(object_id),
+            direction,
+            props,
+        }
+    }
+
+    #[test]
+    fn state_node_ports() {
+        let mut state = State::default();
+        let wirehose = mock::WirehoseHandle::default();
+        let node_id = ObjectId::from_raw_id(10);
+
+        state.update(&wirehose, port_event(12, 10, PortDirection::Input, "FR"));
+        state.update(&wirehose, port_event(11, 10, PortDirection::Input, "FL"));
+        state
+            .update(&wirehose, port_event(13, 10, PortDirection::Output, "FL"));
+        state.update(&wirehose, port_event(21, 20, PortDirection::Input, "FL"));
+
+        let channels: Vec<_> = state
+            .node_ports(node_id, PortDirection::Input)
+            .iter()
+            .filter_map(|port| port.channel().cloned())
+            .collect();
+        assert_eq!(channels, vec!["FL", "FR"]);
+        assert_eq!(state.node_ports(node_id, PortDirection::Output).len(), 1);
+
+        state.update(
+            &wirehose,
+            StateEvent::Removed {
+                object_id: ObjectId::from_raw_id(11),
+            },
+        );
+        assert_eq!(state.node_ports(node_id, PortDirection::Input).len(), 1);
+    }
+
     fn get_metadata_properties<'a>(
         state: &'a State,
         object_id: &ObjectId,
@@ -804,4 +1124,174 @@ mod tests {
         assert!(get_metadata_properties(&state, &object_id, 0).is_empty());
         assert!(!get_metadata_properties(&state, &object_id, 1).is_empty());
     }
+
+    #[tokio::test]
+    async fn state_from_database() {
+        let db_path = std::env::temp_dir().join("test_state_from_database.db");
+        let _ = std::fs::remove_file(&db_path);
+        let database = Database::new(db_path.to_str().unwrap()).await.unwrap();
+
+        let device_id = ObjectId::from_raw_id(40);
+        let mut device = Device {
+            object_id: device_id,
+            profile_index: Some(1),
+            ..Default::default()
+        };
+        device.profiles.insert(
+            1,
+            Profile {
+                index: 1,
+                description: String::from("Analog Stereo Output"),
+                available: true,
+                classes: vec![(String::from("Audio/Sink"), vec![0])],
+            },
+        );
+        device.routes.insert(
+            0,
+            Route {
+                index: 3,
+                device: 0,
+                profiles: vec![1],
+                description: String::from("Headphones"),
+                available: true,
+                volumes: vec![0.5, 0.5],
+                mute: false,
+            },
+        );
+        device.enum_routes.insert(
+            3,
+            EnumRoute {
+                index: 3,
+                description: String::from("Headphones"),
+                available: true,
+                profiles: vec![1],
+                devices: vec![0],
+            },
+        );
+        database.upsert_device(&device).await.unwrap();
+
+        let node_id = ObjectId::from_raw_id(57);
+        database
+            .upsert_node(&Node {
+                object_id: node_id,
+                volumes: Some(vec![0.25, 0.75]),
+                mute: Some(true),
+                rate: Some(48000),
+                positions: Some(vec![3, 4]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        database
+            .upsert_link(
+                ObjectId::from_raw_id(90),
+                &Link {
+                    output_id: node_id,
+                    input_id: ObjectId::from_raw_id(58),
+                    output_port_id: ObjectId::from_raw_id(91),
+                    input_port_id: ObjectId::from_raw_id(92),
+                },
+            )
+            .await
+            .unwrap();
+
+        let mut props = PropertyStore::default();
+        props.set_node_id(node_id);
+        props.set_audio_channel(String::from("FL"));
+        database
+            .upsert_port(&Port {
+                object_id: ObjectId::from_raw_id(91),
+                direction: PortDirection::Output,
+                props,
+            })
+            .await
+            .unwrap();
+
+        let mut metadata = Metadata {
+            object_id: ObjectId::from_raw_id(30),
+            metadata_name: Some(String::from("default")),
+            ..Default::default()
+        };
+        metadata
+            .properties
+            .entry(0)
+            .or_default()
+            .insert(String::from("key"), String::from("value"));
+        database.upsert_metadata(&metadata).await.unwrap();
+
+        let state = State::from_database(&database).await.unwrap();
+
+        let device = state.devices.get(&device_id).unwrap();
+        assert_eq!(device.profile_index, Some(1));
+        assert_eq!(device.profiles[&1].classes[0].0, "Audio/Sink");
+        assert_eq!(device.routes[&0].index, 3);
+        assert_eq!(device.routes[&0].volumes, vec![0.5, 0.5]);
+        assert_eq!(device.enum_routes[&3].devices, vec![0]);
+
+        let node = state.nodes.get(&node_id).unwrap();
+        assert_eq!(node.volumes, Some(vec![0.25, 0.75]));
+        assert_eq!(node.mute, Some(true));
+        assert_eq!(node.rate, Some(48000));
+        assert_eq!(node.positions, Some(vec![3, 4]));
+
+        assert_eq!(state.outputs(node_id), vec![ObjectId::from_raw_id(58)]);
+        let link = &state.links[&ObjectId::from_raw_id(90)];
+        assert_eq!(link.output_port_id, ObjectId::from_raw_id(91));
+        assert_eq!(link.input_port_id, ObjectId::from_raw_id(92));
+
+        let ports = state.node_ports(node_id, PortDirection::Output);
+        assert_eq!(ports.len(), 1);
+        assert_eq!(ports[0].channel().map(String::as_str), Some("FL"));
+
+        let metadata = state.get_metadata_by_name("default").unwrap();
+        assert_eq!(metadata.properties[&0]["key"], "value");
+
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[test]
+    fn node_peak_hold() {
+        let mut node = Node {
+            rate: Some(1000),
+            ..Default::default()
+        };
+        let peak_hold = Some(Duration::from_millis(100));
+
+        node.update_peaks(&vec![0.8], 10, None, peak_hold);
+        node.update_peaks(&vec![0.2], 50, None, peak_hold);
+        assert_eq!(
+            node.peak_holds,
+            Some(vec![PeakHold { peak: 0.8, age: 50 }])
+        );
+
+        // The hold falls back to the current peak after 100 ms
+        node.update_peaks(&vec![0.3], 60, None, peak_hold);
+        assert_eq!(node.peak_holds, Some(vec![PeakHold { peak: 0.3, age: 0 }]));
+
+        node.update_peaks(&vec![0.3], 60, None, None);
+        assert_eq!(node.peak_holds, None);
+    }
+
+    #[test]
+    fn capture_measures_levels_only_when_enabled() {
+        let mut node = Node {
+            object_id: ObjectId::from_raw_id(10),
+            ..Default::default()
+        };
+        node.props.set_object_serial(57);
+
+        let measures_levels = |state: &State| {
+            matches!(
+                state.start_capture_command(&node),
+                Some(Command::NodeCaptureStart(_, 57, _, true))
+            )
+        };
+        assert!(!measures_levels(&State::default().with_capture(true)));
+        assert!(measures_levels(
+            &State::default()
+                .with_capture(true)
+                .with_measured_levels(true)
+        ));
+    }
 }
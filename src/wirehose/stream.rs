@@ -15,12 +15,19 @@ use libspa::{
 };
 
 use crate::wirehose::event_sender::EventSender;
+use crate::wirehose::levels::{self, LevelMeter};
 use crate::wirehose::{ObjectId, StateEvent};
 
 #[derive(Default)]
 pub struct StreamData {
     format: AudioInfoRaw,
     cursor_move: bool,
+    /// Whether to measure levels as well as peaks
+    measure_levels: bool,
+    /// Measures levels in the current format
+    level_meter: Option<LevelMeter>,
+    /// Decoded samples of the current buffer, reused between buffers
+    samples: Vec<f32>,
 }
 
 pub fn capture_node(
@@ -29,6 +36,7 @@ pub fn capture_node(
     object_id: ObjectId,
     serial: &str,
     capture_sink: bool,
+    measure_levels: bool,
 ) -> Option<(Rc<Stream>, StreamListener<StreamData>)> {
     let mut props = properties! {
         *pipewire::keys::TARGET_OBJECT => String::from(serial),
@@ -42,6 +50,9 @@ pub fn capture_node(
     let data = StreamData {
         format: Default::default(),
         cursor_move: false,
+        measure_levels,
+        level_meter: None,
+        samples: Vec::new(),
     };
 
     let stream = Stream::new(core, "wiremix-capture", props).ok()?;
@@ -76,6 +87,17 @@ pub fn capture_node(
                 // call a helper function to parse the format for us.
                 let _ = user_data.format.parse(param);
 
+                if user_data.measure_levels {
+                    let n_channels = user_data.format.channels() as usize;
+                    let weights = user_data.format.position()
+                        [..n_channels.min(64)]
+                        .iter()
+                        .map(|&position| levels::channel_weight(position))
+                        .collect();
+                    user_data.level_meter =
+                        Some(LevelMeter::new(user_data.format.rate(), weights));
+                }
+
                 let Some(sender) = sender_weak.upgrade() else {
                     return;
                 };
@@ -105,17 +127,29 @@ pub fn capture_node(
                 let n_samples =
                     data.chunk().size() / (mem::size_of::<f32>() as u32);
 
-                if let Some(samples) = data.data() {
+                if let Some(bytes) = data.data() {
+                    let end = (n_samples as usize * mem::size_of::<f32>())
+                        .min(bytes.len());
+                    let samples = &mut user_data.samples;
+                    samples.clear();
+                    samples.extend(
+                        bytes[..end].chunks_exact(mem::size_of::<f32>()).map(
+                            |sample| {
+                                f32::from_le_bytes(
+                                    sample.try_into().unwrap_or([0; 4]),
+                                )
+                            },
+                        ),
+                    );
+
                     let mut peaks = Vec::new();
                     for c in 0..n_channels {
                         let mut max: f32 = 0.0;
-                        for n in (c..n_samples).step_by(n_channels as usize) {
-                            let start = n as usize * mem::size_of::<f32>();
-                            let end = start + mem::size_of::<f32>();
-                            let chan = &samples[start..end];
-                            let f = f32::from_le_bytes(
-                                chan.try_into().unwrap_or([0; 4]),
-                            );
+                        for f in samples
+                            .iter()
+                            .skip(c as usize)
+                            .step_by(n_channels as usize)
+                        {
                             max = max.max(f.abs());
                         }
 
@@ -126,6 +160,15 @@ pub fn capture_node(
                         peaks,
                         samples: n_samples,
                     });
+
+                    if let Some(levels) = user_data
+                        .level_meter
+                        .as_mut()
+                        .and_then(|meter| meter.process(samples))
+                    {
+                        sender
+                            .send(StateEvent::NodeLevels { object_id, levels });
+                    }
                     user_data.cursor_move = true;
                 }
             }